@@ -1,32 +1,435 @@
-use directories::ProjectDirs;
+use directories::{BaseDirs, ProjectDirs};
+use globset::GlobBuilder;
+use ignore::gitignore::{Gitignore, GitignoreBuilder};
 use ignore::WalkBuilder;
+use ratatui::crossterm::event::KeyEvent;
+use ratatui::layout::{Position, Rect};
 use serde::{Deserialize, Serialize};
-use std::collections::HashSet;
+use std::cell::{Cell, Ref, RefCell};
+use std::collections::hash_map::DefaultHasher;
+use std::collections::{BTreeMap, HashMap, HashSet, VecDeque};
 use std::fs;
-use std::path::PathBuf;
+use std::hash::{Hash, Hasher};
+use std::io;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::{self, Receiver, TryRecvError};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant, SystemTime};
+use zip::write::SimpleFileOptions;
+use zip::{CompressionMethod, ZipWriter};
+
+use crate::config::{CollectionView, Config};
+use crate::history::CopyHistory;
+use crate::input::TextInput;
+use crate::lang::{extension_glyph, extension_symbol, extension_to_lang, shebang_extension};
+use crate::minify::minify_contents;
+use crate::output::{file_metadata_line, truncate_contents, OutputFormat, OutputMode};
+use crate::search::{fuzzy_match, fuzzy_score};
+use crate::session::Session;
+use crate::settings::{Setting, MAX_FILE_BYTES_PRESETS, SETTINGS};
+use crate::sizes::DirSizes;
+use crate::sort::SortMode;
+use crate::storage::write_json_atomically;
+use crate::theme::{Theme, BUILT_IN_THEMES};
+use crate::watch::DirWatcher;
+
+// A file in a collection, which can be left out of copies without removing it
+#[derive(Serialize, Deserialize, Clone)]
+#[serde(from = "StoredCollectionFile")]
+pub struct CollectionFile {
+    pub path: PathBuf,
+    pub enabled: bool,
+}
+
+impl CollectionFile {
+    pub fn new(path: PathBuf) -> CollectionFile {
+        CollectionFile {
+            path,
+            enabled: true,
+        }
+    }
+}
+
+// How a collection file is stored; older collections stored only the path
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum StoredCollectionFile {
+    Path(PathBuf),
+    File { path: PathBuf, enabled: bool },
+}
+
+impl From<StoredCollectionFile> for CollectionFile {
+    fn from(stored: StoredCollectionFile) -> Self {
+        match stored {
+            StoredCollectionFile::Path(path) => CollectionFile::new(path),
+            StoredCollectionFile::File { path, enabled } => CollectionFile { path, enabled },
+        }
+    }
+}
 
 // Represents a collection of files
-#[derive(Serialize, Deserialize)]
+#[derive(Serialize, Deserialize, Clone)]
 pub struct Collection {
     pub name: String,
-    pub files: Vec<PathBuf>,
+    pub files: Vec<CollectionFile>,
     pub num_files: usize,
     pub timestamp: chrono::DateTime<chrono::Local>,
+    #[serde(default)]
+    pub tags: Vec<String>,
+    // Modification time of each file when it was added
+    #[serde(default)]
+    pub modified: HashMap<PathBuf, SystemTime>,
+    // Copy format overriding the global default
+    #[serde(default)]
+    pub format: Option<OutputFormat>,
+    // What the collection is for, put at the top of copied output
+    #[serde(default)]
+    pub description: String,
+    // Pinned collections are listed first
+    #[serde(default)]
+    pub pinned: bool,
+    // Directory the paths under it are saved relative to, so the collection
+    // still works after the directory moves or on another machine
+    #[serde(default)]
+    pub root: Option<PathBuf>,
+}
+
+impl Collection {
+    // Paths of all files, in order
+    pub fn paths(&self) -> Vec<PathBuf> {
+        self.files.iter().map(|file| file.path.clone()).collect()
+    }
+
+    // Paths of the files that are copied, leaving out disabled ones
+    pub fn enabled_paths(&self) -> Vec<PathBuf> {
+        self.files
+            .iter()
+            .filter(|file| file.enabled)
+            .map(|file| file.path.clone())
+            .collect()
+    }
+
+    // A copy as it is saved, with the paths under the root made relative to it.
+    // Paths outside the root stay absolute
+    fn to_stored(&self) -> Collection {
+        let mut stored = self.clone();
+        if let Some(root) = &self.root {
+            let relative = |path: &PathBuf| {
+                path.strip_prefix(root)
+                    .map_or(path.clone(), Path::to_path_buf)
+            };
+            for file in &mut stored.files {
+                file.path = relative(&file.path);
+            }
+            stored.modified = self
+                .modified
+                .iter()
+                .map(|(path, &time)| (relative(path), time))
+                .collect();
+        }
+        stored
+    }
+
+    // Turn the relative paths of a loaded collection back into absolute ones
+    // under its root
+    fn resolve_paths(&mut self) {
+        let Some(root) = &self.root else {
+            return;
+        };
+        // Joining an absolute path leaves it as it is
+        for file in &mut self.files {
+            file.path = root.join(&file.path);
+        }
+        self.modified = self
+            .modified
+            .drain()
+            .map(|(path, time)| (root.join(path), time))
+            .collect();
+    }
+
+    // The directory the files were in, if it looks to have moved to the base
+    // directory: it is gone, has the base directory's name, and every missing
+    // file turns up at the same place under the base directory
+    fn moved_from(&self, base_dir: &Path) -> Option<PathBuf> {
+        let missing: Vec<&PathBuf> = self
+            .files
+            .iter()
+            .map(|file| &file.path)
+            .filter(|path| !path.exists())
+            .collect();
+        let first = missing.first()?;
+        let name = base_dir.file_name()?;
+        let candidates: Vec<&Path> = match &self.root {
+            Some(root) => vec![root],
+            // The deepest matching ancestor comes first
+            None => first.ancestors().skip(1).collect(),
+        };
+        candidates
+            .into_iter()
+            .filter(|old| *old != base_dir && old.file_name() == Some(name) && !old.exists())
+            .find(|old| {
+                missing.iter().all(|path| {
+                    path.strip_prefix(old)
+                        .is_ok_and(|rest| base_dir.join(rest).exists())
+                })
+            })
+            .map(Path::to_path_buf)
+    }
+
+    // Point the paths under the old directory at the same places under the base
+    // directory
+    fn rebase(&mut self, old: &Path, base_dir: &Path) {
+        let rebased = |path: &Path| {
+            path.strip_prefix(old)
+                .map_or(path.to_path_buf(), |rest| base_dir.join(rest))
+        };
+        for file in &mut self.files {
+            file.path = rebased(&file.path);
+        }
+        self.modified = self
+            .modified
+            .drain()
+            .map(|(path, time)| (rebased(&path), time))
+            .collect();
+        if self.root.as_deref() == Some(old) {
+            self.root = Some(base_dir.to_path_buf());
+        }
+    }
+}
+
+// What happened to the files while assembling copied text
+#[derive(Default)]
+struct CopyCounts {
+    // Files that had a git diff in diff mode
+    diffs: usize,
+    // Files left out because the token budget was reached
+    dropped: usize,
+}
+
+// A file's contents as copied, before being wrapped in a block
+struct FileParts {
+    header: String,
+    meta: Option<String>,
+    // Extension of the file's language, which picks the fence tag
+    extension: String,
+    contents: String,
+}
+
+// Sorted entries of a directory and their file types
+type Listing = (Vec<PathBuf>, HashMap<PathBuf, fs::FileType>);
+
+// Directories whose cursor positions are remembered before most are forgotten
+const CURSOR_POSITIONS_LIMIT: usize = 1000;
+
+// How long collections must stay unchanged before they are written
+const SAVE_DEBOUNCE: Duration = Duration::from_millis(500);
+
+// Labels shown next to visible files in quick jump mode, home row first
+pub const QUICK_JUMP_LABELS: &str = "asdfghjklqwertyuiopzxcvbnm";
+
+// Summary of the files in a collection
+#[derive(Clone)]
+pub struct CollectionStats {
+    pub file_count: usize,
+    pub total_size: u64,
+    pub largest_file: Option<(PathBuf, u64)>,
+    // Number of files per extension, most common first
+    pub type_breakdown: Vec<(String, usize)>,
+    pub estimated_tokens: usize,
+}
+
+// Rough token estimate for text of the given size, about 4 bytes per token
+pub fn estimate_tokens(bytes: u64) -> usize {
+    bytes.div_ceil(4) as usize
+}
+
+// Pause in typing before the fuzzy jump ranks its candidates again
+const FUZZY_JUMP_DEBOUNCE: Duration = Duration::from_millis(80);
+
+// Most matches the fuzzy jump lists
+const FUZZY_JUMP_LIMIT: usize = 50;
+
+// Most notifications shown at once; older ones make room for new ones
+const NOTIFICATION_LIMIT: usize = 4;
+
+// Largest count that can be typed before a motion
+const MAX_COUNT: usize = 9999;
+
+// Splits between the collection list and the selected files offered when cycling
+const COLLECTIONS_SPLIT_PRESETS: [u16; 5] = [50, 75, 100, 25, 0];
+
+// Bounds of the files pane width so neither pane collapses
+pub const MIN_SPLIT_RATIO: u16 = 20;
+pub const MAX_SPLIT_RATIO: u16 = 80;
+
+// Screen areas of the pane lists from the last draw, used to map mouse clicks
+#[derive(Default)]
+pub struct PaneAreas {
+    pub files: Cell<Rect>,
+    pub collections: Cell<Rect>,
+    pub selected_files: Cell<Rect>,
 }
 
+// Maximum time between two clicks on the same row to count as a double-click
+const DOUBLE_CLICK_INTERVAL: Duration = Duration::from_millis(400);
+
 // Enum representing which pane is currently focused
+#[allow(clippy::enum_variant_names)]
+#[derive(Clone, Copy, PartialEq)]
 pub enum FocusedPane {
     FilesPane,
     CollectionsPane,
     SelectedFilesPane,
 }
 
+// Enum representing which text prompt is currently shown
+#[derive(Clone, Copy)]
+pub enum Prompt {
+    SelectByGlob,
+    LineRange,
+    Grep,
+    EditTemplate,
+    EditSuffix,
+    ToggleTag,
+    FilterByTag,
+    MergeCollections { delete_sources: bool },
+    ExportCollection,
+    ExportZip,
+    TokenBudget,
+    EditDescription,
+    JumpToDirectory,
+    FuzzyJump,
+    SaveCollection,
+    RenameFile,
+}
+
+// A row of the selection review screen
+#[derive(Clone, PartialEq)]
+pub enum ReviewRow {
+    // A parent directory and how many selected items it holds
+    Group(PathBuf, usize),
+    // A selected item inside the group above it
+    Item(PathBuf),
+}
+
+// Enum representing which list popup is currently shown
+#[derive(Clone, Copy)]
+pub enum Popup {
+    RecentFiles,
+    GrepResults,
+    Bookmarks,
+    MoveToCollection,
+    CopyHistory,
+    Selection,
+}
+
+// How serious a notification is, which sets its color
+#[derive(Clone, Copy, PartialEq)]
+pub enum NotificationLevel {
+    Info,
+    Warning,
+    Error,
+}
+
+// A message shown above the footer until its own expiry
+pub struct Notification {
+    pub text: String,
+    pub level: NotificationLevel,
+    pub expiry: Instant,
+}
+
+// Candidates and ranked matches of the fuzzy directory jump
+pub struct FuzzyJump {
+    // Directories under the base directory, relative to it
+    candidates: Vec<String>,
+    // Query the matches were ranked for, and when the typed text last changed
+    // while waiting for typing to pause
+    ranked_query: Option<String>,
+    changed_at: Option<Instant>,
+    // Matching candidates, best first, with the positions of the matched characters
+    pub matches: Vec<(String, Vec<usize>)>,
+    pub index: usize,
+}
+
+impl FuzzyJump {
+    // Rank the candidates matching the query, best first
+    fn rank(&mut self, query: &str) {
+        let mut matches: Vec<(i64, &String, Vec<usize>)> = self
+            .candidates
+            .iter()
+            .filter_map(|candidate| {
+                let positions = fuzzy_match(candidate, query)?;
+                Some((fuzzy_score(candidate, &positions), candidate, positions))
+            })
+            .collect();
+        matches.sort_by(|a, b| b.0.cmp(&a.0).then_with(|| a.1.cmp(b.1)));
+        self.matches = matches
+            .into_iter()
+            .take(FUZZY_JUMP_LIMIT)
+            .map(|(_, candidate, positions)| (candidate.clone(), positions))
+            .collect();
+        self.index = 0;
+        self.ranked_query = Some(query.to_string());
+        self.changed_at = None;
+    }
+}
+
+// Settings a recursive walk follows, split out of the app so a walk can run on
+// another thread
+#[derive(Clone)]
+struct WalkOptions {
+    max_depth: usize,
+    follow_symlinks: bool,
+    respect_gitignore: bool,
+    show_hidden: bool,
+    pray_ignore: Gitignore,
+}
+
+type TokenCache = Arc<Mutex<HashMap<PathBuf, (SystemTime, usize)>>>;
+
+// A count of the selection's tokens running on a background thread
+struct TokenCount {
+    result: Receiver<usize>,
+    // Tells the thread to stop early, set when the count is dropped
+    cancel: Arc<AtomicBool>,
+}
+
+impl Drop for TokenCount {
+    fn drop(&mut self) {
+        self.cancel.store(true, Ordering::Relaxed);
+    }
+}
+
+// Most content search matches kept, so a search for something common can't
+// fill memory
+const GREP_RESULTS_LIMIT: usize = 10_000;
+
+// A content search scanning files on a background thread, streaming matches back
+pub struct GrepSearch {
+    pub term: String,
+    results: Receiver<PathBuf>,
+    // Tells the thread to stop early, set when the search is dropped
+    cancel: Arc<AtomicBool>,
+    // Whether every file has been scanned
+    pub done: bool,
+}
+
+impl Drop for GrepSearch {
+    fn drop(&mut self) {
+        self.cancel.store(true, Ordering::Relaxed);
+    }
+}
+
 // The main application state
 pub struct App {
     // Current directory path
     pub current_dir: PathBuf,
     // List of directory entries in the current directory
     pub directory_entries: Vec<PathBuf>,
+    // File types of the directory entries, read once along with the listing
+    pub entry_types: HashMap<PathBuf, fs::FileType>,
     // Index of the selected item in the files pane
     pub selected_file_index: usize,
     // Index of the selected collection
@@ -35,289 +438,2795 @@ pub struct App {
     pub selected_file_in_collection_index: usize,
     // Store selected items in the current directory
     pub selected_items: HashSet<PathBuf>,
+    // Estimated tokens per file with the modification time they were computed at,
+    // shared with the thread counting the selection
+    file_tokens_cache: TokenCache,
+    // Total estimated tokens of the selection as last counted, if counted yet
+    pub selection_tokens: Option<usize>,
+    // Fingerprint of the selection the last count was started for
+    selection_tokens_for: Option<u64>,
+    // The count still running, if any
+    token_count: Option<TokenCount>,
+    // Sizes of directories in the files pane, filled in by background threads
+    pub dir_sizes: RefCell<DirSizes>,
+    // Watcher of the current directory, so changes on disk show up
+    pub watcher: DirWatcher,
+    // Number of selected items under each directory, with a fingerprint of the
+    // selection it was computed from
+    pub selected_counts_cache: RefCell<(u64, HashMap<PathBuf, usize>)>,
+    // Anchor index of the visual selection range in the files pane
+    pub visual_anchor: Option<usize>,
+    // Line ranges (1-based, inclusive) to copy instead of the whole file
+    pub line_ranges: HashMap<PathBuf, (usize, usize)>,
     // Base directory for relative paths
     pub base_dir: PathBuf,
+    // Directory a base reset was refused for because of selected files outside it,
+    // so resetting again there goes ahead
+    pub base_reset_warned: Option<PathBuf>,
+    // Patterns from the base directory's .prayignore, applied regardless of .gitignore
+    pub pray_ignore: Gitignore,
     // Stack to keep track of navigation and cursor positions
     pub navigation_stack: Vec<(PathBuf, usize)>,
-    // Message to display in the footer
-    pub footer_message: Option<String>,
-    // Counter to keep track of message display duration
-    pub message_counter: u8,
+    // Last cursor position in each visited directory
+    pub cursor_positions: HashMap<PathBuf, usize>,
+    // Recent messages shown above the footer, oldest first
+    pub notifications: VecDeque<Notification>,
+    // Whether something changed since the last draw, so idle loops can skip drawing
+    pub needs_redraw: bool,
+    // Warning kept in the footer while collections or the session can't be written
+    pub save_warning: Option<String>,
     // Flag for select all state in files pane
     pub all_selected: bool,
+    // Directory and files added by the last recursive select, so it can be undone
+    pub recursive_selection: Option<(PathBuf, Vec<PathBuf>)>,
     // List of collections
     pub collections: Vec<Collection>,
+    // Collections marked for merging
+    pub marked_collections: HashSet<usize>,
+    // Only show collections with this tag
+    pub tag_filter: Option<String>,
     // Path to the collections file
     pub collections_file: PathBuf,
+    // Past copies, newest first, and the file they are saved in
+    pub copy_history: CopyHistory,
+    copy_history_file: PathBuf,
+    // When the collections first changed since they were last written
+    pub collections_dirty_since: Option<Instant>,
+    // Path to the file holding the in-progress selection
+    pub session_file: PathBuf,
+    // Persisted settings and the path to the config file
+    pub config: Config,
+    pub config_file: PathBuf,
+    // Colors of the UI and the path to a custom theme file
+    pub theme: Theme,
+    pub theme_file: PathBuf,
     // Focused pane
     pub focused_pane: FocusedPane,
     // Flag to show help screen
     pub show_help: bool,
+    // Flag to show the bindings of the focused pane
+    pub show_pane_help: bool,
+    // Flag to show the stats popup of the selected collection
+    pub show_collection_stats: bool,
+    // Flag to show the review screen of everything selected
+    pub show_selection_review: bool,
+    // Cursor row and collapsed directories of the review screen
+    pub review_index: usize,
+    pub review_collapsed: HashSet<PathBuf>,
+    // Flag to show the settings screen and its cursor row
+    pub show_settings: bool,
+    pub settings_index: usize,
+    // Files the next copy would include, shown for confirmation, and the preview scroll
+    pub copy_preview: Option<Vec<PathBuf>>,
+    pub copy_preview_scroll: usize,
+    // Text the next copy would produce, when the preview shows it instead of the files
+    pub copy_preview_text: Option<String>,
+    // Stats of the collection last shown, with the file list they were computed
+    // from. Only one is kept, dropped when the popup opens or the directory changes
+    pub stats_cache: RefCell<Option<(Vec<PathBuf>, CollectionStats)>>,
     // Renaming state
     pub renaming_collection: bool,
-    pub new_collection_name: String,
-    pub respect_gitignore: bool,
+    pub new_collection_name: TextInput,
+    // Active text prompt and its input
+    pub active_prompt: Option<Prompt>,
+    pub prompt_input: TextInput,
+    // Flag to ask whether to copy the selection before quitting
+    pub confirming_quit: bool,
+    // Export destination waiting for an overwrite/rename/cancel answer,
+    // with the prompt that chose it
+    pub pending_overwrite: Option<(PathBuf, Prompt)>,
+    // Active list popup, its cursor and marked entries
+    pub active_popup: Option<Popup>,
+    pub popup_index: usize,
+    pub popup_marked: HashSet<usize>,
+    // Files matching the last content search
+    pub grep_results: Vec<PathBuf>,
+    // State of the fuzzy directory jump while its prompt is open
+    pub fuzzy_jump: Option<FuzzyJump>,
+    // The content search still streaming into `grep_results`, if any
+    pub grep_search: Option<GrepSearch>,
+    // How many of the results the popup lists, grown by loading more
+    pub grep_shown: usize,
+    // Count typed before a motion, like `5j` in vim
+    pub pending_count: Option<usize>,
+    // Hash of the text last copied, to notice copying the same output twice
+    last_copy_hash: Option<u64>,
     pub scroll_position: usize,
+    // Number of rows visible in the files pane
+    pub files_pane_height: usize,
+    // Visible collections scrolled past at the top of the collections pane
+    pub collections_scroll: usize,
+    // Files scrolled past at the top of the selected files pane
+    pub selected_files_scroll: usize,
+    // Name search in the files pane, and whether its query is still being typed
+    pub search_input: TextInput,
+    pub searching: bool,
+    // Quick jump mode, where the next key jumps to the labeled file
+    pub quick_jump: bool,
+    // Columns scrolled horizontally in the focused pane
+    pub horizontal_scroll: usize,
+    // Pane areas for mouse handling and the last click for double-click detection
+    pub pane_areas: PaneAreas,
+    pub last_click: Option<(Instant, u16, u16)>,
 }
 
 impl App {
     // Create a new `App` instance.
     pub fn new() -> App {
         // Start at the current working directory
-        let current_dir = std::env::current_dir().unwrap();
-        let respect_gitignore = true; // default to true
-        let directory_entries = Self::read_directory(&current_dir, respect_gitignore);
+        App::new_with_dir(std::env::current_dir().unwrap())
+    }
+
+    // Create a new `App` instance starting in, and relative to, the given directory
+    pub fn new_with_dir(current_dir: PathBuf) -> App {
+        let project_dirs = ProjectDirs::from("", "", "pray").unwrap();
+        App::with_state_dirs(
+            current_dir,
+            project_dirs.config_dir(),
+            project_dirs.data_local_dir(),
+        )
+    }
 
-        // Set the base directory to the current directory
+    // Create the app with its config and its data (collections, session) kept
+    // in the given directories
+    pub fn with_state_dirs(current_dir: PathBuf, config_dir: &Path, data_local_dir: &Path) -> App {
+        // Set the base directory to the starting directory
         let base_dir = current_dir.clone();
+        let pray_ignore = Self::load_pray_ignore(&base_dir);
 
         // Set the path to the collections file in the data local directory
-        let project_dirs = ProjectDirs::from("", "", "pray").unwrap();
-        let data_local_dir = project_dirs.data_local_dir();
-        fs::create_dir_all(data_local_dir).unwrap();
+        // A missing or read-only data directory only surfaces once something is saved
+        let _ = fs::create_dir_all(data_local_dir);
         let collections_file = data_local_dir.join("collections.json");
+        let session_file = data_local_dir.join("session.json");
+        let selected_items = Session::load(&session_file).selection_for(&base_dir);
+
+        // Load the config from the config directory, which like the data
+        // directory may be missing or read-only until the config is saved
+        let _ = fs::create_dir_all(config_dir);
+        let config_file = config_dir.join("config.json");
+        let mut config = Config::load(&config_file);
+        let copy_history_file = data_local_dir.join("copy_history.json");
+        let mut copy_history = CopyHistory::load(&copy_history_file);
+        // Take over a history older versions kept in the config
+        let legacy_history = std::mem::take(&mut config.legacy_copy_history);
+        if copy_history.records.is_empty() && !legacy_history.is_empty() {
+            copy_history.records = legacy_history;
+            let _ = copy_history.save(&copy_history_file);
+        }
+        let theme_file = config_dir.join("theme.json");
+        let theme = Theme::load(&config.theme, &theme_file);
+        let listing = Self::read_directory(
+            &current_dir,
+            config.respect_gitignore,
+            config.show_hidden,
+            &pray_ignore,
+            config.sort_mode,
+        );
+        let read_error = listing
+            .as_ref()
+            .err()
+            .map(|err| Self::read_error_message(&current_dir, err));
+        let (directory_entries, entry_types) = listing.unwrap_or_default();
 
         // Attempt to read the collections from the file
-        let collections = if collections_file.exists() {
-            let file = fs::File::open(&collections_file).unwrap();
-            serde_json::from_reader(file).unwrap_or_else(|_| vec![])
-        } else {
-            vec![]
-        };
+        let (mut collections, load_error) = Self::load_collections(&collections_file);
+        for collection in &mut collections {
+            collection.resolve_paths();
+        }
 
-        App {
+        let mut app = App {
             base_dir,
+            base_reset_warned: None,
+            pray_ignore,
             current_dir: current_dir.clone(),
             directory_entries,
+            entry_types,
             selected_file_index: 0,
             selected_collection_index: 0,
             selected_file_in_collection_index: 0,
-            selected_items: HashSet::new(),
+            selected_items,
+            selected_counts_cache: RefCell::new((0, HashMap::new())),
+            file_tokens_cache: TokenCache::default(),
+            dir_sizes: RefCell::new(DirSizes::new()),
+            selection_tokens: None,
+            selection_tokens_for: None,
+            token_count: None,
+            watcher: DirWatcher::new(),
+            visual_anchor: None,
+            line_ranges: HashMap::new(),
             navigation_stack: vec![],
-            footer_message: None,
-            message_counter: 0,
+            cursor_positions: HashMap::new(),
+            notifications: VecDeque::new(),
+            save_warning: None,
+            needs_redraw: true,
             all_selected: false,
+            recursive_selection: None,
             collections,
+            marked_collections: HashSet::new(),
+            tag_filter: None,
             collections_file,
+            copy_history,
+            copy_history_file,
+            collections_dirty_since: None,
+            session_file,
+            config,
+            config_file,
+            theme,
+            theme_file,
             focused_pane: FocusedPane::FilesPane,
             show_help: false,
+            show_pane_help: false,
+            show_collection_stats: false,
+            show_selection_review: false,
+            review_index: 0,
+            review_collapsed: HashSet::new(),
+            show_settings: false,
+            settings_index: 0,
+            copy_preview: None,
+            copy_preview_scroll: 0,
+            copy_preview_text: None,
+            stats_cache: RefCell::new(None),
             renaming_collection: false,
-            new_collection_name: String::new(),
-            respect_gitignore,
+            new_collection_name: TextInput::default(),
+            active_prompt: None,
+            prompt_input: TextInput::default(),
+            confirming_quit: false,
+            pending_overwrite: None,
+            active_popup: None,
+            popup_index: 0,
+            popup_marked: HashSet::new(),
+            grep_results: vec![],
+            fuzzy_jump: None,
+            grep_search: None,
+            grep_shown: 0,
+            pending_count: None,
+            last_copy_hash: None,
             scroll_position: 0,
+            collections_scroll: 0,
+            selected_files_scroll: 0,
+            files_pane_height: 0,
+            search_input: TextInput::default(),
+            searching: false,
+            quick_jump: false,
+            horizontal_scroll: 0,
+            pane_areas: PaneAreas::default(),
+            last_click: None,
+        };
+        match read_error {
+            Some(message) => app.push_notification(message, NotificationLevel::Error),
+            None => app.watch_current_directory(),
+        }
+        if let Some(message) = load_error {
+            app.push_notification(message, NotificationLevel::Warning);
         }
+        app.note_moved_collections();
+        app
     }
 
-    // Read the directory entries
-    fn read_directory(path: &PathBuf, respect_gitignore: bool) -> Vec<PathBuf> {
-        let walker = WalkBuilder::new(path)
-            .hidden(false) // Show hidden files
-            .git_ignore(respect_gitignore) // Respect .gitignore files
-            .max_depth(Some(1)) // Only read immediate directory contents
-            .build();
-
-        let mut entries: Vec<PathBuf> = walker
-            .filter_map(|entry| entry.ok())
-            .map(|entry| entry.path().to_path_buf())
-            .filter(|p| p != path) // Exclude the directory itself
-            .collect();
-        entries.sort();
-        entries
+    // Collections whose files look moved to the base directory, with the
+    // directory they were in
+    fn moved_collections(&self) -> Vec<(usize, PathBuf)> {
+        self.collections
+            .iter()
+            .enumerate()
+            .filter_map(|(i, collection)| Some((i, collection.moved_from(&self.base_dir)?)))
+            .collect()
     }
 
-    // Enter a directory
-    pub fn enter_directory(&mut self) {
-        if self.directory_entries.is_empty() {
-            return;
-        }
-        let selected_path = &self.directory_entries[self.selected_file_index];
-        if selected_path.is_dir() {
-            // Push current state onto the navigation stack
-            self.navigation_stack
-                .push((self.current_dir.clone(), self.selected_file_index));
-            self.current_dir = selected_path.clone();
-            self.directory_entries =
-                Self::read_directory(&self.current_dir, self.respect_gitignore);
-            self.selected_file_index = 0;
+    // Point out collections that look moved to the base directory, leaving it to
+    // the user to rebase them
+    fn note_moved_collections(&mut self) {
+        let moved = self.moved_collections().len();
+        if moved > 0 {
+            self.set_footer_message(format!(
+                "{} collections look moved under {}, press R in Collections to rebase them",
+                moved,
+                self.base_dir.display()
+            ));
         }
     }
 
-    // Go back to parent directory
-    pub fn go_back(&mut self) {
-        if let Some((previous_dir, previous_index)) = self.navigation_stack.pop() {
-            self.current_dir = previous_dir;
-            self.directory_entries =
-                Self::read_directory(&self.current_dir, self.respect_gitignore);
-            self.selected_file_index = previous_index;
+    // Rebase the collections whose files look moved to the base directory
+    pub fn rebase_moved_collections(&mut self) {
+        let moved = self.moved_collections();
+        if moved.is_empty() {
+            self.set_footer_message(format!(
+                "No collections look moved under {}",
+                self.base_dir.display()
+            ));
+            return;
+        }
+        let base_dir = self.base_dir.clone();
+        for (i, old) in &moved {
+            self.collections[*i].rebase(old, &base_dir);
         }
+        self.save_collections();
+        self.set_footer_message(format!(
+            "Rebased {} collections under {}",
+            moved.len(),
+            base_dir.display()
+        ));
     }
 
-    // Toggle selection of the current item
-    pub fn toggle_selection(&mut self) {
-        if let Some(selected_path) = self.directory_entries.get(self.selected_file_index) {
-            if self.selected_items.contains(selected_path) {
-                self.selected_items.remove(selected_path);
+    // Save the selected collection's paths relative to the base directory, or
+    // back as absolute paths
+    pub fn toggle_collection_root(&mut self) {
+        let base_dir = self.base_dir.clone();
+        let Some(collection) = self.collections.get_mut(self.selected_collection_index) else {
+            return;
+        };
+        let message = if collection.root.take().is_some() {
+            format!("{} saves absolute paths", collection.name)
+        } else {
+            let outside = collection
+                .files
+                .iter()
+                .filter(|file| !file.path.starts_with(&base_dir))
+                .count();
+            collection.root = Some(base_dir.clone());
+            if outside > 0 {
+                format!(
+                    "{} saves paths relative to {} ({} files outside it stay absolute)",
+                    collection.name,
+                    base_dir.display(),
+                    outside
+                )
             } else {
-                self.selected_items.insert(selected_path.clone());
+                format!(
+                    "{} saves paths relative to {}",
+                    collection.name,
+                    base_dir.display()
+                )
             }
-        }
+        };
+        self.save_collections();
+        self.set_footer_message(message);
     }
 
-    // Check if all items in current directory are selected
-    fn is_current_dir_all_selected(&self) -> bool {
-        self.directory_entries
-            .iter()
-            .all(|entry| self.selected_items.contains(entry))
+    // Read the collections file one collection at a time, so a malformed one
+    // doesn't take the rest with it. Anything unreadable is backed up instead of
+    // letting the next save overwrite it
+    fn load_collections(path: &Path) -> (Vec<Collection>, Option<String>) {
+        let Ok(file) = fs::File::open(path) else {
+            return (vec![], None);
+        };
+        match serde_json::from_reader::<_, Vec<serde_json::Value>>(io::BufReader::new(file)) {
+            Ok(values) => {
+                let total = values.len();
+                let mut first_error = None;
+                let collections: Vec<Collection> = values
+                    .into_iter()
+                    .enumerate()
+                    .filter_map(|(i, value)| match serde_json::from_value(value) {
+                        Ok(collection) => Some(collection),
+                        Err(err) => {
+                            first_error.get_or_insert((i + 1, err));
+                            None
+                        }
+                    })
+                    .collect();
+                let Some((position, err)) = first_error else {
+                    return (collections, None);
+                };
+
+                // The good ones are kept, so copy the file rather than moving it
+                let failed = total - collections.len();
+                let backup = path.with_extension("json.bak");
+                let message = match fs::copy(path, &backup) {
+                    Ok(_) => format!(
+                        "Skipped {} of {} collections that could not be read (#{}: {}), kept a copy at {}",
+                        failed,
+                        total,
+                        position,
+                        err,
+                        backup.display()
+                    ),
+                    Err(_) => format!(
+                        "Skipped {} of {} collections that could not be read (#{}: {})",
+                        failed, total, position, err
+                    ),
+                };
+                (collections, Some(message))
+            }
+            Err(err) => {
+                let backup = path.with_extension("json.bak");
+                let message = match fs::rename(path, &backup) {
+                    Ok(()) => format!(
+                        "Could not read collections ({}), kept a copy at {}",
+                        err,
+                        backup.display()
+                    ),
+                    Err(_) => format!("Could not read collections: {}", err),
+                };
+                (vec![], Some(message))
+            }
+        }
     }
 
-    // Select or deselect all items in current directory only
-    pub fn toggle_select_all(&mut self) {
-        let current_all_selected = self.is_current_dir_all_selected();
+    // Read the directory entries, failing if the directory itself can't be read
+    fn read_directory(
+        path: &PathBuf,
+        respect_gitignore: bool,
+        show_hidden: bool,
+        pray_ignore: &Gitignore,
+        sort_mode: SortMode,
+    ) -> io::Result<Listing> {
+        // The walker silently skips unreadable directories, so check access first
+        fs::read_dir(path)?;
 
-        // Remove only current directory items from selection
-        self.selected_items
-            .retain(|item| !self.directory_entries.contains(item));
+        let walker = WalkBuilder::new(path)
+            .hidden(!show_hidden)
+            .git_ignore(respect_gitignore) // Respect .gitignore files
+            .max_depth(Some(1)) // Only read immediate directory contents
+            .build();
 
-        if !current_all_selected {
-            // Add all current directory items to selection
-            self.selected_items
-                .extend(self.directory_entries.iter().cloned());
+        // Keep the file type the walker already read, so no entry is stat'ed again
+        let mut entries = Vec::new();
+        let mut types = HashMap::new();
+        for entry in walker.filter_map(|entry| entry.ok()) {
+            let entry_path = entry.path().to_path_buf();
+            if entry_path == *path {
+                continue; // Exclude the directory itself
+            }
+            let is_dir = entry
+                .file_type()
+                .is_some_and(|file_type| file_type.is_dir());
+            if Self::is_pray_ignored(pray_ignore, &entry_path, is_dir) {
+                continue;
+            }
+            if let Some(file_type) = entry.file_type() {
+                types.insert(entry_path.clone(), file_type);
+            }
+            entries.push(entry_path);
         }
-
-        self.all_selected = !current_all_selected;
+        sort_mode.sort(&mut entries);
+        Ok((entries, types))
     }
 
-    fn get_all_files_in_dir(&self, dir: &PathBuf) -> Vec<PathBuf> {
-        WalkBuilder::new(dir)
-            .hidden(false)
-            .git_ignore(self.respect_gitignore)
-            .build()
-            .filter_map(|entry| entry.ok())
-            .map(|entry| entry.path().to_path_buf())
-            .filter(|path| path.is_file())
-            .collect()
+    // Read the .prayignore in the base directory; a missing file ignores nothing
+    fn load_pray_ignore(base_dir: &Path) -> Gitignore {
+        let mut builder = GitignoreBuilder::new(base_dir);
+        builder.add(base_dir.join(".prayignore"));
+        builder.build().unwrap_or_else(|_| Gitignore::empty())
     }
 
-    pub fn copy_selected_items_to_clipboard(&mut self) {
-        use clipboard::{ClipboardContext, ClipboardProvider};
-        use std::io::Read;
-
-        let mut output = String::new();
-        let mut all_files = Vec::new();
+    // Re-read the .prayignore, e.g. after the base directory changed
+    pub fn reload_pray_ignore(&mut self) {
+        self.pray_ignore = Self::load_pray_ignore(&self.base_dir);
+        self.reload_current_directory();
+    }
 
-        // Collect all files, including those in selected directories
-        for item in &self.selected_items {
-            if item.is_file() {
-                all_files.push(item.clone());
-            } else if item.is_dir() {
-                all_files.extend(self.get_all_files_in_dir(item));
-            }
+    // Make the current directory the base for relative paths. Selected files
+    // outside it would show absolute paths, so that needs a second press
+    pub fn reset_base_dir(&mut self) {
+        if self.current_dir == self.base_dir {
+            self.set_footer_message(format!("Already the base: {}", self.base_dir.display()));
+            return;
         }
-
-        for item in &all_files {
-            if let Ok(mut file) = fs::File::open(item) {
-                let mut contents = String::new();
-                if let Ok(_) = file.read_to_string(&mut contents) {
-                    let relative_path = item.strip_prefix(&self.base_dir).unwrap_or(item);
-                    output.push_str(&format!("------ {} ------\n", relative_path.display()));
-                    output.push_str("``````\n");
-                    output.push_str(&contents);
-                    output.push_str("\n``````\n");
-                }
-            }
+        let outside = self
+            .selected_items
+            .iter()
+            .filter(|item| !item.starts_with(&self.current_dir))
+            .count();
+        if outside > 0 && self.base_reset_warned.as_ref() != Some(&self.current_dir) {
+            self.base_reset_warned = Some(self.current_dir.clone());
+            self.set_footer_message(format!(
+                "{} selected items are outside this directory and would show absolute paths; press again to reset anyway",
+                outside
+            ));
+            return;
         }
+        self.base_reset_warned = None;
+        self.base_dir = self.current_dir.clone();
+        self.reload_pray_ignore();
+        self.set_footer_message(format!("Base directory: {}", self.base_dir.display()));
+    }
 
-        // Copy to clipboard
-        let mut ctx: ClipboardContext = ClipboardProvider::new().unwrap();
-        ctx.set_contents(output.clone()).unwrap();
-
-        // Display success message in footer
-        self.footer_message = Some("Copied to clipboard!".to_string());
-        self.message_counter = 5; // Display for 5 cycles
+    // Whether a path is excluded by the .prayignore patterns
+    fn is_pray_ignored(pray_ignore: &Gitignore, path: &Path, is_dir: bool) -> bool {
+        // Paths outside the base directory can't match its patterns
+        if !path.starts_with(pray_ignore.path()) {
+            return false;
+        }
+        pray_ignore
+            .matched_path_or_any_parents(path, is_dir)
+            .is_ignore()
+    }
 
-        // Create new collection and add to collections
-        let collection_name = format!("Collection {}", self.collections.len() + 1);
+    // Whether a directory entry is a directory, following symlinks
+    pub fn entry_is_dir(&self, path: &Path) -> bool {
+        match self.entry_types.get(path) {
+            Some(file_type) if !file_type.is_symlink() => file_type.is_dir(),
+            _ => path.is_dir(),
+        }
+    }
 
-        let collection = Collection {
-            name: collection_name,
-            files: all_files.clone(),
-            num_files: all_files.len(),
-            timestamp: chrono::Local::now(),
+    // Absolute path of the entry under the cursor, followed by where it
+    // resolves to if it is a symlink
+    pub fn cursor_path(&self) -> Option<String> {
+        let path = self.directory_entries.get(self.selected_file_index)?;
+        let path = std::path::absolute(path).unwrap_or_else(|_| path.clone());
+        if !self.entry_is_symlink(&path) {
+            return Some(path.display().to_string());
+        }
+        let target = match path.canonicalize() {
+            Ok(target) => target.display().to_string(),
+            Err(_) => "(broken link)".to_string(),
         };
-
-        self.collections.push(collection);
-        self.save_collections();
-
-        // Reset selected items and all_selected flag
-        self.selected_items.clear();
-        self.all_selected = false;
+        Some(format!("{} -> {}", path.display(), target))
     }
 
-    // Decrement message counter
-    pub fn decrement_message_counter(&mut self) {
-        if self.message_counter > 0 {
-            self.message_counter -= 1;
-            if self.message_counter == 0 {
-                self.footer_message = None;
-            }
+    // Whether a directory entry is a symlink
+    pub fn entry_is_symlink(&self, path: &Path) -> bool {
+        match self.entry_types.get(path) {
+            Some(file_type) => file_type.is_symlink(),
+            None => path.is_symlink(),
         }
     }
 
-    // Remove the selected collection
-    pub fn remove_selected_collection(&mut self) {
-        if self.collections.is_empty() {
-            return;
+    // Symbol shown before an entry in the files pane, three columns wide
+    pub fn entry_symbol(&self, path: &Path) -> &'static str {
+        let nerd_font = self.config.nerd_font_icons;
+        if self.entry_is_symlink(path) {
+            return if nerd_font { "\u{f0c1}  " } else { "[L]" };
         }
-
-        self.collections.remove(self.selected_collection_index);
-        if self.selected_collection_index >= self.collections.len()
-            && self.selected_collection_index > 0
-        {
-            self.selected_collection_index -= 1;
+        if self.entry_is_dir(path) {
+            return if nerd_font { "\u{f07b}  " } else { "[D]" };
+        }
+        let ext = path
+            .extension()
+            .map(|ext| ext.to_string_lossy())
+            .unwrap_or_default();
+        if nerd_font {
+            extension_glyph(&ext)
+        } else {
+            extension_symbol(&ext)
         }
-        self.save_collections();
     }
 
-    // Copy files from the selected collection to clipboard
-    pub fn copy_selected_collection_to_clipboard(&mut self) {
-        use clipboard::{ClipboardContext, ClipboardProvider};
-        use std::io::Read;
+    // Describe an error reading a directory for the footer
+    fn read_error_message(path: &Path, err: &io::Error) -> String {
+        if err.kind() == io::ErrorKind::PermissionDenied {
+            format!("Permission denied: {}", path.display())
+        } else {
+            format!("Could not read {}: {}", path.display(), err)
+        }
+    }
 
-        if self.collections.is_empty() {
+    // Enter a directory
+    pub fn enter_directory(&mut self) {
+        let Some(selected_path) = self
+            .directory_entries
+            .get(self.selected_file_index)
+            .cloned()
+        else {
             return;
+        };
+        if self.entry_is_dir(&selected_path) {
+            self.navigate_to(selected_path);
         }
+    }
 
-        let collection = &self.collections[self.selected_collection_index];
+    // Navigate to a directory so that `go_back` returns to the current one,
+    // returning whether it could be read
+    fn navigate_to(&mut self, dir: PathBuf) -> bool {
+        let (entries, types) = match Self::read_directory(
+            &dir,
+            self.config.respect_gitignore,
+            self.config.show_hidden,
+            &self.pray_ignore,
+            self.config.sort_mode,
+        ) {
+            Ok(listing) => listing,
+            Err(err) => {
+                self.push_notification(
+                    Self::read_error_message(&dir, &err),
+                    NotificationLevel::Error,
+                );
+                return false;
+            }
+        };
 
-        let mut output = String::new();
+        // Push current state onto the navigation stack
+        self.navigation_stack
+            .push((self.current_dir.clone(), self.selected_file_index));
+        self.remember_cursor_position();
+        self.visual_anchor = None;
+        self.search_input.clear();
+        self.current_dir = dir;
+        self.watch_current_directory();
+        self.directory_entries = entries;
+        self.entry_types = types;
+        self.selected_file_index = self.restore_cursor_position();
+        true
+    }
 
-        for item in &collection.files {
-            if item.is_file() {
-                if let Ok(mut file) = fs::File::open(item) {
-                    let mut contents = String::new();
-                    if let Ok(_) = file.read_to_string(&mut contents) {
-                        let relative_path = item.strip_prefix(&self.base_dir).unwrap_or(item);
-                        output.push_str(&format!("------ {} ------\n", relative_path.display()));
-                        output.push_str("``````\n");
-                        output.push_str(&contents);
-                        output.push_str("\n``````\n");
-                    }
+    // Resolve a typed path against the current directory, expanding a leading `~`
+    fn expand_path(&self, input: &str) -> PathBuf {
+        let home = BaseDirs::new().map(|dirs| dirs.home_dir().to_path_buf());
+        let path = match (input.strip_prefix('~'), home) {
+            (Some(rest), Some(home)) if rest.is_empty() || rest.starts_with('/') => {
+                home.join(rest.trim_start_matches('/'))
+            }
+            _ => PathBuf::from(input),
+        };
+        self.current_dir.join(path)
+    }
+
+    // Navigate to a typed directory path so that `go_back` returns here
+    pub fn jump_to_directory(&mut self, input: &str) {
+        let input = input.trim();
+        if input.is_empty() {
+            return;
+        }
+        let path = self.expand_path(input);
+        match path.canonicalize() {
+            Ok(dir) if dir.is_dir() => {
+                if dir != self.current_dir {
+                    self.navigate_to(dir);
                 }
             }
+            _ => self.push_notification(
+                format!("Not a directory: {}", input),
+                NotificationLevel::Error,
+            ),
         }
+    }
 
-        // Copy to clipboard
-        let mut ctx: ClipboardContext = ClipboardProvider::new().unwrap();
-        ctx.set_contents(output).unwrap();
+    // Open the fuzzy directory jump, collecting the directories under the base
+    // directory down to the configured depth
+    pub fn start_fuzzy_jump(&mut self) {
+        let pray_ignore = self.pray_ignore.clone();
+        let walker = WalkBuilder::new(&self.base_dir)
+            .git_ignore(self.config.respect_gitignore)
+            .max_depth(Some(self.config.jump_max_depth))
+            .filter_entry(move |entry| {
+                let is_dir = entry
+                    .file_type()
+                    .is_some_and(|file_type| file_type.is_dir());
+                is_dir && !Self::is_pray_ignored(&pray_ignore, entry.path(), true)
+            })
+            .build();
+        let candidates = walker
+            .filter_map(Result::ok)
+            .filter_map(|entry| {
+                let relative = entry.path().strip_prefix(&self.base_dir).ok()?;
+                let relative = relative.to_string_lossy().into_owned();
+                (!relative.is_empty()).then_some(relative)
+            })
+            .collect();
 
-        // Display success message in footer
-        self.footer_message = Some("Collection copied to clipboard!".to_string());
-        self.message_counter = 5; // Display for 5 cycles
+        self.start_prompt(Prompt::FuzzyJump);
+        self.fuzzy_jump = Some(FuzzyJump {
+            candidates,
+            ranked_query: None,
+            changed_at: None,
+            matches: Vec::new(),
+            index: 0,
+        });
+        self.update_fuzzy_jump();
     }
 
-    // Unselect a file from the selected collection
-    pub fn unselect_file_from_collection(&mut self) {
-        if self.collections.is_empty() {
+    // Rank the candidates again once typing has paused
+    pub fn update_fuzzy_jump(&mut self) {
+        let query = self.prompt_input.text();
+        let Some(jump) = &mut self.fuzzy_jump else {
+            return;
+        };
+        if jump.ranked_query.as_deref() == Some(query) {
+            jump.changed_at = None;
+            return;
+        }
+        // Rank right away at first, then wait for a pause in typing
+        if jump.ranked_query.is_some() {
+            let changed_at = *jump.changed_at.get_or_insert_with(Instant::now);
+            if changed_at.elapsed() < FUZZY_JUMP_DEBOUNCE {
+                return;
+            }
+        }
+
+        jump.rank(query);
+        self.needs_redraw = true;
+    }
+
+    // Move the cursor through the fuzzy jump matches
+    pub fn move_fuzzy_jump_cursor(&mut self, delta: isize) {
+        let Some(jump) = &mut self.fuzzy_jump else {
+            return;
+        };
+        jump.index = jump
+            .index
+            .saturating_add_signed(delta)
+            .min(jump.matches.len().saturating_sub(1));
+    }
+
+    // Go to the directory under the fuzzy jump cursor
+    fn confirm_fuzzy_jump(&mut self, query: &str) {
+        let Some(mut jump) = self.fuzzy_jump.take() else {
+            return;
+        };
+        // Rank the final query even if typing only just stopped
+        if jump.ranked_query.as_deref() != Some(query) {
+            jump.rank(query);
+        }
+        let Some((relative, _)) = jump.matches.get(jump.index) else {
+            self.set_footer_message("No matching directory");
+            return;
+        };
+        let dir = self.base_dir.join(relative);
+        if dir != self.current_dir {
+            self.navigate_to(dir);
+        }
+    }
+
+    // Complete the last segment of the typed path to the directories it matches,
+    // as far as they agree
+    pub fn complete_jump_path(&mut self) {
+        let input = self.prompt_input.text().to_string();
+        let (parent, prefix) = match input.rfind('/') {
+            Some(index) => input.split_at(index + 1),
+            None => ("", input.as_str()),
+        };
+        let dir = self.expand_path(if parent.is_empty() { "." } else { parent });
+        let Ok(entries) = fs::read_dir(&dir) else {
+            return;
+        };
+
+        let mut matches: Vec<String> = entries
+            .filter_map(Result::ok)
+            .filter(|entry| entry.path().is_dir())
+            .map(|entry| entry.file_name().to_string_lossy().into_owned())
+            .filter(|name| name.starts_with(prefix))
+            // Hidden directories only complete when asked for
+            .filter(|name| prefix.starts_with('.') || !name.starts_with('.'))
+            .collect();
+        matches.sort();
+        let Some(first) = matches.first() else {
+            return;
+        };
+
+        let completion = if matches.len() == 1 {
+            format!("{}/", first)
+        } else {
+            let mut common = first.clone();
+            for name in &matches[1..] {
+                let shared: usize = common
+                    .chars()
+                    .zip(name.chars())
+                    .take_while(|(a, b)| a == b)
+                    .map(|(a, _)| a.len_utf8())
+                    .sum();
+                common.truncate(shared);
+            }
+            common
+        };
+        self.prompt_input.set(format!("{}{}", parent, completion));
+    }
+
+    // Navigate to the directory containing a file and put the cursor on it
+    fn reveal_path(&mut self, path: &Path) {
+        let Some(parent) = path.parent() else {
+            return;
+        };
+        if parent != self.current_dir && !self.navigate_to(parent.to_path_buf()) {
+            return;
+        }
+        if let Some(index) = self
+            .directory_entries
+            .iter()
+            .position(|entry| entry == path)
+        {
+            self.selected_file_index = index;
+        }
+    }
+
+    // Remember the cursor position in the current directory
+    fn remember_cursor_position(&mut self) {
+        // Forget all but the directories `go_back` returns to once there are many,
+        // instead of checking every remembered directory still exists on each move
+        if self.cursor_positions.len() >= CURSOR_POSITIONS_LIMIT {
+            let stack: HashSet<&PathBuf> =
+                self.navigation_stack.iter().map(|(dir, _)| dir).collect();
+            self.cursor_positions.retain(|path, _| stack.contains(path));
+        }
+        self.cursor_positions
+            .insert(self.current_dir.clone(), self.selected_file_index);
+    }
+
+    // Get the last cursor position in the current directory, clamped to its entries
+    fn restore_cursor_position(&self) -> usize {
+        let last_index = self.directory_entries.len().saturating_sub(1);
+        self.cursor_positions
+            .get(&self.current_dir)
+            .map_or(0, |&index| index.min(last_index))
+    }
+
+    // Go back to parent directory
+    pub fn go_back(&mut self) {
+        if let Some((previous_dir, previous_index)) = self.navigation_stack.pop() {
+            self.remember_cursor_position();
+            self.visual_anchor = None;
+            self.search_input.clear();
+            self.current_dir = previous_dir;
+            self.reload_current_directory();
+            // The directory may have lost entries since it was left
+            self.selected_file_index =
+                previous_index.min(self.directory_entries.len().saturating_sub(1));
+        }
+    }
+
+    // Toggle selection of the current item
+    pub fn toggle_selection(&mut self) {
+        if let Some(selected_path) = self.directory_entries.get(self.selected_file_index) {
+            if self.selected_items.contains(selected_path) {
+                self.selected_items.remove(selected_path);
+            } else {
+                self.selected_items.insert(selected_path.clone());
+            }
+        }
+    }
+
+    // Toggle selection of the given number of items from the cursor down,
+    // leaving the cursor on the last one toggled
+    pub fn toggle_selections(&mut self, count: usize) {
+        for i in 0..count {
+            if i > 0 {
+                if self.selected_file_index + 1 >= self.directory_entries.len() {
+                    break;
+                }
+                self.selected_file_index += 1;
+            }
+            self.toggle_selection();
+        }
+    }
+
+    // Add a typed digit to the count applied to the next motion
+    pub fn push_count_digit(&mut self, digit: char) {
+        let Some(digit) = digit.to_digit(10) else {
+            return;
+        };
+        // Cap it so a held key can't overflow
+        let count = self.pending_count.unwrap_or(0) * 10 + digit as usize;
+        self.pending_count = Some(count.min(MAX_COUNT));
+    }
+
+    // Start visual mode anchored at the cursor, or leave it if already active
+    pub fn toggle_visual_mode(&mut self) {
+        if self.visual_anchor.is_some() {
+            self.visual_anchor = None;
+        } else if !self.directory_entries.is_empty() {
+            self.visual_anchor = Some(self.selected_file_index);
+        }
+    }
+
+    // Leave visual mode without changing the selection
+    pub fn exit_visual_mode(&mut self) {
+        self.visual_anchor = None;
+    }
+
+    // Range of entries between the visual anchor and the cursor
+    pub fn visual_range(&self) -> Option<std::ops::RangeInclusive<usize>> {
+        let anchor = self.visual_anchor?;
+        let start = anchor.min(self.selected_file_index);
+        let end = anchor.max(self.selected_file_index);
+        Some(start..=end)
+    }
+
+    // Toggle selection of every item in the visual range, then leave visual mode
+    pub fn toggle_visual_selection(&mut self) {
+        let Some(range) = self.visual_range() else {
+            return;
+        };
+        let entries = &self.directory_entries[range];
+
+        if entries
+            .iter()
+            .all(|entry| self.selected_items.contains(entry))
+        {
+            for entry in entries {
+                self.selected_items.remove(entry);
+            }
+        } else {
+            self.selected_items.extend(entries.iter().cloned());
+        }
+        self.visual_anchor = None;
+    }
+
+    // Check if all items in current directory are selected
+    fn is_current_dir_all_selected(&self) -> bool {
+        self.directory_entries
+            .iter()
+            .all(|entry| self.selected_items.contains(entry))
+    }
+
+    // Number of selected items inside each directory, recomputed only when the selection changed
+    pub fn selected_counts(&self) -> Ref<'_, HashMap<PathBuf, usize>> {
+        let fingerprint = self.selection_fingerprint();
+
+        if self.selected_counts_cache.borrow().0 != fingerprint {
+            let mut counts: HashMap<PathBuf, usize> = HashMap::new();
+            for item in &self.selected_items {
+                for ancestor in item.ancestors().skip(1) {
+                    *counts.entry(ancestor.to_path_buf()).or_default() += 1;
+                }
+            }
+            *self.selected_counts_cache.borrow_mut() = (fingerprint, counts);
+        }
+        Ref::map(self.selected_counts_cache.borrow(), |(_, counts)| counts)
+    }
+
+    // Order-independent fingerprint of the selection, to tell when caches are stale
+    fn selection_fingerprint(&self) -> u64 {
+        self.selected_items
+            .iter()
+            .fold(self.selected_items.len() as u64, |acc, item| {
+                let mut hasher = DefaultHasher::new();
+                item.hash(&mut hasher);
+                acc ^ hasher.finish()
+            })
+    }
+
+    // Start counting the selection's tokens in the background when it changed
+    // since the last count, and take the result of a finished count
+    pub fn update_selection_tokens(&mut self) {
+        let fingerprint = self.selection_fingerprint();
+        if self.selection_tokens_for != Some(fingerprint) {
+            self.selection_tokens_for = Some(fingerprint);
+            self.token_count = Some(self.count_selection_tokens());
+        }
+        let Some(count) = &self.token_count else {
+            return;
+        };
+        match count.result.try_recv() {
+            Ok(tokens) => {
+                self.selection_tokens = Some(tokens);
+                self.token_count = None;
+                self.needs_redraw = true;
+            }
+            Err(TryRecvError::Empty) => {}
+            Err(TryRecvError::Disconnected) => self.token_count = None,
+        }
+    }
+
+    // Count the selection again on the next update, e.g. after files changed
+    fn invalidate_selection_tokens(&mut self) {
+        self.selection_tokens_for = None;
+    }
+
+    // Walk the selected directories and add up the tokens of every file on a
+    // background thread, so a large selection doesn't hold up drawing
+    fn count_selection_tokens(&self) -> TokenCount {
+        let (sender, result) = mpsc::channel();
+        let cancel = Arc::new(AtomicBool::new(false));
+        let thread_cancel = Arc::clone(&cancel);
+        let items: Vec<PathBuf> = self.selected_items.iter().cloned().collect();
+        let options = self.walk_options();
+        let cache = Arc::clone(&self.file_tokens_cache);
+        std::thread::spawn(move || {
+            let mut tokens = 0;
+            for item in items {
+                let files = if item.is_dir() {
+                    Self::walk_files(&options, &item).0
+                } else {
+                    vec![item]
+                };
+                for file in files {
+                    if thread_cancel.load(Ordering::Relaxed) {
+                        return;
+                    }
+                    tokens += Self::cached_file_tokens(&cache, &file);
+                }
+            }
+            let _ = sender.send(tokens);
+        });
+        TokenCount { result, cancel }
+    }
+
+    pub fn file_tokens(&self, path: &Path) -> usize {
+        Self::cached_file_tokens(&self.file_tokens_cache, path)
+    }
+
+    // Estimated tokens of a file's text, cached until the file is modified
+    // Files that can't be read as text count as zero since they aren't copied
+    fn cached_file_tokens(cache: &TokenCache, path: &Path) -> usize {
+        let Ok(modified) = fs::metadata(path).and_then(|m| m.modified()) else {
+            return 0;
+        };
+        if let Some(&(cached_modified, tokens)) = cache.lock().unwrap().get(path) {
+            if cached_modified == modified {
+                return tokens;
+            }
+        }
+
+        let tokens = fs::read_to_string(path)
+            .map(|contents| estimate_tokens(contents.len() as u64))
+            .unwrap_or(0);
+        cache
+            .lock()
+            .unwrap()
+            .insert(path.to_path_buf(), (modified, tokens));
+        tokens
+    }
+
+    // Select or deselect all items in current directory only
+    pub fn toggle_select_all(&mut self) {
+        let current_all_selected = self.is_current_dir_all_selected();
+
+        // Remove only current directory items from selection
+        self.selected_items
+            .retain(|item| !self.directory_entries.contains(item));
+
+        if !current_all_selected {
+            // Add all current directory items to selection
+            self.selected_items
+                .extend(self.directory_entries.iter().cloned());
+        }
+
+        self.all_selected = !current_all_selected;
+    }
+
+    // Swap selected and unselected items in the current directory only,
+    // leaving selections elsewhere alone
+    pub fn invert_selection(&mut self) {
+        let mut selected_here = 0;
+        for entry in &self.directory_entries {
+            if !self.selected_items.remove(entry) {
+                self.selected_items.insert(entry.clone());
+                selected_here += 1;
+            }
+        }
+        self.all_selected = self.is_current_dir_all_selected();
+        self.set_footer_message(format!(
+            "Selected {} of {} items here",
+            selected_here,
+            self.directory_entries.len()
+        ));
+    }
+
+    // Select every file under the current directory, or deselect exactly the files
+    // a previous press added when pressed again in the same directory
+    pub fn toggle_select_recursive(&mut self) {
+        if let Some((dir, added)) = self.recursive_selection.take() {
+            if dir == self.current_dir {
+                for file in &added {
+                    self.selected_items.remove(file);
+                }
+                self.set_footer_message(format!("Deselected {} files", added.len()));
+                return;
+            }
+        }
+
+        let dir = self.current_dir.clone();
+        let (files, skipped, capped) = self.get_all_files_in_dir(&dir);
+        let added: Vec<PathBuf> = files
+            .into_iter()
+            .filter(|file| self.selected_items.insert(file.clone()))
+            .collect();
+
+        self.set_footer_message(if skipped > 0 {
+            format!(
+                "Selected {} files recursively ({} unreadable entries skipped)",
+                added.len(),
+                skipped
+            )
+        } else {
+            format!("Selected {} files recursively", added.len())
+        });
+        self.warn_capped_dirs(&capped);
+        self.recursive_selection = Some((dir, added));
+    }
+
+    // Recursively collect files in a directory, also returning how many
+    // entries couldn't be read (e.g. permission denied) and the directories
+    // left unwalked because they are nested deeper than the configured limit
+    fn get_all_files_in_dir(&self, dir: &Path) -> (Vec<PathBuf>, usize, Vec<PathBuf>) {
+        Self::walk_files(&self.walk_options(), dir)
+    }
+
+    fn walk_options(&self) -> WalkOptions {
+        WalkOptions {
+            max_depth: self.config.walk_max_depth,
+            follow_symlinks: self.config.follow_symlinks,
+            respect_gitignore: self.config.respect_gitignore,
+            show_hidden: self.config.show_hidden,
+            pray_ignore: self.pray_ignore.clone(),
+        }
+    }
+
+    fn walk_files(options: &WalkOptions, dir: &Path) -> (Vec<PathBuf>, usize, Vec<PathBuf>) {
+        let mut files = Vec::new();
+        let mut skipped = 0;
+        let mut capped = Vec::new();
+        let max_depth = options.max_depth;
+
+        // Canonical paths of directories already walked, so symlink cycles are skipped
+        let visited: Arc<Mutex<HashSet<PathBuf>>> = Arc::new(Mutex::new(HashSet::new()));
+        if let Ok(canonical) = dir.canonicalize() {
+            visited.lock().unwrap().insert(canonical);
+        }
+
+        let follow_symlinks = options.follow_symlinks;
+        let pray_ignore = options.pray_ignore.clone();
+        let walker = WalkBuilder::new(dir)
+            .hidden(!options.show_hidden)
+            .git_ignore(options.respect_gitignore)
+            .follow_links(follow_symlinks)
+            .max_depth(Some(max_depth))
+            .filter_entry(move |entry| {
+                let is_dir = entry
+                    .file_type()
+                    .is_some_and(|file_type| file_type.is_dir());
+                if Self::is_pray_ignored(&pray_ignore, entry.path(), is_dir) {
+                    return false;
+                }
+                if !follow_symlinks {
+                    return !entry.path_is_symlink();
+                }
+                if !is_dir {
+                    return true;
+                }
+                match entry.path().canonicalize() {
+                    Ok(canonical) => visited.lock().unwrap().insert(canonical),
+                    Err(_) => true,
+                }
+            })
+            .build();
+        for entry in walker {
+            match entry {
+                Ok(entry) if entry.path().is_file() => files.push(entry.path().to_path_buf()),
+                // The walk stops here, so only directories with something in them lose files
+                Ok(entry)
+                    if entry.depth() == max_depth
+                        && fs::read_dir(entry.path())
+                            .is_ok_and(|mut entries| entries.next().is_some()) =>
+                {
+                    capped.push(entry.path().to_path_buf())
+                }
+                Ok(_) => {}
+                Err(_) => skipped += 1,
+            }
+        }
+        (files, skipped, capped)
+    }
+
+    // Warn that files under the given directories were left out for being nested too deep
+    fn warn_capped_dirs(&mut self, capped: &[PathBuf]) {
+        const SHOWN: usize = 3;
+        if capped.is_empty() {
+            return;
+        }
+        let mut dirs: Vec<String> = capped
+            .iter()
+            .take(SHOWN)
+            .map(|dir| self.display_path(dir))
+            .collect();
+        if capped.len() > SHOWN {
+            dirs.push(format!("{} more", capped.len() - SHOWN));
+        }
+        self.push_notification(
+            format!(
+                "Stopped {} levels deep, files left out under {}",
+                self.config.walk_max_depth,
+                dirs.join(", ")
+            ),
+            NotificationLevel::Warning,
+        );
+    }
+
+    // Read a file and format it as a fenced block with a path header
+    fn read_file_block(&self, path: &PathBuf, format: OutputFormat) -> Option<String> {
+        let file = self.read_file_parts(path)?;
+        let lang = self.fence_lang(&file.extension);
+        Some(format.block(
+            &file.header,
+            file.meta.as_deref(),
+            lang,
+            &file.contents,
+            self.config.fence,
+        ))
+    }
+
+    // Format the files of a directory as one block, each file after a comment
+    // line with its header
+    fn read_dir_block(
+        &self,
+        dir: &Path,
+        files: &[PathBuf],
+        format: OutputFormat,
+    ) -> Option<String> {
+        let files: Vec<FileParts> = files
+            .iter()
+            .filter_map(|path| self.read_file_parts(path))
+            .collect();
+        let first = files.first()?;
+        // A fence tag only fits when every file is in the same language
+        let lang = if files.iter().all(|file| file.extension == first.extension) {
+            self.fence_lang(&first.extension)
+        } else {
+            ""
+        };
+        let contents: String = files
+            .iter()
+            .map(|file| {
+                format!(
+                    "// ==== {} ====\n{}\n",
+                    file.header,
+                    file.contents.trim_end_matches(['\n', '\r'])
+                )
+            })
+            .collect();
+        let header = format!("{}/", self.display_path(dir));
+        Some(format.block(&header, None, lang, &contents, self.config.fence))
+    }
+
+    // Read a file's contents as they are copied, with its header, metadata line
+    // and the extension of its language
+    fn read_file_parts(&self, path: &PathBuf) -> Option<FileParts> {
+        use std::io::Read;
+
+        // Stat the open file, so the metadata costs no extra lookup of the path
+        let mut file = fs::File::open(path).ok()?;
+        let meta = if self.config.show_file_metadata {
+            file.metadata()
+                .ok()
+                .map(|metadata| file_metadata_line(&metadata))
+        } else {
+            None
+        };
+        let mut contents = String::new();
+        file.read_to_string(&mut contents).ok()?;
+        let mut header = self.display_path(path);
+        // Look at the shebang before a line range can cut it off
+        let extension = self.language_extension(path, &contents);
+
+        if self.config.show_line_counts && !self.line_ranges.contains_key(path) {
+            // A last line without a newline still counts
+            let newlines = contents.bytes().filter(|&b| b == b'\n').count();
+            let lines = newlines + usize::from(!contents.is_empty() && !contents.ends_with('\n'));
+            header.push_str(&format!(" ({} lines)", lines));
+        }
+
+        if let Some(&(start, end)) = self.line_ranges.get(path) {
+            header.push_str(&format!(" (lines {}-{})", start, end));
+            contents = contents
+                .lines()
+                .skip(start - 1)
+                .take(end - start + 1)
+                .collect::<Vec<&str>>()
+                .join("\n");
+        }
+
+        if self.config.strip_comments {
+            contents = minify_contents(&extension, &contents);
+        }
+
+        if let Some(max_bytes) = self.config.max_file_bytes {
+            truncate_contents(&mut contents, max_bytes);
+        }
+
+        Some(FileParts {
+            header,
+            meta,
+            extension,
+            contents,
+        })
+    }
+
+    // Assemble the blocks for the given files in order, stopping at the token budget
+    // and remembering the included files as recent
+    fn assemble_blocks(
+        &mut self,
+        files: &[PathBuf],
+        dirs: &[PathBuf],
+        format: OutputFormat,
+    ) -> (String, CopyCounts) {
+        let (output, counts, included) = self.build_blocks(files, dirs, format);
+        for item in &included {
+            self.config.add_recent_file(item);
+        }
+        self.save_config();
+        (output, counts)
+    }
+
+    // Build the blocks for the given files in order, stopping at the token budget,
+    // along with the files that made it in
+    fn build_blocks(
+        &self,
+        files: &[PathBuf],
+        dirs: &[PathBuf],
+        format: OutputFormat,
+    ) -> (String, CopyCounts, Vec<PathBuf>) {
+        let mut output = String::new();
+        let mut counts = CopyCounts::default();
+        let mut included = Vec::new();
+        let mut tokens = 0;
+
+        let files: Vec<PathBuf> = files
+            .iter()
+            .filter(|item| item.is_file())
+            .cloned()
+            .collect();
+        for (dir, group) in self.copy_groups(&files, dirs) {
+            let block = match &dir {
+                Some(dir) => self.read_dir_block(dir, &group, format),
+                None => self.output_block(&group[0], format, &mut counts.diffs),
+            };
+            let Some(block) = block else {
+                continue;
+            };
+
+            // Once a block doesn't fit, the rest are dropped so earlier files take priority
+            if let Some(budget) = self.config.token_budget {
+                let block_tokens = estimate_tokens(block.len() as u64);
+                if counts.dropped > 0 || tokens + block_tokens > budget {
+                    counts.dropped += group.len();
+                    continue;
+                }
+                tokens += block_tokens;
+            }
+
+            output.push_str(&block);
+            included.extend(group);
+        }
+        (output, counts, included)
+    }
+
+    // Split the files into the blocks they are copied as: one per file, or when
+    // copying one block per directory, the files under each of the given
+    // directories together, placed where the first of them was
+    fn copy_groups(
+        &self,
+        files: &[PathBuf],
+        dirs: &[PathBuf],
+    ) -> Vec<(Option<PathBuf>, Vec<PathBuf>)> {
+        let mut groups: Vec<(Option<PathBuf>, Vec<PathBuf>)> = vec![];
+        for file in files {
+            let dir = if self.config.output_mode == OutputMode::ConcatByDir {
+                // Nested selected directories go into the outermost one
+                dirs.iter()
+                    .filter(|dir| file.starts_with(dir))
+                    .min_by_key(|dir| dir.components().count())
+            } else {
+                None
+            };
+            match dir {
+                Some(dir) => match groups
+                    .iter_mut()
+                    .find(|(group_dir, _)| group_dir.as_ref() == Some(dir))
+                {
+                    Some((_, group)) => group.push(file.clone()),
+                    None => groups.push((Some(dir.clone()), vec![file.clone()])),
+                },
+                None => groups.push((None, vec![file.clone()])),
+            }
+        }
+        groups
+    }
+
+    // Selected directories whose files a copy of the selection includes
+    fn selected_dirs(&self) -> Vec<PathBuf> {
+        self.selected_items
+            .iter()
+            .filter(|item| item.is_dir() && (self.config.follow_symlinks || !item.is_symlink()))
+            .cloned()
+            .collect()
+    }
+
+    // Build the block for a file according to the output mode, counting files with diffs
+    // Returns None for unreadable files and, in diff mode, for files without changes
+    fn output_block(
+        &self,
+        path: &PathBuf,
+        format: OutputFormat,
+        diffs: &mut usize,
+    ) -> Option<String> {
+        if self.config.output_mode == OutputMode::Diff {
+            // Outside a git repository the full contents are used instead
+            if let Some(diff) = Self::git_diff(path) {
+                if diff.trim().is_empty() {
+                    return None;
+                }
+                *diffs += 1;
+                let header = format!("{} (diff)", self.display_path(path));
+                return Some(format.block(
+                    &header,
+                    None,
+                    "diff",
+                    diff.trim_end(),
+                    self.config.fence,
+                ));
+            }
+        }
+        self.read_file_block(path, format)
+    }
+
+    // Run `git diff` for a file, or None when it isn't inside a git repository
+    fn git_diff(path: &Path) -> Option<String> {
+        let parent = path.parent()?;
+        let output = Command::new("git")
+            .arg("-C")
+            .arg(parent)
+            .args(["diff", "--"])
+            .arg(path.file_name()?)
+            .output()
+            .ok()?;
+        if !output.status.success() {
+            return None;
+        }
+        Some(String::from_utf8_lossy(&output.stdout).into_owned())
+    }
+
+    // Open a directory in the platform's file manager without waiting for it
+    pub fn reveal_in_file_manager(&mut self, path: &Path) {
+        let program = if cfg!(target_os = "macos") {
+            "open"
+        } else if cfg!(target_os = "windows") {
+            "explorer"
+        } else {
+            "xdg-open"
+        };
+        let spawned = Command::new(program)
+            .arg(path)
+            .stdin(std::process::Stdio::null())
+            .stdout(std::process::Stdio::null())
+            .stderr(std::process::Stdio::null())
+            .spawn();
+        match spawned {
+            Ok(mut child) => {
+                // Reap the launcher in the background so it doesn't linger
+                std::thread::spawn(move || child.wait());
+                self.set_footer_message(format!("Opened {}", path.display()));
+            }
+            Err(err) => self.push_notification(
+                format!("Could not run {}: {}", program, err),
+                NotificationLevel::Error,
+            ),
+        }
+    }
+
+    // Select the files git reports as modified or untracked in the repository
+    // containing the base directory, returning how many were found
+    // Ignored files count as untracked while .gitignore is not respected
+    pub fn select_git_modified(&mut self) -> usize {
+        let git = |args: &[&str]| {
+            Command::new("git")
+                .arg("-C")
+                .arg(&self.base_dir)
+                .args(args)
+                .output()
+                .ok()
+                .filter(|output| output.status.success())
+        };
+        let Some(toplevel) = git(&["rev-parse", "--show-toplevel"]) else {
+            self.set_footer_message("Not in a git repository");
+            return 0;
+        };
+        let toplevel = PathBuf::from(String::from_utf8_lossy(&toplevel.stdout).trim());
+
+        let mut args = vec!["status", "--porcelain", "-z", "--untracked-files=all"];
+        if !self.config.respect_gitignore {
+            args.push("--ignored=traditional");
+        }
+        let Some(status) = git(&args) else {
+            self.push_notification("Could not read git status", NotificationLevel::Error);
+            return 0;
+        };
+
+        // Entries are "XY path", and renames and copies are followed by their old path
+        let status = String::from_utf8_lossy(&status.stdout).into_owned();
+        let mut entries = status.split('\0').filter(|entry| !entry.is_empty());
+        let mut files = Vec::new();
+        while let Some(entry) = entries.next() {
+            let (code, path) = entry.split_at(entry.len().min(3));
+            if code.starts_with('R') || code.starts_with('C') {
+                entries.next();
+            }
+            let path = toplevel.join(path);
+            if path.is_file() && !Self::is_pray_ignored(&self.pray_ignore, &path, false) {
+                files.push(path);
+            }
+        }
+
+        let count = files.len();
+        self.selected_items.extend(files);
+        if count == 0 {
+            self.set_footer_message("No changed files");
+        } else if self.config.copy_git_changes {
+            self.copy_selected_items_to_clipboard(false);
+        } else {
+            self.set_footer_message(format!("Selected {} changed files", count));
+        }
+        count
+    }
+
+    // Lowercase extension deciding a file's language: its own when recognized,
+    // otherwise the one its shebang implies, so extensionless scripts still get one
+    fn language_extension(&self, path: &Path, contents: &str) -> String {
+        let extension = path
+            .extension()
+            .and_then(|ext| ext.to_str())
+            .unwrap_or("")
+            .to_lowercase();
+        if !self.fence_lang(&extension).is_empty() {
+            return extension;
+        }
+        contents
+            .lines()
+            .next()
+            .and_then(shebang_extension)
+            .map(str::to_string)
+            .unwrap_or(extension)
+    }
+
+    // Fence tag for a lowercase extension, from the config's overrides before the
+    // built-in map, or empty when neither knows it
+    fn fence_lang(&self, extension: &str) -> &str {
+        match self.config.lang_overrides.get(extension) {
+            Some(lang) => lang,
+            None => extension_to_lang(extension).unwrap_or(""),
+        }
+    }
+
+    // Render paths as an indented tree relative to the base directory
+    pub fn render_file_tree(&self, files: &[PathBuf], root: &Path) -> String {
+        let mut paths: Vec<(&Path, bool)> = files
+            .iter()
+            .map(|file| (file.strip_prefix(root).unwrap_or(file), file.is_dir()))
+            .collect();
+        paths.sort();
+        paths.dedup();
+
+        let mut output = String::new();
+        let mut previous: Vec<String> = vec![];
+        for (path, is_dir) in paths {
+            let components: Vec<String> = path
+                .components()
+                .map(|c| c.as_os_str().to_string_lossy().into_owned())
+                .collect();
+
+            // Only print the parts not shared with the previous path
+            let common = previous
+                .iter()
+                .zip(&components)
+                .take_while(|(a, b)| a == b)
+                .count();
+            for (depth, name) in components.iter().enumerate().skip(common) {
+                output.push_str(&"  ".repeat(depth));
+                output.push_str(name);
+                if depth + 1 < components.len() || is_dir {
+                    output.push('/');
+                }
+                output.push('\n');
+            }
+            previous = components;
+        }
+        output
+    }
+
+    // Wrap the file blocks in the prompt template, if one is set
+    // `{files}` expands to the blocks and `{tree}` to the file tree;
+    // without `{files}` the blocks follow the template
+    // The prompt suffix, if any, is appended after everything else
+    fn apply_template(&self, files_output: String, files: &[PathBuf]) -> String {
+        let mut output = match &self.config.prompt_template {
+            Some(template) => {
+                let mut output =
+                    template.replace("{tree}", &self.render_file_tree(files, &self.base_dir));
+                if output.contains("{files}") {
+                    output = output.replace("{files}", &files_output);
+                } else {
+                    output.push_str("\n\n");
+                    output.push_str(&files_output);
+                }
+                output
+            }
+            None => files_output,
+        };
+
+        if let Some(suffix) = &self.config.prompt_suffix {
+            output.push('\n');
+            output.push_str(suffix);
+            output.push('\n');
+        }
+        output
+    }
+
+    // Open the prompt for editing the suffix, prefilled with the current one
+    pub fn start_suffix_prompt(&mut self) {
+        self.start_prompt(Prompt::EditSuffix);
+        self.prompt_input
+            .set(self.config.prompt_suffix.clone().unwrap_or_default());
+    }
+
+    // Set the text appended after copied files, removing it when empty
+    fn set_prompt_suffix(&mut self, suffix: String) {
+        if suffix.trim().is_empty() {
+            self.config.prompt_suffix = None;
+            self.set_footer_message("Prompt suffix removed");
+        } else {
+            self.config.prompt_suffix = Some(suffix);
+            self.set_footer_message("Prompt suffix saved");
+        }
+        self.save_config();
+    }
+
+    // Open the prompt for editing the template, prefilled with the current one
+    pub fn start_template_prompt(&mut self) {
+        self.start_prompt(Prompt::EditTemplate);
+        self.prompt_input
+            .set(self.config.prompt_template.clone().unwrap_or_default());
+    }
+
+    // Set the prompt template, removing it when empty
+    fn set_prompt_template(&mut self, template: String) {
+        if template.trim().is_empty() {
+            self.config.prompt_template = None;
+            self.set_footer_message("Prompt template removed");
+        } else {
+            self.config.prompt_template = Some(template);
+            self.set_footer_message("Prompt template saved");
+        }
+        self.save_config();
+    }
+
+    // Files a copy of the selection would include, in order
+    pub fn resolve_copy_set(&self) -> Vec<PathBuf> {
+        self.collect_copy_set().0
+    }
+
+    // Files a copy of the selection would include, in path order without duplicates
+    // or binaries, and how many unreadable entries were skipped finding them
+    fn collect_copy_set(&self) -> (Vec<PathBuf>, usize, Vec<PathBuf>) {
+        let mut items: Vec<&PathBuf> = self.selected_items.iter().collect();
+        items.sort();
+
+        let mut seen = HashSet::new();
+        let mut all_files = Vec::new();
+        let mut skipped = 0;
+        let mut capped = Vec::new();
+
+        // Collect all files, including those in selected directories
+        for item in items {
+            if !self.config.follow_symlinks && item.is_symlink() {
+                continue;
+            }
+            let files = if item.is_file() {
+                vec![item.clone()]
+            } else if item.is_dir() {
+                let (files, unreadable, deep) = self.get_all_files_in_dir(item);
+                skipped += unreadable;
+                capped.extend(deep);
+                files
+            } else {
+                continue;
+            };
+            for file in files {
+                if !Self::is_probably_binary(&file) && seen.insert(file.clone()) {
+                    all_files.push(file);
+                }
+            }
+        }
+        if self.config.sort_copy_output {
+            all_files.sort();
+        }
+        (all_files, skipped, capped)
+    }
+
+    // Show the files the next copy would include before copying them
+    pub fn open_copy_preview(&mut self) {
+        if self.selected_items.is_empty() {
+            self.set_footer_message("Nothing selected");
+            return;
+        }
+        self.copy_preview = Some(self.resolve_copy_set());
+        self.copy_preview_text = None;
+        self.copy_preview_scroll = 0;
+    }
+
+    // Show the exact text the next copy would produce
+    pub fn open_copy_preview_text(&mut self) {
+        self.open_copy_preview();
+        if self.copy_preview.is_some() {
+            self.toggle_copy_preview_text();
+        }
+    }
+
+    // Close the copy preview in either view
+    pub fn close_copy_preview(&mut self) {
+        self.copy_preview = None;
+        self.copy_preview_text = None;
+    }
+
+    // Switch the copy preview between the list of files and the text a copy would produce,
+    // assembled the same way the copy itself does
+    pub fn toggle_copy_preview_text(&mut self) {
+        self.copy_preview_scroll = 0;
+        if self.copy_preview_text.take().is_some() {
+            return;
+        }
+        let files = self.copy_preview.clone().unwrap_or_default();
+        let dirs = self.selected_dirs();
+        let (output, _, _) = self.build_blocks(&files, &dirs, self.config.output_format);
+        self.copy_preview_text = Some(self.apply_template(output, &files));
+    }
+
+    // Scroll the copy preview, staying within its files or lines
+    pub fn scroll_copy_preview(&mut self, delta: isize) {
+        let len = match &self.copy_preview_text {
+            Some(text) => text.lines().count(),
+            None => self.copy_preview.as_ref().map_or(0, Vec::len),
+        };
+        self.copy_preview_scroll = self
+            .copy_preview_scroll
+            .saturating_add_signed(delta)
+            .min(len.saturating_sub(1));
+    }
+
+    // Add a collection of the given files under a name no other collection has
+    fn push_collection(&mut self, name: &str, files: Vec<PathBuf>) {
+        let collection = Collection {
+            name: self.unique_collection_name(name, None),
+            num_files: files.len(),
+            timestamp: chrono::Local::now(),
+            tags: vec![],
+            modified: Self::modification_times(&files),
+            files: files.into_iter().map(CollectionFile::new).collect(),
+            format: None,
+            description: String::new(),
+            pinned: false,
+            root: None,
+        };
+
+        self.collections.push(collection);
+        self.save_collections();
+    }
+
+    // Ask for a name to save the selection as a collection under, without copying
+    pub fn start_save_collection_prompt(&mut self) {
+        if self.selected_items.is_empty() {
+            self.set_footer_message("Nothing selected");
+            return;
+        }
+        self.start_prompt(Prompt::SaveCollection);
+        self.prompt_input
+            .set(format!("Collection {}", self.collections.len() + 1));
+    }
+
+    // Save the files a copy of the selection would include as a named collection,
+    // keeping the selection
+    fn save_selection_as_collection(&mut self, name: String) {
+        // Names are single-line, so pasted line breaks become spaces
+        let name = name.lines().collect::<Vec<_>>().join(" ");
+        let name = name.trim();
+        if name.is_empty() {
+            self.push_notification("Collection names can't be empty", NotificationLevel::Error);
+            return;
+        }
+        let files = self.resolve_copy_set();
+        if files.is_empty() {
+            self.set_footer_message("No text files in the selection");
+            return;
+        }
+        let count = files.len();
+        self.push_collection(name, files);
+        let saved_name = self.collections[self.collections.len() - 1].name.clone();
+        self.set_footer_message(format!("Saved {} files as {}", count, saved_name));
+    }
+
+    // Copy the selection and save it as a new collection, then clear the selection
+    // unless it should be kept for another round of copying
+    pub fn copy_selected_items_to_clipboard(&mut self, keep_selection: bool) {
+        use clipboard::{ClipboardContext, ClipboardProvider};
+
+        let (all_files, skipped, capped) = self.collect_copy_set();
+
+        let dirs = self.selected_dirs();
+        let (output, counts) = self.assemble_blocks(&all_files, &dirs, self.config.output_format);
+        let output = self.apply_template(output, &all_files);
+        let identical = self.record_copy(&all_files, &output);
+
+        // Copy to clipboard
+        let mut ctx: ClipboardContext = ClipboardProvider::new().unwrap();
+        ctx.set_contents(output).unwrap();
+
+        // Display success message in footer, warning if the output may be incomplete
+        let mut notes = vec![];
+        if skipped > 0 {
+            notes.push(format!("{} unreadable entries skipped", skipped));
+        }
+        notes.extend(self.copy_notes(&counts));
+        if notes.is_empty() {
+            self.set_footer_message("Copied to clipboard!");
+        } else {
+            self.set_footer_message(format!("Copied to clipboard! ({})", notes.join(", ")));
+        }
+        if identical {
+            self.set_footer_message("Identical to last copy");
+        }
+        self.warn_capped_dirs(&capped);
+
+        // Create new collection and add to collections
+        let collection_name = format!("Collection {}", self.collections.len() + 1);
+        self.push_collection(&collection_name, all_files);
+
+        if keep_selection {
+            return;
+        }
+
+        // Reset selected items and all_selected flag
+        self.selected_items.clear();
+        self.recursive_selection = None;
+        self.all_selected = false;
+    }
+
+    // Copy only the entry under the cursor, recursing into a directory, without
+    // touching the selection or creating a collection
+    pub fn copy_file_under_cursor(&mut self) {
+        use clipboard::{ClipboardContext, ClipboardProvider};
+
+        let Some(path) = self
+            .directory_entries
+            .get(self.selected_file_index)
+            .cloned()
+        else {
+            return;
+        };
+
+        let (files, skipped, capped) = if self.entry_is_dir(&path) {
+            self.get_all_files_in_dir(&path)
+        } else {
+            (vec![path.clone()], 0, vec![])
+        };
+        let (output, counts) = self.assemble_blocks(
+            &files,
+            std::slice::from_ref(&path),
+            self.config.output_format,
+        );
+        let output = self.apply_template(output, &files);
+        let identical = self.record_copy(&files, &output);
+
+        let mut ctx: ClipboardContext = ClipboardProvider::new().unwrap();
+        ctx.set_contents(output).unwrap();
+
+        let name = path
+            .file_name()
+            .map(|name| name.to_string_lossy().into_owned())
+            .unwrap_or_else(|| path.display().to_string());
+        let mut notes = vec![];
+        if skipped > 0 {
+            notes.push(format!("{} unreadable entries skipped", skipped));
+        }
+        notes.extend(self.copy_notes(&counts));
+        if notes.is_empty() {
+            self.set_footer_message(format!("Copied {} to clipboard!", name));
+        } else {
+            self.set_footer_message(format!(
+                "Copied {} to clipboard! ({})",
+                name,
+                notes.join(", ")
+            ));
+        }
+        if identical {
+            self.set_footer_message("Identical to last copy");
+        }
+        self.warn_capped_dirs(&capped);
+    }
+
+    // Path to show for a file, relative to the base directory unless absolute paths are on
+    // Files outside the base directory always show their absolute path
+    pub fn display_path(&self, path: &Path) -> String {
+        if self.config.use_absolute_paths {
+            path.display().to_string()
+        } else {
+            path.strip_prefix(&self.base_dir)
+                .unwrap_or(path)
+                .display()
+                .to_string()
+        }
+    }
+
+    // Copy the paths of the selected items to clipboard, one per line
+    pub fn copy_paths_to_clipboard(&mut self) {
+        use clipboard::{ClipboardContext, ClipboardProvider};
+
+        if self.selected_items.is_empty() {
+            return;
+        }
+
+        let mut paths: Vec<String> = self
+            .selected_items
+            .iter()
+            .map(|item| self.display_path(item))
+            .collect();
+        paths.sort();
+
+        // Copy to clipboard
+        let mut ctx: ClipboardContext = ClipboardProvider::new().unwrap();
+        ctx.set_contents(paths.join("\n")).unwrap();
+
+        // Display success message in footer
+        self.set_footer_message(format!("Copied {} paths to clipboard!", paths.len()));
+    }
+
+    // Copy the layout of the current directory, without file contents, to the clipboard
+    // Directories at the depth limit are listed but not walked
+    pub fn copy_directory_tree(&mut self) {
+        use clipboard::{ClipboardContext, ClipboardProvider};
+
+        let pray_ignore = self.pray_ignore.clone();
+        let walker = WalkBuilder::new(&self.current_dir)
+            .hidden(!self.config.show_hidden)
+            .git_ignore(self.config.respect_gitignore)
+            .follow_links(false)
+            .max_depth(Some(self.config.tree_max_depth.max(1)))
+            .filter_entry(move |entry| {
+                let is_dir = entry
+                    .file_type()
+                    .is_some_and(|file_type| file_type.is_dir());
+                !Self::is_pray_ignored(&pray_ignore, entry.path(), is_dir)
+            })
+            .build();
+        let nodes: Vec<PathBuf> = walker
+            .filter_map(Result::ok)
+            .filter(|entry| entry.depth() > 0)
+            .map(|entry| entry.into_path())
+            .collect();
+        if nodes.is_empty() {
+            self.set_footer_message("Nothing to copy, the directory is empty");
+            return;
+        }
+
+        let tree = self.render_file_tree(&nodes, &self.current_dir);
+        let mut ctx: ClipboardContext = ClipboardProvider::new().unwrap();
+        ctx.set_contents(tree).unwrap();
+
+        self.set_footer_message(format!(
+            "Copied tree of {} entries (depth {}) to clipboard!",
+            nodes.len(),
+            self.config.tree_max_depth.max(1)
+        ));
+    }
+
+    // Toggle between absolute and relative paths in copies and the selected files pane
+    pub fn toggle_absolute_paths(&mut self) {
+        self.config.use_absolute_paths = !self.config.use_absolute_paths;
+        self.save_config();
+        self.set_footer_message(format!(
+            "Absolute paths: {}",
+            if self.config.use_absolute_paths {
+                "on"
+            } else {
+                "off"
+            }
+        ));
+    }
+
+    // Show an informational message above the footer
+    pub fn set_footer_message(&mut self, message: impl Into<String>) {
+        self.push_notification(message, NotificationLevel::Info);
+    }
+
+    // Add a message to the notification stack. Warnings and errors stay up three
+    // times as long as other messages so they aren't missed
+    pub fn push_notification(&mut self, message: impl Into<String>, level: NotificationLevel) {
+        let text = message.into();
+        let mut duration = Duration::from_millis(self.config.message_duration_ms);
+        if level != NotificationLevel::Info {
+            duration *= 3;
+        }
+        let expiry = Instant::now() + duration;
+        self.needs_redraw = true;
+
+        // Repeating the newest message only keeps it up longer
+        if let Some(newest) = self.notifications.back_mut() {
+            if newest.text == text && newest.level == level {
+                newest.expiry = expiry;
+                return;
+            }
+        }
+        self.notifications.push_back(Notification {
+            text,
+            level,
+            expiry,
+        });
+        while self.notifications.len() > NOTIFICATION_LIMIT {
+            self.notifications.pop_front();
+        }
+    }
+
+    // How long to wait for input: the poll interval, cut short so the next
+    // notification disappears on time
+    pub fn poll_timeout(&self) -> Duration {
+        let interval = Duration::from_millis(self.config.poll_interval_ms.max(10));
+        // Also wake up when the fuzzy jump is due to rank its matches
+        let jump_due = self
+            .fuzzy_jump
+            .as_ref()
+            .and_then(|jump| jump.changed_at)
+            .map(|changed_at| changed_at + FUZZY_JUMP_DEBOUNCE);
+        let notification_due = self.notifications.iter().map(|n| n.expiry).min();
+        match notification_due.into_iter().chain(jump_due).min() {
+            Some(expiry) => interval.min(expiry.saturating_duration_since(Instant::now())),
+            None => interval,
+        }
+    }
+
+    // Show the directory sizes that finished computing since the last check
+    pub fn receive_dir_sizes(&mut self) {
+        if self.dir_sizes.get_mut().receive() {
+            self.needs_redraw = true;
+        }
+    }
+
+    // Size of an entry in the files pane, or `None` while a directory's size is
+    // still being computed
+    pub fn entry_size(&self, path: &Path) -> Option<u64> {
+        if self.entry_is_dir(path) {
+            self.dir_sizes.borrow_mut().get(path)
+        } else {
+            Some(fs::symlink_metadata(path).map(|m| m.len()).unwrap_or(0))
+        }
+    }
+
+    // Show or hide entry sizes in the files pane
+    pub fn toggle_sizes(&mut self) {
+        self.config.show_sizes = !self.config.show_sizes;
+        self.save_config();
+        self.set_footer_message(format!(
+            "Sizes: {}",
+            if self.config.show_sizes { "on" } else { "off" }
+        ));
+    }
+
+    // Drop the notifications whose display duration has passed
+    pub fn expire_notifications(&mut self) {
+        let now = Instant::now();
+        let before = self.notifications.len();
+        self.notifications
+            .retain(|notification| notification.expiry > now);
+        if self.notifications.len() != before {
+            self.needs_redraw = true;
+        }
+    }
+
+    // Indices of the collections shown with the current tag filter, in display order:
+    // pinned collections first, otherwise keeping their order
+    pub fn visible_collection_indices(&self) -> Vec<usize> {
+        let mut indices: Vec<usize> = self
+            .collections
+            .iter()
+            .enumerate()
+            .filter(|(_, collection)| match &self.tag_filter {
+                Some(tag) => collection.tags.contains(tag),
+                None => true,
+            })
+            .map(|(i, _)| i)
+            .collect();
+        indices.sort_by_key(|&i| !self.collections[i].pinned);
+        indices
+    }
+
+    // Move the cursor to the next visible collection
+    pub fn next_collection(&mut self) {
+        let visible = self.visible_collection_indices();
+        let next = match visible
+            .iter()
+            .position(|&i| i == self.selected_collection_index)
+        {
+            Some(position) => visible.get(position + 1),
+            None => visible.first(),
+        };
+        if let Some(&next) = next {
+            self.selected_collection_index = next;
+            self.selected_file_in_collection_index = 0;
+        }
+    }
+
+    // Move the cursor to the previous visible collection
+    pub fn previous_collection(&mut self) {
+        let visible = self.visible_collection_indices();
+        let previous = match visible
+            .iter()
+            .position(|&i| i == self.selected_collection_index)
+        {
+            Some(position) if position > 0 => visible.get(position - 1),
+            Some(_) => None,
+            None => visible.first(),
+        };
+        if let Some(&previous) = previous {
+            self.selected_collection_index = previous;
+            self.selected_file_in_collection_index = 0;
+        }
+    }
+
+    // Pin the selected collection to the top of the list, or unpin it
+    pub fn toggle_pin(&mut self) {
+        let Some(collection) = self.collections.get_mut(self.selected_collection_index) else {
+            return;
+        };
+        collection.pinned = !collection.pinned;
+        let message = format!(
+            "{} {}",
+            if collection.pinned {
+                "Pinned"
+            } else {
+                "Unpinned"
+            },
+            collection.name
+        );
+        self.save_collections();
+        self.set_footer_message(message);
+    }
+
+    // Add the tag to the selected collection, or remove it if already present
+    pub fn toggle_tag(&mut self, tag: &str) {
+        let tag = tag.trim();
+        if tag.is_empty() || self.collections.is_empty() {
+            return;
+        }
+
+        let collection = &mut self.collections[self.selected_collection_index];
+        if let Some(position) = collection.tags.iter().position(|t| t == tag) {
+            collection.tags.remove(position);
+            self.set_footer_message(format!("Removed tag {}", tag));
+        } else {
+            collection.tags.push(tag.to_string());
+            self.set_footer_message(format!("Added tag {}", tag));
+        }
+        self.save_collections();
+    }
+
+    // Only show collections with the given tag, or all of them if empty
+    pub fn set_tag_filter(&mut self, tag: &str) {
+        let tag = tag.trim();
+        if tag.is_empty() {
+            self.tag_filter = None;
+            self.set_footer_message("Tag filter cleared");
+        } else {
+            self.tag_filter = Some(tag.to_string());
+            self.set_footer_message(format!("Filtering by tag {}", tag));
+        }
+
+        // Keep the cursor on a visible collection. With none left, the cursor
+        // stays put but counts as on nothing until the filter changes
+        let visible = self.visible_collection_indices();
+        if !visible.contains(&self.selected_collection_index) {
+            match visible.first() {
+                Some(&first) => {
+                    self.selected_collection_index = first;
+                    self.selected_file_in_collection_index = 0;
+                }
+                None => self.set_footer_message(format!("No collections tagged {}", tag)),
+            }
+        }
+    }
+
+    // Whether the cursor is on a collection the tag filter shows, so there is
+    // one to act on
+    pub fn selected_collection_visible(&self) -> bool {
+        self.selected_collection_index < self.collections.len()
+            && self.tag_filter.as_ref().is_none_or(|tag| {
+                self.collections[self.selected_collection_index]
+                    .tags
+                    .contains(tag)
+            })
+    }
+
+    // Mark or unmark the selected collection for merging
+    pub fn toggle_collection_mark(&mut self) {
+        if self.collections.is_empty() {
+            return;
+        }
+        if !self
+            .marked_collections
+            .remove(&self.selected_collection_index)
+        {
+            self.marked_collections
+                .insert(self.selected_collection_index);
+        }
+    }
+
+    // Open the name prompt for merging the marked collections
+    pub fn start_merge_prompt(&mut self, delete_sources: bool) {
+        if self.marked_collections.len() < 2 {
+            self.set_footer_message("Mark at least two collections with Space");
+            return;
+        }
+        self.start_prompt(Prompt::MergeCollections { delete_sources });
+        self.prompt_input
+            .set(format!("Collection {}", self.collections.len() + 1));
+    }
+
+    // Merge the marked collections, optionally deleting them afterwards
+    fn merge_marked_collections(&mut self, name: String, delete_sources: bool) {
+        let mut indices: Vec<usize> = self.marked_collections.drain().collect();
+        indices.sort_unstable();
+
+        let mut merged_index = self.merge_collections(&indices, name);
+        if delete_sources {
+            // Remove from the back so earlier indices stay valid
+            for &i in indices.iter().rev() {
+                self.collections.remove(i);
+            }
+            merged_index -= indices.len();
+        }
+        // The name may be free only now that the sources are gone
+        let name = &self.collections[merged_index].name;
+        self.collections[merged_index].name = self.unique_collection_name(name, Some(merged_index));
+
+        self.selected_collection_index = merged_index;
+        self.selected_file_in_collection_index = 0;
+        self.save_collections();
+
+        self.set_footer_message(format!("Merged {} collections", indices.len()));
+    }
+
+    // Create a new collection with the deduplicated files of the given collections,
+    // returning its index
+    pub fn merge_collections(&mut self, indices: &[usize], name: String) -> usize {
+        let mut seen = HashSet::new();
+        let mut files = Vec::new();
+        for &i in indices {
+            for file in &self.collections[i].files {
+                if seen.insert(file.path.clone()) {
+                    files.push(file.clone());
+                }
+            }
+        }
+
+        let mut tags = Vec::new();
+        let mut modified = HashMap::new();
+        for &i in indices {
+            for tag in &self.collections[i].tags {
+                if !tags.contains(tag) {
+                    tags.push(tag.clone());
+                }
+            }
+            for (file, time) in &self.collections[i].modified {
+                modified.entry(file.clone()).or_insert(*time);
+            }
+        }
+
+        self.collections.push(Collection {
+            name,
+            num_files: files.len(),
+            files,
+            timestamp: chrono::Local::now(),
+            tags,
+            modified,
+            format: None,
+            description: String::new(),
+            pinned: false,
+            root: None,
+        });
+        self.save_collections();
+        self.collections.len() - 1
+    }
+
+    // Compute stats for the collection at the given index, reusing cached results
+    pub fn collection_stats(&self, index: usize) -> CollectionStats {
+        let files = self.collections[index].paths();
+        if let Some((cached_files, stats)) = &*self.stats_cache.borrow() {
+            if *cached_files == files {
+                return stats.clone();
+            }
+        }
+
+        let mut total_size = 0;
+        let mut largest_file: Option<(PathBuf, u64)> = None;
+        let mut types: HashMap<String, usize> = HashMap::new();
+        for file in &files {
+            let Ok(metadata) = fs::metadata(file) else {
+                continue;
+            };
+            let size = metadata.len();
+            total_size += size;
+            if largest_file
+                .as_ref()
+                .is_none_or(|(_, largest)| size > *largest)
+            {
+                largest_file = Some((file.clone(), size));
+            }
+            let extension = file
+                .extension()
+                .map(|ext| ext.to_string_lossy().to_lowercase())
+                .unwrap_or_else(|| "(none)".to_string());
+            *types.entry(extension).or_default() += 1;
+        }
+
+        let mut type_breakdown: Vec<(String, usize)> = types.into_iter().collect();
+        type_breakdown.sort_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.cmp(&b.0)));
+
+        let stats = CollectionStats {
+            file_count: files.len(),
+            total_size,
+            largest_file,
+            type_breakdown,
+            estimated_tokens: estimate_tokens(total_size),
+        };
+        *self.stats_cache.borrow_mut() = Some((files, stats.clone()));
+        stats
+    }
+
+    // Show the stats popup for the selected collection
+    pub fn open_collection_stats(&mut self) {
+        if !self.collections.is_empty() {
+            // Files may have changed since the stats were last shown
+            self.stats_cache.take();
+            self.show_collection_stats = true;
+        }
+    }
+
+    // Show the review screen of all selected items
+    pub fn open_selection_review(&mut self) {
+        if self.selected_items.is_empty() {
+            self.set_footer_message("Nothing selected");
+            return;
+        }
+        self.show_selection_review = true;
+        self.review_index = 0;
+    }
+
+    // Rows of the review screen: selected items grouped by parent directory,
+    // leaving out the items of collapsed groups
+    pub fn review_rows(&self) -> Vec<ReviewRow> {
+        let mut groups: BTreeMap<PathBuf, Vec<PathBuf>> = BTreeMap::new();
+        for item in &self.selected_items {
+            let parent = item.parent().unwrap_or(item).to_path_buf();
+            groups.entry(parent).or_default().push(item.clone());
+        }
+
+        let mut rows = vec![];
+        for (dir, mut items) in groups {
+            items.sort();
+            let collapsed = self.review_collapsed.contains(&dir);
+            rows.push(ReviewRow::Group(dir, items.len()));
+            if !collapsed {
+                rows.extend(items.into_iter().map(ReviewRow::Item));
+            }
+        }
+        rows
+    }
+
+    // Move the review cursor, staying within the rows
+    pub fn move_review_cursor(&mut self, delta: isize) {
+        let len = self.review_rows().len();
+        self.review_index = self
+            .review_index
+            .saturating_add_signed(delta)
+            .min(len.saturating_sub(1));
+    }
+
+    // Row index and directory of the review group the cursor is in
+    fn review_cursor_group(&self) -> Option<(usize, PathBuf)> {
+        let rows = self.review_rows();
+        rows.get(..=self.review_index)?
+            .iter()
+            .enumerate()
+            .rev()
+            .find_map(|(index, row)| match row {
+                ReviewRow::Group(dir, _) => Some((index, dir.clone())),
+                ReviewRow::Item(_) => None,
+            })
+    }
+
+    // Expand or collapse the group under the review cursor; on an item,
+    // collapse its group and move the cursor up to it
+    pub fn toggle_review_group(&mut self) {
+        let Some((index, dir)) = self.review_cursor_group() else {
+            return;
+        };
+        if index == self.review_index && self.review_collapsed.remove(&dir) {
+            return;
+        }
+        self.review_collapsed.insert(dir);
+        self.review_index = index;
+    }
+
+    // Collapse every group of the review, or expand them all if all are collapsed
+    pub fn toggle_all_review_groups(&mut self) {
+        let current = self.review_cursor_group().map(|(_, dir)| dir);
+        let dirs: HashSet<PathBuf> = self
+            .review_rows()
+            .into_iter()
+            .filter_map(|row| match row {
+                ReviewRow::Group(dir, _) => Some(dir),
+                ReviewRow::Item(_) => None,
+            })
+            .collect();
+        if dirs.is_subset(&self.review_collapsed) {
+            self.review_collapsed.clear();
+        } else {
+            self.review_collapsed = dirs;
+        }
+
+        // Keep the cursor on its group, which moved as rows above it changed
+        self.review_index = self
+            .review_rows()
+            .iter()
+            .position(
+                |row| matches!(row, ReviewRow::Group(dir, _) if Some(dir) == current.as_ref()),
+            )
+            .unwrap_or(0);
+    }
+
+    // Remove the item under the review cursor from the selection,
+    // or every item of the group when on a directory row
+    pub fn remove_review_row(&mut self) {
+        let removed = match self.review_rows().get(self.review_index) {
+            Some(ReviewRow::Group(dir, _)) => {
+                let before = self.selected_items.len();
+                self.selected_items
+                    .retain(|item| item.parent() != Some(dir.as_path()));
+                before - self.selected_items.len()
+            }
+            Some(ReviewRow::Item(path)) => {
+                self.selected_items.remove(path);
+                1
+            }
+            None => return,
+        };
+        self.all_selected = false;
+        self.recursive_selection = None;
+
+        let len = self.review_rows().len();
+        if len == 0 {
+            self.show_selection_review = false;
+        }
+        self.review_index = self.review_index.min(len.saturating_sub(1));
+        self.set_footer_message(format!("Removed {} from the selection", removed));
+    }
+
+    // Remove the selected collection
+    pub fn remove_selected_collection(&mut self) {
+        if self.collections.is_empty() {
+            return;
+        }
+
+        self.collections.remove(self.selected_collection_index);
+        self.marked_collections.clear();
+        if self.selected_collection_index >= self.collections.len()
+            && self.selected_collection_index > 0
+        {
+            self.selected_collection_index -= 1;
+        }
+        self.save_collections();
+    }
+
+    // Insert a copy of the collection right after it and move the cursor to the copy
+    pub fn duplicate_collection(&mut self, index: usize) {
+        let Some(original) = self.collections.get(index) else {
+            return;
+        };
+
+        let mut copy = original.clone();
+        copy.name = self.unique_collection_name(&format!("{} (copy)", original.name), None);
+        copy.timestamp = chrono::Local::now();
+        self.collections.insert(index + 1, copy);
+
+        // Keep marks pointing at the same collections
+        self.marked_collections = self
+            .marked_collections
+            .iter()
+            .map(|&i| if i > index { i + 1 } else { i })
+            .collect();
+
+        self.selected_collection_index = index + 1;
+        self.selected_file_in_collection_index = 0;
+        self.save_collections();
+        self.set_footer_message(format!(
+            "Duplicated collection: {}",
+            self.collections[index].name
+        ));
+    }
+
+    // Assemble the text for a collection in its format and file order
+    fn collection_output(&mut self, index: usize) -> (String, CopyCounts) {
+        let collection = &self.collections[index];
+        let format = collection.format.unwrap_or(self.config.output_format);
+        let files = self.collection_copy_paths(index);
+
+        let description = collection.description.trim().to_string();
+
+        let (output, counts) = self.assemble_blocks(&files, &[], format);
+        let mut output = self.apply_template(output, &files);
+        if !description.is_empty() {
+            output.insert_str(0, &format.comment(&description));
+        }
+        (output, counts)
+    }
+
+    // Files a copy of the collection includes, in its own order unless sorting by path
+    fn collection_copy_paths(&self, index: usize) -> Vec<PathBuf> {
+        let mut files = self.collections[index].enabled_paths();
+        if self.config.sort_collection_output {
+            files.sort();
+        }
+        files
+    }
+
+    // Footer notes about diffs found and files dropped over the token budget
+    fn copy_notes(&self, counts: &CopyCounts) -> Vec<String> {
+        let mut notes = vec![];
+        if self.config.output_mode == OutputMode::Diff {
+            notes.push(format!("{} files with diffs", counts.diffs));
+        }
+        if counts.dropped > 0 {
+            notes.push(format!(
+                "{} files dropped over the token budget",
+                counts.dropped
+            ));
+        }
+        notes
+    }
+
+    // Open the export prompt, prefilled with a file name based on the collection's name
+    pub fn start_export_prompt(&mut self) {
+        let Some(collection) = self.collections.get(self.selected_collection_index) else {
+            return;
+        };
+        let file_name = format!("{}.md", collection.name.replace('/', "_"));
+        self.start_prompt(Prompt::ExportCollection);
+        self.prompt_input.set(file_name);
+    }
+
+    // Write the selected collection to a file relative to the current directory,
+    // asking before replacing an existing file
+    pub fn export_collection_to_file(&mut self, input: &str) {
+        let input = input.trim();
+        if input.is_empty() || self.collections.is_empty() {
+            return;
+        }
+
+        let path = self.current_dir.join(input);
+        if path.exists() {
+            self.pending_overwrite = Some((path, Prompt::ExportCollection));
+            return;
+        }
+        self.write_export(&path);
+    }
+
+    // Open the zip export prompt, prefilled with a file name based on the collection's name
+    pub fn start_zip_export_prompt(&mut self) {
+        let Some(collection) = self.collections.get(self.selected_collection_index) else {
+            return;
+        };
+        let file_name = format!("{}.zip", collection.name.replace('/', "_"));
+        self.start_prompt(Prompt::ExportZip);
+        self.prompt_input.set(file_name);
+    }
+
+    // Bundle the selected collection's files into a zip archive relative to the
+    // current directory, asking before replacing an existing file
+    fn export_zip_to_file(&mut self, input: &str) {
+        let input = input.trim();
+        if input.is_empty() || self.collections.is_empty() {
+            return;
+        }
+
+        let path = self.current_dir.join(input);
+        if path.exists() {
+            self.pending_overwrite = Some((path, Prompt::ExportZip));
+            return;
+        }
+        self.write_zip_export(&path);
+    }
+
+    // Write the zip archive and report the result in the footer
+    fn write_zip_export(&mut self, path: &Path) {
+        match self.export_collection_as_zip(self.selected_collection_index, path) {
+            Ok((written, 0)) => {
+                self.set_footer_message(format!("Zipped {} files to {}", written, path.display()));
+                self.reload_current_directory();
+            }
+            Ok((written, missing)) => {
+                self.set_footer_message(format!(
+                    "Zipped {} files to {} ({} missing files skipped)",
+                    written,
+                    path.display(),
+                    missing
+                ));
+                self.reload_current_directory();
+            }
+            Err(err) => self.push_notification(
+                format!("Could not export to {}: {}", path.display(), err),
+                NotificationLevel::Error,
+            ),
+        }
+    }
+
+    // Write a collection's files into a zip archive under their paths relative to
+    // the base directory, returning how many were written and how many were missing
+    pub fn export_collection_as_zip(
+        &self,
+        index: usize,
+        dest: &Path,
+    ) -> io::Result<(usize, usize)> {
+        let mut zip = ZipWriter::new(io::BufWriter::new(fs::File::create(dest)?));
+        let mut written = 0;
+        let mut missing = 0;
+        for file in &self.collections[index].paths() {
+            let Ok(mut contents) = fs::File::open(file) else {
+                missing += 1;
+                continue;
+            };
+            let Ok(metadata) = contents.metadata() else {
+                missing += 1;
+                continue;
+            };
+            let name = file
+                .strip_prefix(&self.base_dir)
+                .unwrap_or(file)
+                .components()
+                .filter_map(|component| match component {
+                    std::path::Component::Normal(part) => Some(part.to_string_lossy()),
+                    _ => None,
+                })
+                .collect::<Vec<_>>()
+                .join("/");
+            let modified = metadata.modified().unwrap_or_else(|_| SystemTime::now());
+            // Zip timestamps are local times from 1980 on
+            let modified = chrono::DateTime::<chrono::Local>::from(modified).naive_local();
+            let options = SimpleFileOptions::default()
+                .compression_method(CompressionMethod::Deflated)
+                .last_modified_time(modified.try_into().unwrap_or_default())
+                // Files of 4 GiB or more need ZIP64 headers written up front
+                .large_file(metadata.len() >= u32::MAX as u64);
+            zip.start_file(name, options)?;
+            io::copy(&mut contents, &mut zip)?;
+            written += 1;
+        }
+        zip.finish()?;
+        Ok((written, missing))
+    }
+
+    // Answer the overwrite question: [o]verwrite, [r]ename or [c]ancel
+    pub fn resolve_overwrite(&mut self, choice: char) {
+        let Some((path, prompt)) = self.pending_overwrite.take() else {
+            return;
+        };
+        match choice {
+            'o' if matches!(prompt, Prompt::ExportZip) => self.write_zip_export(&path),
+            'o' => self.write_export(&path),
+            'r' => {
+                // Let the user edit the path that was taken
+                let relative_path = path.strip_prefix(&self.current_dir).unwrap_or(&path);
+                let input = relative_path.display().to_string();
+                self.start_prompt(prompt);
+                self.prompt_input.set(input);
+            }
+            'c' => self.set_footer_message("Export cancelled"),
+            // Keep asking on any other key
+            _ => self.pending_overwrite = Some((path, prompt)),
+        }
+    }
+
+    fn write_export(&mut self, path: &Path) {
+        let (output, counts) = self.collection_output(self.selected_collection_index);
+        match fs::write(path, output) {
+            Ok(()) => {
+                let notes = self.copy_notes(&counts);
+                self.set_footer_message(if notes.is_empty() {
+                    format!("Exported collection to {}", path.display())
+                } else {
+                    format!(
+                        "Exported collection to {} ({})",
+                        path.display(),
+                        notes.join(", ")
+                    )
+                });
+                self.reload_current_directory();
+            }
+            Err(err) => self.push_notification(
+                format!("Could not export to {}: {}", path.display(), err),
+                NotificationLevel::Error,
+            ),
+        }
+    }
+
+    // Copy files from the selected collection to clipboard
+    pub fn copy_selected_collection_to_clipboard(&mut self) {
+        use clipboard::{ClipboardContext, ClipboardProvider};
+
+        if self.collections.is_empty() {
+            return;
+        }
+
+        let (output, counts) = self.collection_output(self.selected_collection_index);
+        let files = self.collection_copy_paths(self.selected_collection_index);
+        let identical = self.record_copy(&files, &output);
+
+        // Copy to clipboard
+        let mut ctx: ClipboardContext = ClipboardProvider::new().unwrap();
+        ctx.set_contents(output).unwrap();
+
+        // Display success message in footer, noting files that are gone or changed
+        let missing = self
+            .validate_collection(self.selected_collection_index)
+            .len();
+        let changed = self.changed_files(self.selected_collection_index).len();
+        let mut notes = vec![];
+        if missing > 0 {
+            notes.push(format!("{} files missing", missing));
+        }
+        if changed > 0 {
+            notes.push(format!("{} changed since added", changed));
+        }
+        notes.extend(self.copy_notes(&counts));
+        if notes.is_empty() {
+            self.set_footer_message("Collection copied to clipboard!");
+        } else {
+            self.set_footer_message(format!(
+                "Collection copied to clipboard! ({})",
+                notes.join(", ")
+            ));
+        }
+        if identical {
+            self.set_footer_message("Identical to last copy");
+        }
+    }
+
+    // Current modification times of the given files
+    fn modification_times(files: &[PathBuf]) -> HashMap<PathBuf, SystemTime> {
+        files
+            .iter()
+            .filter_map(|file| {
+                let modified = fs::metadata(file).and_then(|m| m.modified()).ok()?;
+                Some((file.clone(), modified))
+            })
+            .collect()
+    }
+
+    // Files of the collection at the given index that no longer exist
+    pub fn validate_collection(&self, index: usize) -> Vec<PathBuf> {
+        self.collections[index]
+            .paths()
+            .into_iter()
+            .filter(|file| !file.is_file())
+            .collect()
+    }
+
+    // Files of the collection at the given index modified since they were added
+    pub fn changed_files(&self, index: usize) -> Vec<PathBuf> {
+        let collection = &self.collections[index];
+        collection
+            .paths()
+            .into_iter()
+            .filter(|file| {
+                let Some(added) = collection.modified.get(file) else {
+                    return false;
+                };
+                fs::metadata(file)
+                    .and_then(|m| m.modified())
+                    .is_ok_and(|current| current != *added)
+            })
+            .collect()
+    }
+
+    // Unselect a file from the selected collection
+    pub fn unselect_file_from_collection(&mut self) {
+        if self.collections.is_empty() {
             return;
         }
         let collection = &mut self.collections[self.selected_collection_index];
@@ -327,79 +3236,1759 @@ impl App {
                 .remove(self.selected_file_in_collection_index);
             collection.num_files = collection.files.len();
 
-            // Adjust index if necessary
-            if self.selected_file_in_collection_index >= collection.files.len()
-                && self.selected_file_in_collection_index > 0
-            {
-                self.selected_file_in_collection_index -= 1;
+            // Adjust index if necessary
+            if self.selected_file_in_collection_index >= collection.files.len()
+                && self.selected_file_in_collection_index > 0
+            {
+                self.selected_file_in_collection_index -= 1;
+            }
+
+            // Move this outside the mutable borrow of collection
+            self.save_collections();
+        }
+    }
+
+    // Include or leave out the file under the cursor when copying the selected collection
+    pub fn toggle_collection_file(&mut self) {
+        let Some(collection) = self.collections.get_mut(self.selected_collection_index) else {
+            return;
+        };
+        let Some(file) = collection
+            .files
+            .get_mut(self.selected_file_in_collection_index)
+        else {
+            return;
+        };
+        file.enabled = !file.enabled;
+        let (path, enabled) = (file.path.clone(), file.enabled);
+        self.save_collections();
+        self.set_footer_message(format!(
+            "{} {}",
+            if enabled {
+                "Included in copies:"
+            } else {
+                "Left out of copies:"
+            },
+            self.display_path(&path)
+        ));
+    }
+
+    // Pick another collection to move the file under the cursor to
+    pub fn start_move_to_collection(&mut self) {
+        let has_file = self
+            .collections
+            .get(self.selected_collection_index)
+            .is_some_and(|collection| {
+                self.selected_file_in_collection_index < collection.files.len()
+            });
+        if !has_file {
+            return;
+        }
+        self.open_popup(Popup::MoveToCollection);
+        self.popup_index = self.selected_collection_index;
+    }
+
+    // Move a file of the selected collection to another collection, leaving the
+    // source in place even when it ends up empty
+    pub fn move_file_between_collections(&mut self, file_idx: usize, dest_collection_idx: usize) {
+        let source_idx = self.selected_collection_index;
+        if dest_collection_idx == source_idx || dest_collection_idx >= self.collections.len() {
+            self.set_footer_message("Pick a different collection to move the file to");
+            return;
+        }
+        let Some(path) = self.collections[source_idx]
+            .files
+            .get(file_idx)
+            .map(|file| file.path.clone())
+        else {
+            return;
+        };
+        let dest = &self.collections[dest_collection_idx];
+        // Leave both alone rather than quietly dropping the file from the source
+        if dest.files.iter().any(|existing| existing.path == path) {
+            self.set_footer_message(format!(
+                "{} is already in {}",
+                self.display_path(&path),
+                dest.name
+            ));
+            return;
+        }
+
+        let source = &mut self.collections[source_idx];
+        let file = source.files.remove(file_idx);
+        let modified = source.modified.remove(&file.path);
+        source.num_files = source.files.len();
+        let remaining = source.files.len();
+
+        let dest = &mut self.collections[dest_collection_idx];
+        dest.files.push(file.clone());
+        if let Some(time) = modified {
+            dest.modified.insert(file.path.clone(), time);
+        }
+        dest.num_files = dest.files.len();
+        let dest_name = dest.name.clone();
+
+        // Keep the cursor on a file of the source collection
+        self.selected_file_in_collection_index = file_idx.min(remaining.saturating_sub(1));
+        self.save_collections();
+        self.set_footer_message(format!(
+            "Moved {} to {}",
+            self.display_path(&file.path),
+            dest_name
+        ));
+    }
+
+    // Schedule the collections to be written once changes settle down
+    fn save_collections(&mut self) {
+        self.collections_dirty_since = Some(Instant::now());
+    }
+
+    // Write the collections if they changed and nothing else changed for a moment
+    pub fn flush_collections_if_idle(&mut self) {
+        if self
+            .collections_dirty_since
+            .is_some_and(|since| since.elapsed() >= SAVE_DEBOUNCE)
+        {
+            self.flush_collections();
+        }
+    }
+
+    // Write pending collection changes now, through a temporary file so an
+    // interrupted write never leaves a truncated collections file
+    pub fn flush_collections(&mut self) {
+        if self.collections_dirty_since.take().is_none() {
+            return;
+        }
+        let result = self.write_collections();
+        self.record_save_result("collections", result);
+    }
+
+    // Keep a footer warning while saving fails, and drop it after the next successful save.
+    // The in-memory state is left as is, so nothing is lost until the app quits
+    fn record_save_result(&mut self, what: &str, result: io::Result<()>) {
+        match result {
+            Ok(()) => {
+                if self.save_warning.take().is_some() {
+                    self.needs_redraw = true;
+                }
+            }
+            Err(err) => {
+                self.save_warning = Some(format!("Could not save {} (read-only?): {}", what, err));
+                self.needs_redraw = true;
+            }
+        }
+    }
+
+    // Write the config, reporting a failure like any other save
+    fn save_config(&mut self) {
+        let result = self.config.save(&self.config_file);
+        self.record_save_result("config", result);
+    }
+
+    // Write the collections to `collections.json.tmp`, then replace the real file
+    // only once the data is safely on disk
+    fn write_collections(&self) -> io::Result<()> {
+        let stored: Vec<Collection> = self.collections.iter().map(Collection::to_stored).collect();
+        write_json_atomically(&self.collections_file, &stored)
+    }
+
+    // Open the description prompt for the selected collection, prefilled with the current one
+    pub fn start_description_prompt(&mut self) {
+        let Some(collection) = self.collections.get(self.selected_collection_index) else {
+            return;
+        };
+        let description = collection.description.clone();
+        self.start_prompt(Prompt::EditDescription);
+        self.prompt_input.set(description);
+    }
+
+    // Set the description of the selected collection
+    fn set_description(&mut self, description: String) {
+        let Some(collection) = self.collections.get_mut(self.selected_collection_index) else {
+            return;
+        };
+        collection.description = description.trim().to_string();
+        self.save_collections();
+        self.set_footer_message("Description saved");
+    }
+
+    // Start renaming a collection
+    pub fn start_rename(&mut self) {
+        if self.collections.is_empty() {
+            return;
+        }
+        self.renaming_collection = true;
+        let name = self.collections[self.selected_collection_index]
+            .name
+            .clone();
+        self.new_collection_name.set(name);
+    }
+
+    // Confirm the rename operation
+    pub fn confirm_rename(&mut self) {
+        if self.collections.is_empty() || !self.renaming_collection {
+            return;
+        }
+        // Names are single-line, so pasted line breaks become spaces
+        let requested = self.new_collection_name.take();
+        let requested = requested.lines().collect::<Vec<_>>().join(" ");
+        let name = self.unique_collection_name(&requested, Some(self.selected_collection_index));
+        let taken = name != requested;
+        self.collections[self.selected_collection_index].name = name.clone();
+        self.save_collections();
+        self.renaming_collection = false;
+        self.new_collection_name.clear();
+
+        // Display success message
+        if taken {
+            self.set_footer_message(format!(
+                "{} is taken, renamed to {} instead",
+                requested, name
+            ));
+        } else {
+            self.set_footer_message("Collection renamed!");
+        }
+    }
+
+    // The given name, or the first free "name (n)" if another collection has it
+    fn unique_collection_name(&self, name: &str, except: Option<usize>) -> String {
+        let is_taken = |candidate: &str| {
+            self.collections
+                .iter()
+                .enumerate()
+                .any(|(i, collection)| Some(i) != except && collection.name == candidate)
+        };
+        if !is_taken(name) {
+            return name.to_string();
+        }
+        (2..)
+            .map(|n| format!("{} ({})", name, n))
+            .find(|candidate| !is_taken(candidate))
+            .unwrap()
+    }
+
+    // Cancel the rename operation
+    pub fn cancel_rename(&mut self) {
+        if self.renaming_collection {
+            self.renaming_collection = false;
+            self.new_collection_name.clear();
+
+            // Display cancellation message
+            self.set_footer_message("Rename canceled.");
+        }
+    }
+
+    // Open a text prompt with empty input
+    pub fn start_prompt(&mut self, prompt: Prompt) {
+        self.active_prompt = Some(prompt);
+        self.prompt_input.clear();
+    }
+
+    // Run the action of the active prompt with its input
+    pub fn confirm_prompt(&mut self) {
+        let Some(prompt) = self.active_prompt.take() else {
+            return;
+        };
+        let input = self.prompt_input.take();
+        match prompt {
+            Prompt::SelectByGlob => {
+                self.select_by_glob(&input);
+            }
+            Prompt::LineRange => {
+                self.set_line_range(&input);
+            }
+            Prompt::Grep => {
+                self.search_contents(&input);
+            }
+            Prompt::EditTemplate => {
+                self.set_prompt_template(input);
+            }
+            Prompt::EditSuffix => {
+                self.set_prompt_suffix(input);
+            }
+            Prompt::ToggleTag => {
+                self.toggle_tag(&input);
+            }
+            Prompt::FilterByTag => {
+                self.set_tag_filter(&input);
+            }
+            Prompt::MergeCollections { delete_sources } => {
+                self.merge_marked_collections(input, delete_sources);
+            }
+            Prompt::ExportZip => {
+                self.export_zip_to_file(&input);
+            }
+            Prompt::ExportCollection => {
+                self.export_collection_to_file(&input);
+            }
+            Prompt::TokenBudget => {
+                self.set_token_budget(&input);
+            }
+            Prompt::EditDescription => {
+                self.set_description(input);
+            }
+            Prompt::JumpToDirectory => {
+                self.jump_to_directory(&input);
+            }
+            Prompt::FuzzyJump => {
+                self.confirm_fuzzy_jump(&input);
+            }
+            Prompt::SaveCollection => {
+                self.save_selection_as_collection(input);
+            }
+            Prompt::RenameFile => {
+                self.rename_file(&input);
+            }
+        }
+    }
+
+    // Open the line range prompt for the file under the cursor
+    pub fn start_line_range_prompt(&mut self) {
+        let Some(path) = self.directory_entries.get(self.selected_file_index) else {
+            return;
+        };
+        if !path.is_file() {
+            self.set_footer_message("Line ranges can only be set on files");
+            return;
+        }
+        let current_range = self
+            .line_ranges
+            .get(path)
+            .map(|(start, end)| format!("{}-{}", start, end));
+        self.start_prompt(Prompt::LineRange);
+        if let Some(range) = current_range {
+            self.prompt_input.set(range);
+        }
+    }
+
+    // Ask for a new name for the entry under the cursor, starting from its current one
+    pub fn start_rename_file_prompt(&mut self) {
+        let Some(name) = self
+            .directory_entries
+            .get(self.selected_file_index)
+            .and_then(|path| path.file_name())
+            .map(|name| name.to_string_lossy().into_owned())
+        else {
+            return;
+        };
+        self.start_prompt(Prompt::RenameFile);
+        self.prompt_input.set(name);
+    }
+
+    // Rename the entry under the cursor on disk, keeping the cursor, selections and
+    // line ranges on it under its new name
+    pub fn rename_file(&mut self, input: &str) {
+        let Some(path) = self
+            .directory_entries
+            .get(self.selected_file_index)
+            .cloned()
+        else {
+            return;
+        };
+        let name = input.trim();
+        if name.is_empty() || path.file_name().is_some_and(|old| old == name) {
+            return;
+        }
+        if name.contains('/') || name == "." || name == ".." {
+            self.push_notification(
+                format!("Not a file name: {}", name),
+                NotificationLevel::Error,
+            );
+            return;
+        }
+        let target = path.with_file_name(name);
+        // Don't let the rename replace whatever is there
+        if target.symlink_metadata().is_ok() {
+            self.push_notification(
+                format!("{} already exists", self.display_path(&target)),
+                NotificationLevel::Error,
+            );
+            return;
+        }
+        if let Err(err) = fs::rename(&path, &target) {
+            self.push_notification(
+                format!("Could not rename {}: {}", self.display_path(&path), err),
+                NotificationLevel::Error,
+            );
+            return;
+        }
+
+        // Point selections, line ranges, collections and bookmarks at the new
+        // path, including everything inside a renamed directory
+        let renamed = |item: &Path| -> Option<PathBuf> {
+            let rest = item.strip_prefix(&path).ok()?;
+            Some(if rest.as_os_str().is_empty() {
+                target.clone()
+            } else {
+                target.join(rest)
+            })
+        };
+        let mut collections_changed = false;
+        for collection in &mut self.collections {
+            for file in &mut collection.files {
+                if let Some(new_path) = renamed(&file.path) {
+                    file.path = new_path;
+                    collections_changed = true;
+                }
+            }
+            collection.modified = collection
+                .modified
+                .drain()
+                .map(|(item, time)| (renamed(&item).unwrap_or(item), time))
+                .collect();
+            if let Some(root) = collection.root.as_deref().and_then(renamed) {
+                collection.root = Some(root);
+                collections_changed = true;
+            }
+        }
+        let mut bookmarks_changed = false;
+        for bookmark in &mut self.config.bookmarks {
+            if let Some(new_path) = renamed(bookmark) {
+                *bookmark = new_path;
+                bookmarks_changed = true;
+            }
+        }
+        self.selected_items = self
+            .selected_items
+            .drain()
+            .map(|item| renamed(&item).unwrap_or(item))
+            .collect();
+        self.line_ranges = self
+            .line_ranges
+            .drain()
+            .map(|(item, range)| (renamed(&item).unwrap_or(item), range))
+            .collect();
+        self.recursive_selection = None;
+        if collections_changed {
+            self.save_collections();
+        }
+        if bookmarks_changed {
+            self.save_config();
+        }
+        self.save_session();
+
+        self.reload_current_directory();
+        self.reveal_path(&target);
+        self.set_footer_message(format!(
+            "Renamed {} to {}",
+            path.file_name().unwrap_or_default().to_string_lossy(),
+            name
+        ));
+    }
+
+    // Set the line range of the file under the cursor from input like "40-120"
+    // An empty input clears the range so the whole file is copied again
+    pub fn set_line_range(&mut self, input: &str) {
+        let Some(path) = self
+            .directory_entries
+            .get(self.selected_file_index)
+            .cloned()
+        else {
+            return;
+        };
+
+        let input = input.trim();
+        if input.is_empty() {
+            self.line_ranges.remove(&path);
+            self.set_footer_message("Line range cleared");
+            return;
+        }
+
+        let range = input
+            .split_once('-')
+            .and_then(|(start, end)| {
+                Some((
+                    start.trim().parse::<usize>().ok()?,
+                    end.trim().parse::<usize>().ok()?,
+                ))
+            })
+            .filter(|&(start, end)| start >= 1 && start <= end);
+
+        match range {
+            Some((start, end)) => {
+                self.line_ranges.insert(path, (start, end));
+                self.set_footer_message(format!("Line range set to {}-{}", start, end));
+            }
+            None => {
+                self.push_notification(
+                    format!("Invalid line range: {}", input),
+                    NotificationLevel::Error,
+                );
+            }
+        }
+    }
+
+    // Close the active prompt without running its action
+    pub fn cancel_prompt(&mut self) {
+        self.active_prompt = None;
+        self.prompt_input.clear();
+        self.fuzzy_jump = None;
+    }
+
+    // Open a list popup with the cursor at the top and nothing marked
+    fn open_popup(&mut self, popup: Popup) {
+        self.active_popup = Some(popup);
+        self.popup_index = 0;
+        self.popup_marked.clear();
+    }
+
+    // Close the active list popup
+    pub fn close_popup(&mut self) {
+        if matches!(self.active_popup, Some(Popup::GrepResults)) {
+            // Stop a search nobody is looking at anymore
+            self.grep_search = None;
+        }
+        self.active_popup = None;
+        self.popup_marked.clear();
+    }
+
+    // Number of entries in the active list popup
+    pub fn popup_len(&self) -> usize {
+        match self.active_popup {
+            Some(Popup::RecentFiles) => self.config.recent_files.len(),
+            Some(Popup::GrepResults) => self.grep_results.len().min(self.grep_shown),
+            Some(Popup::Bookmarks) => self.config.bookmarks.len(),
+            Some(Popup::MoveToCollection) => self.collections.len(),
+            Some(Popup::CopyHistory) => self.copy_history.records.len(),
+            Some(Popup::Selection) => self.selected_items.len(),
+            None => 0,
+        }
+    }
+
+    // Whether entries of the active popup can be marked to act on several at once
+    pub fn popup_is_markable(&self) -> bool {
+        !matches!(
+            self.active_popup,
+            Some(
+                Popup::Bookmarks | Popup::MoveToCollection | Popup::CopyHistory | Popup::Selection
+            )
+        )
+    }
+
+    // Mark or unmark the entry under the popup cursor
+    pub fn toggle_popup_mark(&mut self) {
+        if self.popup_index >= self.popup_len() || !self.popup_is_markable() {
+            return;
+        }
+        if !self.popup_marked.remove(&self.popup_index) {
+            self.popup_marked.insert(self.popup_index);
+        }
+    }
+
+    // Run the action of the active popup on the marked entries,
+    // or on the entry under the cursor if nothing is marked
+    pub fn confirm_popup(&mut self) {
+        let Some(popup) = self.active_popup else {
+            return;
+        };
+        let has_marks = !self.popup_marked.is_empty();
+        let mut indices: Vec<usize> = self.popup_marked.iter().copied().collect();
+        indices.sort_unstable();
+        if indices.is_empty() && self.popup_index < self.popup_len() {
+            indices.push(self.popup_index);
+        }
+        self.close_popup();
+
+        match popup {
+            Popup::RecentFiles => self.select_recent_files(&indices),
+            Popup::GrepResults if has_marks => self.select_grep_results(&indices),
+            Popup::GrepResults => {
+                if let Some(path) = indices.first().and_then(|&i| self.grep_results.get(i)) {
+                    self.reveal_path(&path.clone());
+                }
+            }
+            Popup::MoveToCollection => {
+                if let Some(&index) = indices.first() {
+                    self.move_file_between_collections(
+                        self.selected_file_in_collection_index,
+                        index,
+                    );
+                }
+            }
+            Popup::CopyHistory => {
+                if let Some(&index) = indices.first() {
+                    self.recopy_history(index);
+                }
+            }
+            Popup::Selection => {
+                if let Some(path) = indices
+                    .first()
+                    .and_then(|&i| self.sorted_selection().get(i).cloned())
+                {
+                    self.reveal_path(&path);
+                }
+            }
+            Popup::Bookmarks => {
+                if let Some(dir) = indices.first().and_then(|&i| self.config.bookmarks.get(i)) {
+                    let dir = dir.clone();
+                    if dir != self.current_dir {
+                        self.navigate_to(dir);
+                    }
+                }
+            }
+        }
+    }
+
+    // Check for NUL bytes near the start of a file, like git does
+    pub fn is_probably_binary(path: &Path) -> bool {
+        use std::io::Read;
+
+        let Ok(file) = fs::File::open(path) else {
+            return false;
+        };
+        let mut buffer = Vec::with_capacity(8000);
+        if file.take(8000).read_to_end(&mut buffer).is_err() {
+            return false;
+        }
+        buffer.contains(&0)
+    }
+
+    // Walk the current directory and scan its text files for the search term on
+    // a background thread, sending matches back in path order as they are found
+    // and stopping once the result limit is reached
+    pub fn grep_tree(&self, term: &str) -> GrepSearch {
+        let (sender, results) = mpsc::channel();
+        let cancel = Arc::new(AtomicBool::new(false));
+        let thread_cancel = Arc::clone(&cancel);
+        let thread_term = term.to_string();
+        let options = self.walk_options();
+        let dir = self.current_dir.clone();
+        std::thread::spawn(move || {
+            let (mut files, _, _) = Self::walk_files(&options, &dir);
+            files.sort();
+            let mut found = 0;
+            for path in files {
+                if thread_cancel.load(Ordering::Relaxed) {
+                    return;
+                }
+                if Self::is_probably_binary(&path) {
+                    continue;
+                }
+                let matched =
+                    fs::read_to_string(&path).is_ok_and(|contents| contents.contains(&thread_term));
+                if !matched {
+                    continue;
+                }
+                if sender.send(path).is_err() {
+                    return;
+                }
+                found += 1;
+                if found == GREP_RESULTS_LIMIT {
+                    return;
+                }
+            }
+        });
+        GrepSearch {
+            term: term.to_string(),
+            results,
+            cancel,
+            done: false,
+        }
+    }
+
+    // Search file contents and list the matches in a popup as they come in
+    fn search_contents(&mut self, term: &str) {
+        if term.is_empty() {
+            return;
+        }
+        self.grep_results.clear();
+        self.grep_shown = self.config.grep_max_results.max(1);
+        self.grep_search = Some(self.grep_tree(term));
+        self.open_popup(Popup::GrepResults);
+    }
+
+    // Move matches found since the last check into the results
+    pub fn receive_grep_results(&mut self) {
+        let Some(search) = &mut self.grep_search else {
+            return;
+        };
+        if search.done {
+            return;
+        }
+        loop {
+            match search.results.try_recv() {
+                Ok(path) => self.grep_results.push(path),
+                Err(TryRecvError::Empty) => break,
+                Err(TryRecvError::Disconnected) => {
+                    search.done = true;
+                    break;
+                }
+            }
+        }
+        self.needs_redraw = true;
+        if search.done && self.grep_results.is_empty() {
+            let message = format!("No files contain {}", search.term);
+            self.close_popup();
+            self.set_footer_message(message);
+        }
+    }
+
+    // List the next batch of search results
+    pub fn load_more_grep_results(&mut self) {
+        if self.grep_shown >= self.grep_results.len() {
+            return;
+        }
+        self.grep_shown += self.config.grep_max_results.max(1);
+        self.needs_redraw = true;
+    }
+
+    // Progress of the content search, e.g. "showing 200 of 1000+"
+    pub fn grep_status(&self) -> String {
+        let Some(search) = &self.grep_search else {
+            return String::new();
+        };
+        let found = self.grep_results.len();
+        let more = if search.done { "" } else { "+" };
+        if found > self.grep_shown {
+            format!(
+                "showing {} of {}{} files containing {}  [m] Load more",
+                self.grep_shown, found, more, search.term
+            )
+        } else if search.done && found >= GREP_RESULTS_LIMIT {
+            format!(
+                "stopped at the first {} files containing {}",
+                found, search.term
+            )
+        } else if search.done {
+            format!("{} files contain {}", found, search.term)
+        } else {
+            format!("{} files so far contain {}, searching…", found, search.term)
+        }
+    }
+
+    // Add the search results at the given indices to the selection
+    fn select_grep_results(&mut self, indices: &[usize]) {
+        let files: Vec<PathBuf> = indices
+            .iter()
+            .filter_map(|&i| self.grep_results.get(i).cloned())
+            .collect();
+        let count = files.len();
+        self.selected_items.extend(files);
+        self.set_footer_message(format!("Selected {} search results", count));
+    }
+
+    // Show the recently used files, pruning ones that no longer exist
+    pub fn open_recent_files(&mut self) {
+        let before = self.config.recent_files.len();
+        self.config.recent_files.retain(|path| path.is_file());
+        if self.config.recent_files.len() != before {
+            self.save_config();
+        }
+        self.open_popup(Popup::RecentFiles);
+    }
+
+    // Show the past copies, newest first
+    pub fn open_copy_history(&mut self) {
+        self.open_popup(Popup::CopyHistory);
+    }
+
+    // Log a copy in the history, returning whether its output is the same as the last copy's
+    fn record_copy(&mut self, files: &[PathBuf], output: &str) -> bool {
+        let mut hasher = DefaultHasher::new();
+        output.hash(&mut hasher);
+        let hash = hasher.finish();
+        let identical = self.last_copy_hash.replace(hash) == Some(hash);
+        if !files.is_empty() {
+            self.copy_history
+                .add(files, output.len(), self.config.copy_history_limit);
+            let result = self.copy_history.save(&self.copy_history_file);
+            self.record_save_result("copy history", result);
+        }
+        identical
+    }
+
+    // Copy the files of a past copy again, in the current format
+    fn recopy_history(&mut self, index: usize) {
+        use clipboard::{ClipboardContext, ClipboardProvider};
+
+        let Some(record) = self.copy_history.records.get(index) else {
+            return;
+        };
+        let files = record.files.clone();
+        let missing = files.iter().filter(|file| !file.is_file()).count();
+        let (output, counts) = self.assemble_blocks(&files, &[], self.config.output_format);
+        let output = self.apply_template(output, &files);
+        let identical = self.record_copy(&files, &output);
+
+        let mut ctx: ClipboardContext = ClipboardProvider::new().unwrap();
+        ctx.set_contents(output).unwrap();
+
+        let mut notes = vec![];
+        if missing > 0 {
+            notes.push(format!("{} missing", missing));
+        }
+        notes.extend(self.copy_notes(&counts));
+        if notes.is_empty() {
+            self.set_footer_message(format!("Copied {} files again", files.len()));
+        } else {
+            self.set_footer_message(format!(
+                "Copied {} files again ({})",
+                files.len(),
+                notes.join(", ")
+            ));
+        }
+        if identical {
+            self.set_footer_message("Identical to last copy");
+        }
+    }
+
+    // Save the past copy under the popup cursor as a new collection
+    pub fn save_history_as_collection(&mut self) {
+        let Some(record) = self.copy_history.records.get(self.popup_index) else {
+            return;
+        };
+        let files = record.files.clone();
+        self.close_popup();
+        let name = format!("Collection {}", self.collections.len() + 1);
+        self.push_collection(&name, files);
+        let saved_name = self.collections[self.collections.len() - 1].name.clone();
+        self.set_footer_message(format!("Saved copy as {}", saved_name));
+    }
+
+    // Bookmark the current directory, or remove its bookmark if it has one
+    pub fn toggle_bookmark(&mut self) {
+        let dir = self.current_dir.clone();
+        let message = if let Some(index) = self.config.bookmarks.iter().position(|b| *b == dir) {
+            self.config.bookmarks.remove(index);
+            format!("Removed bookmark {}", dir.display())
+        } else {
+            self.config.bookmarks.push(dir.clone());
+            format!("Bookmarked {}", dir.display())
+        };
+        self.save_config();
+        self.set_footer_message(message);
+    }
+
+    // Show the bookmarks, pointing out ones whose directory is gone
+    pub fn open_bookmarks(&mut self) {
+        self.open_popup(Popup::Bookmarks);
+        let missing = self
+            .config
+            .bookmarks
+            .iter()
+            .filter(|dir| !dir.is_dir())
+            .count();
+        if missing > 0 {
+            self.push_notification(
+                format!("{} bookmarks point to missing directories", missing),
+                NotificationLevel::Warning,
+            );
+        }
+    }
+
+    // Delete the bookmark under the popup cursor
+    pub fn delete_bookmark(&mut self) {
+        if self.popup_index >= self.config.bookmarks.len() {
+            return;
+        }
+        let dir = self.config.bookmarks.remove(self.popup_index);
+        self.save_config();
+        self.popup_index = self
+            .popup_index
+            .min(self.config.bookmarks.len().saturating_sub(1));
+        self.set_footer_message(format!("Removed bookmark {}", dir.display()));
+    }
+
+    // List everything selected, from any directory, to prune it without navigating there
+    pub fn open_selection_list(&mut self) {
+        if self.selected_items.is_empty() {
+            self.set_footer_message("Nothing selected");
+            return;
+        }
+        self.open_popup(Popup::Selection);
+    }
+
+    // Selected paths in the order the selection popup lists them
+    pub fn sorted_selection(&self) -> Vec<PathBuf> {
+        let mut paths: Vec<PathBuf> = self.selected_items.iter().cloned().collect();
+        paths.sort();
+        paths
+    }
+
+    // Unselect the path under the selection popup cursor
+    pub fn remove_from_selection_list(&mut self) {
+        let Some(path) = self.sorted_selection().get(self.popup_index).cloned() else {
+            return;
+        };
+        self.selected_items.remove(&path);
+        self.all_selected = false;
+        self.recursive_selection = None;
+        if self.selected_items.is_empty() {
+            self.close_popup();
+        }
+        self.popup_index = self
+            .popup_index
+            .min(self.selected_items.len().saturating_sub(1));
+        self.set_footer_message(format!("Unselected {}", self.display_path(&path)));
+    }
+
+    // Add the recent files at the given indices to the selection
+    fn select_recent_files(&mut self, indices: &[usize]) {
+        let files: Vec<PathBuf> = indices
+            .iter()
+            .filter_map(|&i| self.config.recent_files.get(i).cloned())
+            .collect();
+        let count = files.len();
+        self.selected_items.extend(files);
+
+        self.set_footer_message(format!("Selected {} recent files", count));
+    }
+
+    // Select all files under the current directory matching a glob pattern
+    pub fn select_by_glob(&mut self, pattern: &str) -> usize {
+        // `*` stays within a path segment, `**` crosses directories
+        let matcher = match GlobBuilder::new(pattern).literal_separator(true).build() {
+            Ok(glob) => glob.compile_matcher(),
+            Err(err) => {
+                self.push_notification(
+                    format!("Invalid glob pattern: {}", err),
+                    NotificationLevel::Error,
+                );
+                return 0;
+            }
+        };
+
+        // Match paths relative to the current directory
+        let current_dir = self.current_dir.clone();
+        let (files, _, _) = self.get_all_files_in_dir(&current_dir);
+        let matches: Vec<PathBuf> = files
+            .into_iter()
+            .filter(|path| matcher.is_match(path.strip_prefix(&current_dir).unwrap_or(path)))
+            .collect();
+        let count = matches.len();
+        self.selected_items.extend(matches);
+
+        self.set_footer_message(format!("Selected {} files matching {}", count, pattern));
+        count
+    }
+
+    // Re-read the current directory, keeping the cursor on the same entry when it
+    // still exists and dropping selections of files that vanished
+    pub fn refresh_directory(&mut self) {
+        self.dir_sizes.get_mut().invalidate(&self.current_dir);
+        let dropped = self.reconcile_directory();
+        self.set_footer_message(if dropped > 0 {
+            format!(
+                "Refreshed ({} vanished files removed from the selection)",
+                dropped
+            )
+        } else {
+            "Refreshed".to_string()
+        });
+    }
+
+    // Re-read the current directory in place, returning how many vanished
+    // files were removed from the selection
+    fn reconcile_directory(&mut self) -> usize {
+        let current = self
+            .directory_entries
+            .get(self.selected_file_index)
+            .cloned();
+        let visual_anchor = self.visual_anchor;
+        self.reload_current_directory();
+        if let Some(index) = current.and_then(|current| {
+            self.directory_entries
+                .iter()
+                .position(|entry| *entry == current)
+        }) {
+            self.selected_file_index = index;
+        }
+        self.visual_anchor = visual_anchor.filter(|&anchor| anchor < self.directory_entries.len());
+
+        let before = self.selected_items.len();
+        self.selected_items.retain(|item| item.exists());
+        before - self.selected_items.len()
+    }
+
+    // Move the watcher to the current directory, dropping the previous watch
+    fn watch_current_directory(&mut self) {
+        if let Err(err) = self.watcher.watch(&self.current_dir) {
+            self.set_footer_message(format!(
+                "Not watching {} for changes: {}",
+                self.current_dir.display(),
+                err
+            ));
+        }
+    }
+
+    // Re-read the current directory if the watcher saw it or its files change
+    pub fn check_for_directory_changes(&mut self) {
+        if self.watcher.changed() {
+            self.needs_redraw = true;
+            self.dir_sizes.get_mut().invalidate(&self.current_dir);
+            let dropped = self.reconcile_directory();
+            if dropped > 0 {
+                self.push_notification(
+                    format!("{} vanished files removed from the selection", dropped),
+                    NotificationLevel::Warning,
+                );
+            }
+        }
+    }
+
+    // Reload current directory
+    pub fn reload_current_directory(&mut self) {
+        if !self.current_dir.is_dir() {
+            self.leave_missing_directory();
+        }
+        // Files under the selection or in collections may have changed too
+        self.invalidate_selection_tokens();
+        self.stats_cache.take();
+        // Watch before reading so changes during the read are noticed later
+        self.watch_current_directory();
+        (self.directory_entries, self.entry_types) = match Self::read_directory(
+            &self.current_dir,
+            self.config.respect_gitignore,
+            self.config.show_hidden,
+            &self.pray_ignore,
+            self.config.sort_mode,
+        ) {
+            Ok(listing) => listing,
+            Err(err) => {
+                self.push_notification(
+                    Self::read_error_message(&self.current_dir, &err),
+                    NotificationLevel::Error,
+                );
+                Default::default()
+            }
+        };
+        self.visual_anchor = None;
+
+        // Keep the cursor within the entries if some disappeared
+        self.selected_file_index = self
+            .selected_file_index
+            .min(self.directory_entries.len().saturating_sub(1));
+    }
+
+    // Move to the nearest directory that still exists after the current one was
+    // removed, going back through the navigation history and then up its parents
+    fn leave_missing_directory(&mut self) {
+        let missing = self.current_dir.clone();
+        // Positions in the missing directories are of no use any more
+        self.cursor_positions.remove(&missing);
+        let mut previous = None;
+        while let Some((dir, index)) = self.navigation_stack.pop() {
+            if dir.is_dir() {
+                previous = Some((dir, index));
+                break;
+            }
+            self.cursor_positions.remove(&dir);
+        }
+        let (dir, index) = previous.unwrap_or_else(|| {
+            let parent = missing
+                .ancestors()
+                .skip(1)
+                .find(|dir| dir.is_dir())
+                .map_or_else(|| self.base_dir.clone(), Path::to_path_buf);
+            (parent, 0)
+        });
+
+        self.search_input.clear();
+        self.current_dir = dir;
+        self.selected_file_index = index;
+        self.push_notification(
+            format!(
+                "{} no longer exists, moved to {}",
+                missing.display(),
+                self.current_dir.display()
+            ),
+            NotificationLevel::Warning,
+        );
+    }
+
+    // Toggle method
+    pub fn toggle_gitignore(&mut self) {
+        self.config.respect_gitignore = !self.config.respect_gitignore;
+        self.save_config();
+        self.reload_current_directory();
+        self.set_footer_message(format!(
+            "Respect .gitignore: {}",
+            if self.config.respect_gitignore {
+                "on"
+            } else {
+                "off"
+            }
+        ));
+    }
+
+    // Persist the current selection so it can be resumed on the next launch
+    pub fn save_session(&mut self) {
+        let mut session = Session::load(&self.session_file);
+        session.set_selection(&self.base_dir, &self.selected_items);
+        let result = session.save(&self.session_file);
+        self.record_save_result("session", result);
+    }
+
+    // Clear the whole selection across all directories
+    pub fn clear_selection(&mut self) {
+        if self.selected_items.is_empty() {
+            self.set_footer_message("Nothing selected");
+            return;
+        }
+        let count = self.selected_items.len();
+        self.selected_items.clear();
+        self.all_selected = false;
+        self.recursive_selection = None;
+        self.set_footer_message(format!("Cleared {} selected items", count));
+    }
+
+    // Clear the selection and forget the persisted one for this directory
+    pub fn clear_session(&mut self) {
+        self.selected_items.clear();
+        self.recursive_selection = None;
+        self.all_selected = false;
+        self.save_session();
+        self.set_footer_message("Selection and saved session cleared");
+    }
+
+    // Open the token budget prompt, prefilled with the current budget
+    pub fn start_token_budget_prompt(&mut self) {
+        self.start_prompt(Prompt::TokenBudget);
+        if let Some(budget) = self.config.token_budget {
+            self.prompt_input.set(budget.to_string());
+        }
+    }
+
+    // Set the token budget from input like "8000", removing it when empty
+    fn set_token_budget(&mut self, input: &str) {
+        let input = input.trim();
+        if input.is_empty() {
+            self.config.token_budget = None;
+            self.set_footer_message("Token budget removed");
+        } else {
+            match input.parse::<usize>() {
+                Ok(budget) if budget > 0 => {
+                    self.config.token_budget = Some(budget);
+                    self.set_footer_message(format!("Token budget: {}", budget));
+                }
+                _ => {
+                    self.push_notification(
+                        format!("Invalid token budget: {}", input),
+                        NotificationLevel::Error,
+                    );
+                    return;
+                }
+            }
+        }
+        self.save_config();
+    }
+
+    // Switch to the next default copy format
+    pub fn cycle_output_format(&mut self) {
+        self.config.output_format = self.config.output_format.next();
+        self.save_config();
+        self.set_footer_message(format!(
+            "Default format: {}",
+            self.config.output_format.name()
+        ));
+    }
+
+    // Open the settings screen at the first option
+    pub fn open_settings(&mut self) {
+        self.show_settings = true;
+        self.settings_index = 0;
+    }
+
+    // Close the settings screen, keeping the changes for the next launch
+    pub fn close_settings(&mut self) {
+        self.show_settings = false;
+        self.save_config();
+    }
+
+    // Move the settings cursor, staying within the options
+    pub fn move_settings_cursor(&mut self, delta: isize) {
+        self.settings_index = self
+            .settings_index
+            .saturating_add_signed(delta)
+            .min(SETTINGS.len() - 1);
+    }
+
+    // Current value of an option as shown on the settings screen
+    pub fn setting_value(&self, setting: Setting) -> String {
+        let on_off = |on: bool| if on { "on" } else { "off" }.to_string();
+        match setting {
+            Setting::RespectGitignore => on_off(self.config.respect_gitignore),
+            Setting::FollowSymlinks => on_off(self.config.follow_symlinks),
+            Setting::ShowHidden => on_off(self.config.show_hidden),
+            Setting::StripComments => on_off(self.config.strip_comments),
+            Setting::Theme => self.theme.name.clone(),
+            Setting::OutputFormat => self.config.output_format.name().to_string(),
+            Setting::OutputMode => self.config.output_mode.name().to_string(),
+            Setting::Fence => self.config.fence.name(),
+            Setting::SortMode => self.config.sort_mode.name().to_string(),
+            Setting::CollectionView => self.config.collection_view.name().to_string(),
+            Setting::AbsolutePaths => on_off(self.config.use_absolute_paths),
+            Setting::ConfirmQuit => on_off(self.config.confirm_quit),
+            Setting::NerdFontIcons => on_off(self.config.nerd_font_icons),
+            Setting::LineCounts => on_off(self.config.show_line_counts),
+            Setting::FileMetadata => on_off(self.config.show_file_metadata),
+            Setting::Sizes => on_off(self.config.show_sizes),
+            Setting::CursorPath => on_off(self.config.show_cursor_path),
+            Setting::CopyGitChanges => on_off(self.config.copy_git_changes),
+            Setting::SortCopyOutput => on_off(self.config.sort_copy_output),
+            Setting::SortCollectionOutput => on_off(self.config.sort_collection_output),
+            Setting::MaxFileSize => match self.config.max_file_bytes {
+                Some(max_bytes) => format!("{} KB", max_bytes / 1024),
+                None => "off".to_string(),
+            },
+        }
+    }
+
+    // Toggle or cycle the option under the settings cursor
+    pub fn change_setting(&mut self) {
+        let Some(&setting) = SETTINGS.get(self.settings_index) else {
+            return;
+        };
+        match setting {
+            Setting::RespectGitignore => self.toggle_gitignore(),
+            Setting::FollowSymlinks => self.toggle_follow_symlinks(),
+            Setting::ShowHidden => self.toggle_show_hidden(),
+            Setting::StripComments => self.toggle_strip_comments(),
+            Setting::Theme => self.cycle_theme(),
+            Setting::OutputFormat => self.cycle_output_format(),
+            Setting::OutputMode => self.cycle_output_mode(),
+            Setting::Fence => self.cycle_fence(),
+            Setting::SortMode => self.cycle_sort_mode(),
+            Setting::CollectionView => self.cycle_collection_view(),
+            Setting::AbsolutePaths => self.toggle_absolute_paths(),
+            Setting::ConfirmQuit => {
+                self.config.confirm_quit = !self.config.confirm_quit;
+            }
+            Setting::NerdFontIcons => {
+                self.config.nerd_font_icons = !self.config.nerd_font_icons;
+            }
+            Setting::LineCounts => {
+                self.config.show_line_counts = !self.config.show_line_counts;
+            }
+            Setting::FileMetadata => {
+                self.config.show_file_metadata = !self.config.show_file_metadata;
+            }
+            Setting::Sizes => {
+                self.config.show_sizes = !self.config.show_sizes;
+            }
+            Setting::CursorPath => {
+                self.config.show_cursor_path = !self.config.show_cursor_path;
+            }
+            Setting::CopyGitChanges => {
+                self.config.copy_git_changes = !self.config.copy_git_changes;
+            }
+            Setting::SortCopyOutput => {
+                self.config.sort_copy_output = !self.config.sort_copy_output;
+            }
+            Setting::SortCollectionOutput => {
+                self.config.sort_collection_output = !self.config.sort_collection_output;
+            }
+            Setting::MaxFileSize => {
+                // Step through a few common caps, then back to no cap
+                let position = MAX_FILE_BYTES_PRESETS
+                    .iter()
+                    .position(|&preset| Some(preset) == self.config.max_file_bytes);
+                self.config.max_file_bytes = match position {
+                    Some(position) => MAX_FILE_BYTES_PRESETS.get(position + 1).copied(),
+                    None => MAX_FILE_BYTES_PRESETS.first().copied(),
+                };
+            }
+        }
+    }
+
+    // Cycle the code fence used around files in the markdown format
+    pub fn cycle_fence(&mut self) {
+        self.config.fence = self.config.fence.next();
+        self.save_config();
+        self.set_footer_message(format!("Code fence: {}", self.config.fence.name()));
+    }
+
+    // Cycle the selected collection's format through each format and back to the default
+    pub fn cycle_collection_format(&mut self) {
+        let Some(collection) = self.collections.get_mut(self.selected_collection_index) else {
+            return;
+        };
+
+        collection.format = match collection.format {
+            None => Some(OutputFormat::default()),
+            Some(format) if format.next() == OutputFormat::default() => None,
+            Some(format) => Some(format.next()),
+        };
+        let message = match collection.format {
+            Some(format) => format!("Collection format: {}", format.name()),
+            None => "Collection format: default".to_string(),
+        };
+        self.save_collections();
+        self.set_footer_message(message);
+    }
+
+    // Cycle between copying full contents, git diffs and one block per directory
+    pub fn cycle_output_mode(&mut self) {
+        self.config.output_mode = self.config.output_mode.next();
+        self.save_config();
+        self.set_footer_message(format!("Output mode: {}", self.config.output_mode.name()));
+    }
+
+    // Switch to the next sort mode, keeping the cursor on the same entry
+    pub fn cycle_sort_mode(&mut self) {
+        let current = self
+            .directory_entries
+            .get(self.selected_file_index)
+            .cloned();
+        self.config.sort_mode = self.config.sort_mode.next();
+        self.save_config();
+        self.reload_current_directory();
+        if let Some(path) = current {
+            self.reveal_path(&path);
+        }
+        self.set_footer_message(format!("Sort: {}", self.config.sort_mode.name()));
+    }
+
+    // Switch to the next theme, including the custom one if present
+    pub fn cycle_theme(&mut self) {
+        let mut names: Vec<String> = BUILT_IN_THEMES.iter().map(|n| n.to_string()).collect();
+        if let Some(custom) = Theme::load_custom(&self.theme_file) {
+            if !names.contains(&custom.name) {
+                names.push(custom.name);
+            }
+        }
+        let current = names
+            .iter()
+            .position(|name| *name == self.theme.name)
+            .unwrap_or(0);
+        let next = &names[(current + 1) % names.len()];
+
+        self.theme = Theme::load(next, &self.theme_file);
+        self.config.theme = self.theme.name.clone();
+        self.save_config();
+        self.set_footer_message(format!("Theme: {}", self.theme.name));
+    }
+
+    // Toggle listing and collecting hidden files
+    pub fn toggle_show_hidden(&mut self) {
+        self.config.show_hidden = !self.config.show_hidden;
+        self.save_config();
+        self.reload_current_directory();
+        self.set_footer_message(format!(
+            "Hidden files: {}",
+            if self.config.show_hidden {
+                "shown"
+            } else {
+                "hidden"
+            }
+        ));
+    }
+
+    // Toggle following symlinks when collecting files
+    pub fn toggle_follow_symlinks(&mut self) {
+        self.config.follow_symlinks = !self.config.follow_symlinks;
+        self.save_config();
+        self.invalidate_selection_tokens();
+        self.set_footer_message(format!(
+            "Follow symlinks: {}",
+            if self.config.follow_symlinks {
+                "on"
+            } else {
+                "off"
+            }
+        ));
+    }
+
+    // Toggle stripping comments from copied source
+    pub fn toggle_strip_comments(&mut self) {
+        self.config.strip_comments = !self.config.strip_comments;
+        self.save_config();
+        self.set_footer_message(format!(
+            "Strip comments: {}",
+            if self.config.strip_comments {
+                "on"
+            } else {
+                "off"
+            }
+        ));
+    }
+
+    // Widen the focused pane by moving the split between files and collections
+    pub fn widen_focused_pane(&mut self, percent: i16) {
+        let delta = match self.focused_pane {
+            FocusedPane::FilesPane => percent,
+            FocusedPane::CollectionsPane | FocusedPane::SelectedFilesPane => -percent,
+        };
+        self.config.split_ratio = self
+            .config
+            .split_ratio
+            .saturating_add_signed(delta)
+            .clamp(MIN_SPLIT_RATIO, MAX_SPLIT_RATIO);
+        self.save_config();
+    }
+
+    // Cycle how the collections pane is split between the collection list and the
+    // selected files, moving focus away from a half that gets collapsed
+    pub fn cycle_collections_split(&mut self) {
+        let position = COLLECTIONS_SPLIT_PRESETS
+            .iter()
+            .position(|&preset| preset == self.config.collections_split);
+        self.config.collections_split = match position {
+            Some(position) => {
+                COLLECTIONS_SPLIT_PRESETS[(position + 1) % COLLECTIONS_SPLIT_PRESETS.len()]
+            }
+            None => COLLECTIONS_SPLIT_PRESETS[0],
+        };
+        self.save_config();
+
+        let message = match self.config.collections_split {
+            100 => {
+                if matches!(self.focused_pane, FocusedPane::SelectedFilesPane) {
+                    self.focus_pane(FocusedPane::CollectionsPane);
+                }
+                "Selected files collapsed".to_string()
+            }
+            0 => {
+                if matches!(self.focused_pane, FocusedPane::CollectionsPane) {
+                    self.focus_pane(FocusedPane::SelectedFilesPane);
+                }
+                "Collection list collapsed".to_string()
+            }
+            split => format!("Collection list: {}% of the pane", split),
+        };
+        self.set_footer_message(message);
+    }
+
+    // Rows of the focused pane's list, moved by a page
+    pub fn page_rows(&self) -> usize {
+        let area = match self.focused_pane {
+            FocusedPane::FilesPane => self.pane_areas.files.get(),
+            FocusedPane::CollectionsPane => self.pane_areas.collections.get(),
+            FocusedPane::SelectedFilesPane => self.pane_areas.selected_files.get(),
+        };
+        // Leave out the borders
+        usize::from(area.height.saturating_sub(2)).max(1)
+    }
+
+    // Move the cursor of the focused pane by the given number of rows
+    pub fn move_cursor(&mut self, delta: isize) {
+        match self.focused_pane {
+            FocusedPane::FilesPane => {
+                let last = self.directory_entries.len().saturating_sub(1);
+                self.selected_file_index = self
+                    .selected_file_index
+                    .saturating_add_signed(delta)
+                    .min(last);
+            }
+            FocusedPane::CollectionsPane => {
+                for _ in 0..delta.unsigned_abs() {
+                    if delta > 0 {
+                        self.next_collection();
+                    } else {
+                        self.previous_collection();
+                    }
+                }
+            }
+            FocusedPane::SelectedFilesPane => {
+                let Some(collection) = self.collections.get(self.selected_collection_index) else {
+                    return;
+                };
+                let last = collection.files.len().saturating_sub(1);
+                self.selected_file_in_collection_index = self
+                    .selected_file_in_collection_index
+                    .saturating_add_signed(delta)
+                    .min(last);
+            }
+        }
+    }
+
+    // Pane whose list contains the given screen position, and the row within it
+    fn pane_at(&self, column: u16, row: u16) -> Option<(FocusedPane, usize)> {
+        let position = Position::new(column, row);
+        let panes = [
+            (FocusedPane::FilesPane, self.pane_areas.files.get()),
+            (
+                FocusedPane::CollectionsPane,
+                self.pane_areas.collections.get(),
+            ),
+            (
+                FocusedPane::SelectedFilesPane,
+                self.pane_areas.selected_files.get(),
+            ),
+        ];
+        panes
+            .into_iter()
+            .find(|(_, area)| area.contains(position))
+            .map(|(pane, area)| (pane, (row - area.y) as usize))
+    }
+
+    // Focus the clicked pane and move its cursor to the clicked row,
+    // entering the directory on a double-click in the files pane
+    pub fn click_at(&mut self, column: u16, row: u16) {
+        let Some((pane, offset)) = self.pane_at(column, row) else {
+            return;
+        };
+        let is_double_click = self.last_click.is_some_and(|(time, c, r)| {
+            c == column && r == row && time.elapsed() <= DOUBLE_CLICK_INTERVAL
+        });
+        self.last_click = Some((Instant::now(), column, row));
+
+        match pane {
+            FocusedPane::FilesPane => {
+                let index = self.scroll_position + offset;
+                if index >= self.directory_entries.len() {
+                    return;
+                }
+                self.focus_pane(FocusedPane::FilesPane);
+                self.selected_file_index = index;
+                if is_double_click {
+                    self.last_click = None;
+                    self.enter_directory();
+                }
+            }
+            FocusedPane::CollectionsPane => {
+                let Some(index) = self.collection_at_row(offset) else {
+                    return;
+                };
+                self.focus_pane(FocusedPane::CollectionsPane);
+                if index != self.selected_collection_index {
+                    self.selected_collection_index = index;
+                    self.selected_file_in_collection_index = 0;
+                }
+            }
+            FocusedPane::SelectedFilesPane => {
+                let Some(collection) = self.collections.get(self.selected_collection_index) else {
+                    return;
+                };
+                let index = self.selected_files_scroll + offset;
+                if index >= collection.files.len() {
+                    return;
+                }
+                self.focus_pane(FocusedPane::SelectedFilesPane);
+                self.selected_file_in_collection_index = index;
             }
+        }
+    }
 
-            // Move this outside the mutable borrow of collection
-            self.save_collections();
+    // Number of rows a collection takes up in the collections pane
+    pub fn collection_item_height(&self, index: usize) -> usize {
+        match self.config.collection_view {
+            CollectionView::Verbose if self.collections[index].tags.is_empty() => 3,
+            CollectionView::Verbose => 4,
+            _ => 1,
+        }
+    }
+
+    // Index of the visible collection drawn at a row of the collections pane
+    fn collection_at_row(&self, row: usize) -> Option<usize> {
+        let mut top = 0;
+        for index in self
+            .visible_collection_indices()
+            .into_iter()
+            .skip(self.collections_scroll)
+        {
+            top += self.collection_item_height(index);
+            if row < top {
+                return Some(index);
+            }
         }
+        None
     }
 
-    // Save collections to the collections file
-    fn save_collections(&self) {
-        let file = fs::File::create(&self.collections_file).unwrap();
-        serde_json::to_writer(file, &self.collections).unwrap();
+    // Switch to the next level of detail in the collections pane
+    pub fn cycle_collection_view(&mut self) {
+        self.config.collection_view = self.config.collection_view.next();
+        self.save_config();
+        self.set_footer_message(format!(
+            "Collections view: {}",
+            self.config.collection_view.name()
+        ));
     }
 
-    // Start renaming a collection
-    pub fn start_rename(&mut self) {
-        if self.collections.is_empty() {
+    // Focus the pane under the mouse and move its cursor by the scrolled rows
+    pub fn scroll_at(&mut self, column: u16, row: u16, delta: isize) {
+        let Some((pane, _)) = self.pane_at(column, row) else {
             return;
+        };
+        if pane != self.focused_pane {
+            self.focus_pane(pane);
         }
-        self.renaming_collection = true;
-        self.new_collection_name = self.collections[self.selected_collection_index]
-            .name
-            .clone();
+        self.move_cursor(delta);
     }
 
-    // Confirm the rename operation
-    pub fn confirm_rename(&mut self) {
-        if self.collections.is_empty() || !self.renaming_collection {
+    // Focus a pane, resetting the horizontal scroll
+    pub fn focus_pane(&mut self, pane: FocusedPane) {
+        self.focused_pane = pane;
+        self.horizontal_scroll = 0;
+    }
+
+    // Scroll the focused pane horizontally by the given number of columns
+    pub fn scroll_horizontally(&mut self, columns: isize) {
+        self.horizontal_scroll = self.horizontal_scroll.saturating_add_signed(columns);
+    }
+
+    // Start typing a name search in the files pane
+    pub fn start_file_search(&mut self) {
+        self.searching = true;
+        self.search_input.clear();
+    }
+
+    // Stop typing the search, keeping the query to step through matches
+    pub fn finish_file_search(&mut self) {
+        self.searching = false;
+    }
+
+    // Stop searching and forget the query
+    pub fn cancel_file_search(&mut self) {
+        self.searching = false;
+        self.search_input.clear();
+    }
+
+    // Indices of the entries in the current directory whose names match the search
+    pub fn file_search_matches(&self) -> Vec<usize> {
+        let query = self.search_input.text();
+        if query.is_empty() {
+            return vec![];
+        }
+        self.directory_entries
+            .iter()
+            .enumerate()
+            .filter(|(_, entry)| {
+                let name = entry.file_name().unwrap_or(entry.as_os_str());
+                fuzzy_match(&name.to_string_lossy(), query).is_some()
+            })
+            .map(|(i, _)| i)
+            .collect()
+    }
+
+    // Handle a key while typing the search, moving the cursor to the nearest match
+    // from where it is; without matches the cursor stays put
+    pub fn handle_search_key(&mut self, key: KeyEvent) {
+        if !self.search_input.handle_key(key) {
             return;
         }
-        self.collections[self.selected_collection_index].name = self.new_collection_name.clone();
-        self.save_collections();
-        self.renaming_collection = false;
-        self.new_collection_name.clear();
+        let matches = self.file_search_matches();
+        if !matches.contains(&self.selected_file_index) {
+            self.step_search_match(true);
+        }
+    }
 
-        // Display success message
-        self.footer_message = Some("Collection renamed!".to_string());
-        self.message_counter = 5; // Display for 5 cycles
+    // Move the cursor to the next or previous match, wrapping around the ends
+    pub fn step_search_match(&mut self, forward: bool) {
+        let matches = self.file_search_matches();
+        let cursor = self.selected_file_index;
+        let target = if forward {
+            matches.iter().find(|&&i| i > cursor).or(matches.first())
+        } else {
+            matches
+                .iter()
+                .rev()
+                .find(|&&i| i < cursor)
+                .or(matches.last())
+        };
+        if let Some(&index) = target {
+            self.selected_file_index = index;
+        }
     }
 
-    // Cancel the rename operation
-    pub fn cancel_rename(&mut self) {
-        if self.renaming_collection {
-            self.renaming_collection = false;
-            self.new_collection_name.clear();
+    // Footer status of the search, like "3/12 matches"
+    pub fn search_status(&self) -> String {
+        let matches = self.file_search_matches();
+        if matches.is_empty() {
+            return "no matches".to_string();
+        }
+        match matches.iter().position(|&i| i == self.selected_file_index) {
+            Some(position) => format!("{}/{} matches", position + 1, matches.len()),
+            None => format!("{} matches", matches.len()),
+        }
+    }
 
-            // Display cancellation message
-            self.footer_message = Some("Rename canceled.".to_string());
-            self.message_counter = 5; // Display for 5 cycles
+    // Enter quick jump mode if there are files to jump to
+    pub fn start_quick_jump(&mut self) {
+        self.quick_jump = !self.directory_entries.is_empty();
+    }
+
+    // Move the cursor to the visible file with the given label and leave quick jump mode
+    pub fn quick_jump_to(&mut self, label: char) {
+        self.quick_jump = false;
+        let Some(offset) = QUICK_JUMP_LABELS.chars().position(|c| c == label) else {
+            return;
+        };
+        let index = self.scroll_position + offset;
+        if offset < self.files_pane_height && index < self.directory_entries.len() {
+            self.selected_file_index = index;
         }
     }
 
-    // Reload current directory
-    pub fn reload_current_directory(&mut self) {
-        self.directory_entries = Self::read_directory(&self.current_dir, self.respect_gitignore);
+    // Scroll every pane so its cursor stays visible, using the heights they were drawn at
+    pub fn update_scrolls(&mut self) {
+        self.update_scroll(self.pane_areas.files.get().height as usize);
+        self.update_collections_scroll(self.pane_areas.collections.get().height as usize);
+        self.update_selected_files_scroll(self.pane_areas.selected_files.get().height as usize);
     }
 
-    // Toggle method
-    pub fn toggle_gitignore(&mut self) {
-        self.respect_gitignore = !self.respect_gitignore;
-        self.reload_current_directory();
-        self.footer_message = Some(format!(
-            "Respect .gitignore: {}",
-            if self.respect_gitignore { "on" } else { "off" }
-        ));
-        self.message_counter = 5;
+    // Scroll the collections pane by whole collections until the one under the
+    // cursor fits, since verbose collections take several rows
+    fn update_collections_scroll(&mut self, list_height: usize) {
+        let visible = self.visible_collection_indices();
+        let Some(cursor) = visible
+            .iter()
+            .position(|&index| index == self.selected_collection_index)
+        else {
+            self.collections_scroll = 0;
+            return;
+        };
+
+        let mut scroll = self.collections_scroll.min(cursor);
+        let rows_to_cursor = |scroll: usize| -> usize {
+            visible[scroll..=cursor]
+                .iter()
+                .map(|&index| self.collection_item_height(index))
+                .sum()
+        };
+        while scroll < cursor && rows_to_cursor(scroll) > list_height {
+            scroll += 1;
+        }
+        self.collections_scroll = scroll;
+    }
+
+    // Scroll the selected files pane just enough to keep its cursor visible
+    fn update_selected_files_scroll(&mut self, list_height: usize) {
+        let list_len = match self.focused_pane {
+            FocusedPane::FilesPane => self.selected_items.len(),
+            FocusedPane::CollectionsPane | FocusedPane::SelectedFilesPane => self
+                .collections
+                .get(self.selected_collection_index)
+                .map_or(0, |collection| collection.files.len()),
+        };
+        if list_height == 0 || list_len <= list_height {
+            self.selected_files_scroll = 0;
+            return;
+        }
+
+        let max_scroll = list_len - list_height;
+        let mut scroll = self.selected_files_scroll;
+        // Follow the files pane cursor while it is on a selected item, as the
+        // list then shows the selection in path order
+        let cursor = match self.focused_pane {
+            FocusedPane::FilesPane => {
+                let under_cursor = self.directory_entries.get(self.selected_file_index);
+                match self
+                    .sorted_selection()
+                    .iter()
+                    .position(|path| Some(path) == under_cursor)
+                {
+                    Some(position) => position,
+                    None => {
+                        self.selected_files_scroll = scroll.min(max_scroll);
+                        return;
+                    }
+                }
+            }
+            FocusedPane::CollectionsPane | FocusedPane::SelectedFilesPane => {
+                self.selected_file_in_collection_index
+            }
+        };
+        if cursor < scroll {
+            scroll = cursor;
+        } else if cursor >= scroll + list_height {
+            scroll = cursor + 1 - list_height;
+        }
+        self.selected_files_scroll = scroll.min(max_scroll);
     }
 
-    pub fn update_scroll(&mut self, list_height: usize) {
+    fn update_scroll(&mut self, list_height: usize) {
+        self.files_pane_height = list_height;
         let half_height = list_height.saturating_sub(1) / 2;
         let list_len = self.directory_entries.len();
 
@@ -413,3 +5002,800 @@ impl App {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // A scratch directory under the system temp dir, removed with everything
+    // in it when dropped, even if the test panics
+    struct TempDir(PathBuf);
+
+    impl TempDir {
+        fn new(name: &str) -> TempDir {
+            let path = std::env::temp_dir().join(format!("pray-{}-{}", name, std::process::id()));
+            let _ = fs::remove_dir_all(&path);
+            fs::create_dir_all(path.join("work")).unwrap();
+            TempDir(path)
+        }
+
+        // Directory the app is opened in, apart from its config and data
+        fn work(&self) -> PathBuf {
+            self.0.join("work")
+        }
+
+        // An app opened in the work directory, with its config and data kept
+        // here instead of in the user's directories
+        fn app(&self) -> App {
+            self.app_in(self.work())
+        }
+
+        fn app_in(&self, dir: PathBuf) -> App {
+            App::with_state_dirs(dir, &self.0.join("config"), &self.data())
+        }
+
+        fn data(&self) -> PathBuf {
+            self.0.join("data")
+        }
+    }
+
+    impl Drop for TempDir {
+        fn drop(&mut self) {
+            let _ = fs::remove_dir_all(&self.0);
+        }
+    }
+
+    // The newest message in the footer
+    fn last_message(app: &App) -> &str {
+        app.notifications.back().map_or("", |n| n.text.as_str())
+    }
+
+    #[test]
+    fn lang_overrides_set_the_fence_tag() {
+        let tmp = TempDir::new("lang-overrides");
+        let custom = tmp.work().join("rules.dsl");
+        let known = tmp.work().join("main.rs");
+        fs::write(&custom, "rule a\n").unwrap();
+        fs::write(&known, "fn main() {}\n").unwrap();
+
+        let mut app = tmp.app();
+        app.config
+            .lang_overrides
+            .insert("dsl".to_string(), "rules".to_string());
+        app.config
+            .lang_overrides
+            .insert("rs".to_string(), "rs".to_string());
+        let (output, _, _) = app.build_blocks(&[custom, known], &[], OutputFormat::Markdown);
+
+        assert!(
+            output.contains("``````rules\nrule a\n``````\n"),
+            "{}",
+            output
+        );
+        assert!(
+            output.contains("``````rs\nfn main() {}\n``````\n"),
+            "{}",
+            output
+        );
+    }
+
+    #[test]
+    fn walks_stop_at_the_max_depth() {
+        let tmp = TempDir::new("walk-depth");
+        let dir = tmp.work();
+        let deep = dir.join("a").join("b");
+        fs::create_dir_all(&deep).unwrap();
+        fs::create_dir_all(dir.join("empty")).unwrap();
+        fs::write(dir.join("top.txt"), "top\n").unwrap();
+        fs::write(deep.join("deep.txt"), "deep\n").unwrap();
+
+        let mut app = tmp.app();
+        app.config.walk_max_depth = 1;
+        let (files, skipped, capped) = app.get_all_files_in_dir(&dir);
+
+        assert_eq!(files, vec![dir.join("top.txt")]);
+        assert_eq!(skipped, 0);
+        assert_eq!(capped, vec![dir.join("a")]);
+    }
+
+    #[test]
+    fn hidden_files_can_be_left_out_and_the_choice_is_kept() {
+        let tmp = TempDir::new("hidden");
+        let dir = tmp.work();
+        fs::write(dir.join(".env"), "").unwrap();
+        fs::write(dir.join("main.rs"), "").unwrap();
+
+        let mut app = tmp.app();
+        assert_eq!(app.directory_entries.len(), 2);
+        app.toggle_show_hidden();
+
+        assert_eq!(app.directory_entries, [dir.join("main.rs")]);
+        assert_eq!(tmp.app().directory_entries, [dir.join("main.rs")]);
+    }
+
+    #[test]
+    fn selection_tokens_are_counted_in_the_background_and_recounted_on_reload() {
+        let tmp = TempDir::new("tokens");
+        let dir = tmp.work().join("src");
+        fs::create_dir_all(&dir).unwrap();
+        fs::write(dir.join("a.rs"), "x".repeat(400)).unwrap();
+
+        let mut app = tmp.app();
+        app.selected_items.insert(dir.clone());
+        let counted = |app: &mut App| {
+            for _ in 0..500 {
+                app.update_selection_tokens();
+                if app.token_count.is_none() {
+                    return app.selection_tokens;
+                }
+                std::thread::sleep(Duration::from_millis(10));
+            }
+            None
+        };
+        let before = counted(&mut app);
+        fs::write(dir.join("b.rs"), "x".repeat(400)).unwrap();
+        app.reload_current_directory();
+
+        assert_eq!(before, Some(estimate_tokens(400)));
+        assert_eq!(counted(&mut app), Some(2 * estimate_tokens(400)));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn the_cursor_path_shows_where_a_symlink_points() {
+        let tmp = TempDir::new("cursor-path");
+        let dir = tmp.work();
+        fs::write(dir.join("target.txt"), "").unwrap();
+        std::os::unix::fs::symlink(dir.join("target.txt"), dir.join("link")).unwrap();
+        std::os::unix::fs::symlink(dir.join("gone.txt"), dir.join("broken")).unwrap();
+        let target = dir.join("target.txt").canonicalize().unwrap();
+
+        let mut app = tmp.app();
+        app.reveal_path(&dir.join("target.txt"));
+        assert_eq!(
+            app.cursor_path(),
+            Some(dir.join("target.txt").display().to_string())
+        );
+        app.reveal_path(&dir.join("link"));
+        assert_eq!(
+            app.cursor_path(),
+            Some(format!(
+                "{} -> {}",
+                dir.join("link").display(),
+                target.display()
+            ))
+        );
+        app.reveal_path(&dir.join("broken"));
+        assert_eq!(
+            app.cursor_path(),
+            Some(format!("{} -> (broken link)", dir.join("broken").display()))
+        );
+    }
+
+    #[test]
+    fn a_removed_directory_is_left_for_one_that_exists() {
+        let tmp = TempDir::new("removed-dir");
+        let dir = tmp.work();
+        let nested = dir.join("a").join("b");
+        fs::create_dir_all(&nested).unwrap();
+
+        let mut app = tmp.app();
+        assert!(app.navigate_to(dir.join("a")));
+        assert!(app.navigate_to(nested));
+        fs::remove_dir_all(dir.join("a")).unwrap();
+        app.reload_current_directory();
+
+        assert_eq!(app.current_dir, dir);
+        assert!(app.navigation_stack.is_empty());
+        assert!(!app.cursor_positions.contains_key(&dir.join("a")));
+    }
+
+    #[test]
+    fn cursor_positions_are_forgotten_past_the_limit() {
+        let tmp = TempDir::new("cursor-positions");
+        let dir = tmp.work();
+        fs::create_dir_all(dir.join("sub")).unwrap();
+
+        let mut app = tmp.app();
+        for i in 0..CURSOR_POSITIONS_LIMIT {
+            app.cursor_positions.insert(dir.join(i.to_string()), 0);
+        }
+        app.enter_directory();
+        assert_eq!(app.cursor_positions.len(), 1);
+        assert_eq!(app.cursor_positions.get(&dir), Some(&0));
+        app.go_back();
+        assert_eq!(app.cursor_positions.len(), 2);
+    }
+
+    #[test]
+    fn the_watcher_follows_the_current_directory() {
+        let tmp = TempDir::new("watch");
+        let dir = tmp.work();
+        fs::create_dir_all(dir.join("sub")).unwrap();
+        fs::write(dir.join("a.txt"), "").unwrap();
+
+        let mut app = tmp.app();
+        // Events can take a moment to arrive, so wait for one before giving up
+        let changed = |app: &mut App| {
+            for _ in 0..100 {
+                if app.watcher.changed() {
+                    return true;
+                }
+                std::thread::sleep(Duration::from_millis(10));
+            }
+            false
+        };
+        fs::write(dir.join("a.txt"), "edited").unwrap();
+        assert!(changed(&mut app));
+        app.reveal_path(&dir.join("sub"));
+        app.enter_directory();
+        fs::write(dir.join("b.txt"), "").unwrap();
+        assert!(!changed(&mut app));
+        fs::write(dir.join("sub").join("c.txt"), "").unwrap();
+        assert!(changed(&mut app));
+        app.go_back();
+        fs::write(dir.join("c.txt"), "").unwrap();
+        for _ in 0..100 {
+            app.check_for_directory_changes();
+            if app.directory_entries.len() == 4 {
+                break;
+            }
+            std::thread::sleep(Duration::from_millis(10));
+        }
+
+        assert_eq!(
+            app.directory_entries,
+            [
+                dir.join("a.txt"),
+                dir.join("b.txt"),
+                dir.join("c.txt"),
+                dir.join("sub")
+            ]
+        );
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn an_unreadable_start_directory_is_reported() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let tmp = TempDir::new("unreadable");
+        let dir = tmp.work().join("locked");
+        fs::create_dir_all(&dir).unwrap();
+        fs::set_permissions(&dir, fs::Permissions::from_mode(0o000)).unwrap();
+        // Root can read it anyway, so there is nothing to report
+        let readable = fs::read_dir(&dir).is_ok();
+
+        let app = tmp.app_in(dir.clone());
+        fs::set_permissions(&dir, fs::Permissions::from_mode(0o755)).unwrap();
+        if !readable {
+            assert_eq!(
+                last_message(&app),
+                format!("Permission denied: {}", dir.display())
+            );
+        }
+        assert!(app.directory_entries.is_empty());
+
+        let missing = tmp.work().join("missing");
+        let app = tmp.app_in(missing.clone());
+        let expected = format!("Could not read {}: ", missing.display());
+        assert!(
+            last_message(&app).starts_with(&expected),
+            "{}",
+            last_message(&app)
+        );
+    }
+
+    #[test]
+    fn going_back_keeps_the_cursor_within_a_shrunken_directory() {
+        let tmp = TempDir::new("go-back");
+        let dir = tmp.work();
+        fs::create_dir_all(dir.join("z")).unwrap();
+        fs::write(dir.join("a.txt"), "").unwrap();
+        fs::write(dir.join("b.txt"), "").unwrap();
+
+        let mut app = tmp.app();
+        app.reveal_path(&dir.join("z"));
+        app.enter_directory();
+        fs::remove_file(dir.join("a.txt")).unwrap();
+        fs::remove_file(dir.join("b.txt")).unwrap();
+        assert_eq!(
+            app.navigation_stack.last().map(|(_, index)| *index),
+            Some(2)
+        );
+        app.go_back();
+
+        assert_eq!(app.current_dir, dir);
+        assert_eq!(app.selected_file_index, 0);
+    }
+
+    #[test]
+    fn copy_history_moves_out_of_the_config() {
+        let tmp = TempDir::new("history");
+        let config_dir = tmp.0.join("config");
+        fs::create_dir_all(&config_dir).unwrap();
+        let record = serde_json::json!({
+            "timestamp": "2024-01-02T03:04:05+00:00",
+            "files": ["a.rs"],
+            "bytes": 3,
+        });
+        let config = serde_json::json!({ "copy_history": [record] });
+        fs::write(config_dir.join("config.json"), config.to_string()).unwrap();
+
+        let mut app = tmp.app();
+        assert_eq!(app.copy_history.records.len(), 1);
+        app.save_config();
+
+        let saved = fs::read_to_string(config_dir.join("config.json")).unwrap();
+        assert!(!saved.contains("copy_history\""), "{}", saved);
+        assert_eq!(tmp.app().copy_history.records.len(), 1);
+    }
+
+    #[test]
+    fn a_malformed_collection_leaves_the_others() {
+        let tmp = TempDir::new("collections");
+        let path = tmp.work().join("collections.json");
+        let good = |name: &str| {
+            serde_json::json!({
+                "name": name,
+                "files": ["a.rs"],
+                "num_files": 1,
+                "timestamp": "2024-01-02T03:04:05+00:00",
+            })
+        };
+        let values = vec![
+            good("first"),
+            serde_json::json!({"name": 3}),
+            good("second"),
+        ];
+        fs::write(&path, serde_json::to_string(&values).unwrap()).unwrap();
+
+        let (collections, message) = App::load_collections(&path);
+
+        let names: Vec<&str> = collections.iter().map(|c| c.name.as_str()).collect();
+        assert_eq!(names, ["first", "second"]);
+        assert!(message.unwrap().starts_with("Skipped 1 of 3 collections"));
+        assert!(path.with_extension("json.bak").exists());
+    }
+
+    #[test]
+    fn concat_by_dir_joins_the_files_of_selected_directories() {
+        let tmp = TempDir::new("concat");
+        let dir = tmp.work();
+        let module = dir.join("module");
+        fs::create_dir_all(&module).unwrap();
+        let files = [
+            module.join("a.rs"),
+            module.join("b.rs"),
+            dir.join("main.rs"),
+        ];
+        for (file, contents) in files
+            .iter()
+            .zip(["fn a() {}\n", "fn b() {}\n", "fn main() {}\n"])
+        {
+            fs::write(file, contents).unwrap();
+        }
+
+        let mut app = tmp.app();
+        app.config.output_mode = OutputMode::ConcatByDir;
+        let (output, _, included) = app.build_blocks(&files, &[module], OutputFormat::Markdown);
+
+        assert_eq!(
+            output,
+            "------ module/ ------\n``````rust\n// ==== module/a.rs ====\nfn a() {}\n\
+             // ==== module/b.rs ====\nfn b() {}\n``````\n\
+             ------ main.rs ------\n``````rust\nfn main() {}\n``````\n"
+        );
+        assert_eq!(included, files);
+    }
+
+    #[test]
+    fn renaming_a_file_keeps_it_selected_and_refuses_to_overwrite() {
+        let tmp = TempDir::new("rename");
+        let dir = tmp.work();
+        fs::write(dir.join("a.txt"), "a\n").unwrap();
+        fs::write(dir.join("b.txt"), "b\n").unwrap();
+
+        let mut app = tmp.app();
+        app.reveal_path(&dir.join("a.txt"));
+        app.selected_items.insert(dir.join("a.txt"));
+        app.rename_file("b.txt");
+        assert!(dir.join("a.txt").exists());
+        app.rename_file("c.txt");
+
+        assert!(dir.join("c.txt").exists() && !dir.join("a.txt").exists());
+        assert_eq!(app.selected_items, HashSet::from([dir.join("c.txt")]));
+        assert_eq!(
+            app.directory_entries[app.selected_file_index],
+            dir.join("c.txt")
+        );
+    }
+
+    #[test]
+    fn renaming_a_directory_moves_collections_and_bookmarks_along() {
+        let tmp = TempDir::new("rename-dir");
+        let dir = tmp.work();
+        fs::create_dir_all(dir.join("old")).unwrap();
+        fs::write(dir.join("old").join("a.rs"), "").unwrap();
+        store_rooted_collection(&tmp, &dir.join("old"));
+
+        let mut app = tmp.app();
+        app.config.bookmarks.push(dir.join("old"));
+        app.reveal_path(&dir.join("old"));
+        app.rename_file("new");
+
+        let collection = &app.collections[0];
+        assert_eq!(
+            collection.paths(),
+            [dir.join("new").join("src").join("a.rs")]
+        );
+        assert_eq!(collection.root, Some(dir.join("new")));
+        assert_eq!(app.config.bookmarks, [dir.join("new")]);
+    }
+
+    // Write a collection of `src/a.rs` saved relative to the given root
+    fn store_rooted_collection(tmp: &TempDir, root: &Path) -> String {
+        let collection = Collection {
+            name: "c".to_string(),
+            files: vec![CollectionFile::new(root.join("src").join("a.rs"))],
+            num_files: 1,
+            timestamp: chrono::Local::now(),
+            tags: vec![],
+            modified: HashMap::new(),
+            format: None,
+            description: String::new(),
+            pinned: false,
+            root: Some(root.to_path_buf()),
+        };
+        let stored = serde_json::to_string(&[collection.to_stored()]).unwrap();
+        fs::create_dir_all(tmp.data()).unwrap();
+        fs::write(tmp.data().join("collections.json"), &stored).unwrap();
+        stored
+    }
+
+    #[test]
+    fn the_text_preview_shows_the_assembled_copy() {
+        let tmp = TempDir::new("text-preview");
+        fs::write(tmp.work().join("a.rs"), "fn a() {}\n").unwrap();
+
+        let mut app = tmp.app();
+        app.open_copy_preview_text();
+        assert!(app.copy_preview.is_none());
+        app.selected_items.insert(tmp.work().join("a.rs"));
+        app.config.prompt_suffix = Some("Review this.".to_string());
+        app.open_copy_preview_text();
+
+        let text = app.copy_preview_text.clone().unwrap();
+        assert!(text.contains("fn a() {}"), "{}", text);
+        assert!(text.ends_with("\nReview this.\n"), "{}", text);
+        app.toggle_copy_preview_text();
+        assert_eq!(
+            app.copy_preview.as_deref(),
+            Some(&[tmp.work().join("a.rs")][..])
+        );
+        assert!(app.copy_preview_text.is_none());
+    }
+
+    #[test]
+    fn moving_a_file_between_collections_refuses_duplicates_and_keeps_empty_sources() {
+        let tmp = TempDir::new("move-between");
+        let (a, b) = (tmp.work().join("a.rs"), tmp.work().join("b.rs"));
+        fs::write(&a, "").unwrap();
+        fs::write(&b, "").unwrap();
+
+        let mut app = tmp.app();
+        app.push_collection("one", vec![a.clone(), b.clone()]);
+        app.push_collection("two", vec![b.clone()]);
+        app.push_collection("three", vec![]);
+        app.move_file_between_collections(1, 1);
+        assert_eq!(last_message(&app), "b.rs is already in two");
+        assert_eq!(app.collections[0].paths(), [a.clone(), b.clone()]);
+
+        app.move_file_between_collections(0, 1);
+        assert_eq!(last_message(&app), "Moved a.rs to two");
+        assert_eq!(app.collections[1].paths(), [b.clone(), a.clone()]);
+        assert!(app.collections[1].modified.contains_key(&a));
+        app.move_file_between_collections(0, 2);
+
+        assert_eq!(app.collections.len(), 3);
+        assert!(app.collections[0].files.is_empty());
+        assert_eq!(app.collections[0].num_files, 0);
+        assert!(app.collections[0].modified.is_empty());
+        assert_eq!(app.collections[2].paths(), std::slice::from_ref(&b));
+        assert_eq!(app.collections[2].num_files, 1);
+    }
+
+    #[test]
+    fn zip_exports_deflate_files_under_their_relative_paths() {
+        let tmp = TempDir::new("zip-export");
+        let root = tmp.work();
+        fs::create_dir_all(root.join("src")).unwrap();
+        let contents = "fn a() {}\n".repeat(100);
+        fs::write(root.join("src").join("a.rs"), &contents).unwrap();
+        store_rooted_collection(&tmp, &root);
+
+        let mut app = tmp.app();
+        app.collections[0]
+            .files
+            .push(CollectionFile::new(root.join("gone.rs")));
+        let dest = tmp.0.join("c.zip");
+        assert_eq!(app.export_collection_as_zip(0, &dest).unwrap(), (1, 1));
+
+        let mut archive = zip::ZipArchive::new(fs::File::open(&dest).unwrap()).unwrap();
+        assert_eq!(archive.len(), 1);
+        let mut entry = archive.by_index(0).unwrap();
+        assert_eq!(entry.name(), "src/a.rs");
+        assert_eq!(entry.compression(), CompressionMethod::Deflated);
+        assert!(entry.compressed_size() < entry.size());
+        let mut unzipped = String::new();
+        io::Read::read_to_string(&mut entry, &mut unzipped).unwrap();
+        assert_eq!(unzipped, contents);
+    }
+
+    #[test]
+    fn collection_paths_follow_a_moved_directory_when_asked() {
+        let tmp = TempDir::new("moved");
+        let old = tmp.work().join("before").join("project");
+        let base = tmp.work().join("after").join("project");
+        fs::create_dir_all(base.join("src")).unwrap();
+        fs::write(base.join("src").join("a.rs"), "").unwrap();
+        let stored = store_rooted_collection(&tmp, &old);
+
+        let mut app = tmp.app_in(base.clone());
+        assert_eq!(app.collections[0].paths(), [old.join("src").join("a.rs")]);
+        assert_eq!(
+            fs::read_to_string(tmp.data().join("collections.json")).unwrap(),
+            stored
+        );
+        app.rebase_moved_collections();
+
+        assert_eq!(app.collections[0].paths(), [base.join("src").join("a.rs")]);
+        assert_eq!(app.collections[0].root, Some(base));
+    }
+
+    #[test]
+    fn a_tag_filter_leaves_the_cursor_on_a_shown_collection_or_none() {
+        let tmp = TempDir::new("tag-filter");
+        store_rooted_collection(&tmp, &tmp.work());
+        let mut app = tmp.app();
+        let first = app.collections[0].clone();
+        app.collections.push(Collection {
+            name: "tagged".to_string(),
+            tags: vec!["api".to_string()],
+            ..first
+        });
+
+        app.set_tag_filter("api");
+        assert_eq!(app.selected_collection_index, 1);
+        assert!(app.selected_collection_visible());
+        app.set_tag_filter("none");
+        assert!(!app.selected_collection_visible());
+        app.set_tag_filter("");
+        assert!(app.selected_collection_visible());
+    }
+
+    #[test]
+    fn an_unrelated_base_directory_is_left_alone() {
+        let tmp = TempDir::new("unrelated");
+        let old = tmp.work().join("before").join("project");
+        let base = tmp.work().join("elsewhere");
+        fs::create_dir_all(base.join("src")).unwrap();
+        fs::write(base.join("src").join("a.rs"), "").unwrap();
+        store_rooted_collection(&tmp, &old);
+        let absolute = tmp.work().join("gone").join("elsewhere");
+        let partial = Collection {
+            root: None,
+            files: vec![
+                CollectionFile::new(absolute.join("src").join("a.rs")),
+                CollectionFile::new(absolute.join("src").join("b.rs")),
+            ],
+            ..App::load_collections(&tmp.data().join("collections.json")).0[0].clone()
+        };
+
+        let mut app = tmp.app_in(base.clone());
+        app.rebase_moved_collections();
+
+        assert_eq!(app.collections[0].paths(), [old.join("src").join("a.rs")]);
+        assert_eq!(app.collections[0].root, Some(old));
+        // Only one of the two files turns up under the base directory
+        assert_eq!(partial.moved_from(&base), None);
+    }
+
+    #[test]
+    fn glob_selection_adds_matches_under_the_current_directory() {
+        let tmp = TempDir::new("glob-select");
+        let dir = tmp.work();
+        fs::create_dir_all(dir.join("src").join("nested")).unwrap();
+        for file in ["main.rs", "README.md", "src/lib.rs", "src/nested/deep.rs"] {
+            fs::write(dir.join(file), "").unwrap();
+        }
+
+        let mut app = tmp.app();
+        assert_eq!(app.select_by_glob("*.rs"), 1);
+        assert_eq!(last_message(&app), "Selected 1 files matching *.rs");
+        assert_eq!(app.select_by_glob("**/*.rs"), 3);
+        let mut selected: Vec<PathBuf> = app.selected_items.iter().cloned().collect();
+        selected.sort();
+        assert_eq!(
+            selected,
+            [
+                dir.join("main.rs"),
+                dir.join("src").join("lib.rs"),
+                dir.join("src").join("nested").join("deep.rs"),
+            ]
+        );
+
+        assert_eq!(app.select_by_glob("[*.rs"), 0);
+        assert!(last_message(&app).starts_with("Invalid glob pattern"));
+        assert_eq!(app.selected_items.len(), 3);
+    }
+
+    #[test]
+    fn visual_mode_toggles_the_range_between_the_anchor_and_the_cursor() {
+        let tmp = TempDir::new("visual");
+        let dir = tmp.work();
+        for file in ["a", "b", "c", "d"] {
+            fs::write(dir.join(file), "").unwrap();
+        }
+
+        let mut app = tmp.app();
+        app.selected_file_index = 2;
+        app.toggle_visual_mode();
+        app.move_cursor(-2);
+        assert_eq!(app.visual_range(), Some(0..=2));
+        app.toggle_visual_selection();
+        assert_eq!(app.visual_anchor, None);
+        let mut selected: Vec<PathBuf> = app.selected_items.iter().cloned().collect();
+        selected.sort();
+        assert_eq!(selected, [dir.join("a"), dir.join("b"), dir.join("c")]);
+
+        // A range with something unselected selects all of it, then deselects it
+        app.toggle_visual_mode();
+        app.move_cursor(3);
+        app.toggle_visual_selection();
+        assert_eq!(app.selected_items.len(), 4);
+        app.selected_file_index = 1;
+        app.toggle_visual_mode();
+        app.move_cursor(1);
+        app.toggle_visual_selection();
+        assert_eq!(app.selected_items.len(), 2);
+
+        app.toggle_visual_mode();
+        app.move_cursor(1);
+        app.exit_visual_mode();
+        assert_eq!(app.visual_anchor, None);
+        assert_eq!(app.selected_items.len(), 2);
+    }
+
+    #[test]
+    fn merging_collections_dedups_their_files_and_can_delete_the_sources() {
+        let tmp = TempDir::new("merge");
+        let (a, b, c) = (
+            tmp.work().join("a.rs"),
+            tmp.work().join("b.rs"),
+            tmp.work().join("c.rs"),
+        );
+        for file in [&a, &b, &c] {
+            fs::write(file, "").unwrap();
+        }
+
+        let mut app = tmp.app();
+        app.push_collection("one", vec![a.clone(), b.clone()]);
+        app.push_collection("two", vec![b.clone(), c.clone()]);
+        app.push_collection("other", vec![]);
+        let merged = app.merge_collections(&[0, 1], "both".to_string());
+        assert_eq!(
+            app.collections[merged].paths(),
+            [a.clone(), b.clone(), c.clone()]
+        );
+        assert_eq!(app.collections[merged].num_files, 3);
+        assert_eq!(app.collections.len(), 4);
+
+        app.marked_collections = HashSet::from([0, 1]);
+        app.merge_marked_collections("one".to_string(), true);
+        let names: Vec<&str> = app.collections.iter().map(|c| c.name.as_str()).collect();
+        assert_eq!(names, ["other", "both", "one"]);
+        assert_eq!(app.selected_collection_index, 2);
+        assert_eq!(app.collections[2].paths(), [a, b, c]);
+        assert!(app.marked_collections.is_empty());
+    }
+
+    #[test]
+    fn duplicating_a_collection_inserts_a_renamed_copy_after_it() {
+        let tmp = TempDir::new("duplicate");
+        let a = tmp.work().join("a.rs");
+        fs::write(&a, "").unwrap();
+
+        let mut app = tmp.app();
+        app.push_collection("one", vec![a.clone()]);
+        app.push_collection("two", vec![]);
+        app.marked_collections = HashSet::from([1]);
+        let original = app.collections[0].timestamp;
+        app.duplicate_collection(0);
+        app.duplicate_collection(0);
+
+        let names: Vec<&str> = app.collections.iter().map(|c| c.name.as_str()).collect();
+        assert_eq!(names, ["one", "one (copy) (2)", "one (copy)", "two"]);
+        assert_eq!(app.collections[1].paths(), std::slice::from_ref(&a));
+        assert!(app.collections[1].timestamp >= original);
+        assert_eq!(app.selected_collection_index, 1);
+        assert_eq!(app.marked_collections, HashSet::from([3]));
+    }
+
+    #[test]
+    fn recursive_selection_undoes_only_what_it_added() {
+        let tmp = TempDir::new("recursive-select");
+        let dir = tmp.work();
+        fs::create_dir_all(dir.join("src")).unwrap();
+        fs::write(dir.join("top.rs"), "").unwrap();
+        fs::write(dir.join("src").join("lib.rs"), "").unwrap();
+        let elsewhere = tmp.0.join("elsewhere.rs");
+
+        let mut app = tmp.app();
+        app.selected_items.insert(dir.join("top.rs"));
+        app.selected_items.insert(elsewhere.clone());
+        app.toggle_select_recursive();
+        assert_eq!(last_message(&app), "Selected 1 files recursively");
+        assert!(app.selected_items.contains(&dir.join("src").join("lib.rs")));
+        assert_eq!(app.selected_items.len(), 3);
+
+        app.toggle_select_recursive();
+        assert_eq!(last_message(&app), "Deselected 1 files");
+        assert_eq!(
+            app.selected_items,
+            HashSet::from([dir.join("top.rs"), elsewhere])
+        );
+    }
+
+    #[test]
+    fn collection_names_stay_unique_on_rename_and_create() {
+        let tmp = TempDir::new("unique-names");
+
+        let mut app = tmp.app();
+        app.push_collection("one", vec![]);
+        app.push_collection("two", vec![]);
+        app.push_collection("one", vec![]);
+        assert_eq!(app.collections[2].name, "one (2)");
+
+        app.selected_collection_index = 1;
+        app.renaming_collection = true;
+        app.new_collection_name.set("one".to_string());
+        app.confirm_rename();
+        assert_eq!(app.collections[1].name, "one (3)");
+        assert_eq!(
+            last_message(&app),
+            "one is taken, renamed to one (3) instead"
+        );
+
+        // Keeping its own name isn't a collision
+        app.renaming_collection = true;
+        app.new_collection_name.set("one (3)".to_string());
+        app.confirm_rename();
+        assert_eq!(app.collections[1].name, "one (3)");
+        assert_eq!(last_message(&app), "Collection renamed!");
+    }
+
+    #[test]
+    fn inverting_the_selection_leaves_other_directories_alone() {
+        let tmp = TempDir::new("invert");
+        let dir = tmp.work();
+        for file in ["a", "b", "c"] {
+            fs::write(dir.join(file), "").unwrap();
+        }
+        let elsewhere = tmp.0.join("elsewhere.rs");
+
+        let mut app = tmp.app();
+        app.selected_items.insert(dir.join("a"));
+        app.selected_items.insert(elsewhere.clone());
+        app.invert_selection();
+
+        assert_eq!(last_message(&app), "Selected 2 of 3 items here");
+        assert_eq!(
+            app.selected_items,
+            HashSet::from([dir.join("b"), dir.join("c"), elsewhere])
+        );
+        app.invert_selection();
+        assert_eq!(app.selected_items.len(), 2);
+        assert!(app.selected_items.contains(&dir.join("a")));
+    }
+}
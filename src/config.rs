@@ -0,0 +1,225 @@
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, VecDeque};
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use crate::history::CopyRecord;
+use crate::output::{Fence, OutputFormat, OutputMode};
+use crate::sort::SortMode;
+use crate::storage::write_pretty_json_atomically;
+
+// Maximum number of recently used files to remember
+pub const RECENT_FILES_LIMIT: usize = 20;
+
+// How much detail the collections pane shows per collection
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Default)]
+pub enum CollectionView {
+    // Name, file count and timestamp on one line, as far as they fit
+    #[default]
+    Normal,
+    // Just the name
+    Compact,
+    // Name, count, size, tags and timestamp on separate lines
+    Verbose,
+}
+
+impl CollectionView {
+    // The view that follows this one when cycling
+    pub fn next(self) -> CollectionView {
+        match self {
+            CollectionView::Normal => CollectionView::Compact,
+            CollectionView::Compact => CollectionView::Verbose,
+            CollectionView::Verbose => CollectionView::Normal,
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            CollectionView::Normal => "normal",
+            CollectionView::Compact => "compact",
+            CollectionView::Verbose => "verbose",
+        }
+    }
+}
+
+// User settings and state persisted across sessions
+#[derive(Serialize, Deserialize)]
+#[serde(default)]
+pub struct Config {
+    // Files most recently included in a copy, newest first
+    pub recent_files: VecDeque<PathBuf>,
+    // Past copies saved here by older versions, read once to move them to
+    // their own file and never written back
+    #[serde(rename = "copy_history", skip_serializing)]
+    pub legacy_copy_history: Vec<CopyRecord>,
+    // Number of copies kept in the history
+    pub copy_history_limit: usize,
+    // Content search results shown at first and added per "load more"
+    pub grep_max_results: usize,
+    // How long notifications stay visible, in milliseconds
+    pub message_duration_ms: u64,
+    // Longest wait for input between checks for expired messages and directory changes
+    pub poll_interval_ms: u64,
+    // Name of the color theme
+    pub theme: String,
+    // Text wrapped around copied files, with {tree} and {files} placeholders
+    pub prompt_template: Option<String>,
+    // Instructions appended after the copied files
+    pub prompt_suffix: Option<String>,
+    // Width of the files pane as a percentage of the screen
+    pub split_ratio: u16,
+    // Height of the collection list as a percentage of the collections pane, the
+    // selected files getting the rest; 0 or 100 collapses one of them
+    pub collections_split: u16,
+    // Order of entries in the files pane
+    pub sort_mode: SortMode,
+    // Fence tags for extensions, replacing or adding to the built-in ones
+    pub lang_overrides: HashMap<String, String>,
+    // Copy format for selections and collections without their own
+    pub output_format: OutputFormat,
+    // Estimated tokens to stop copying at, if any
+    pub token_budget: Option<usize>,
+    // Detail level of the collections pane
+    pub collection_view: CollectionView,
+    // Use absolute instead of base-relative paths in copies
+    pub use_absolute_paths: bool,
+    // Ask before quitting with items selected but not copied
+    pub confirm_quit: bool,
+    // Code fence around files in the markdown format
+    pub fence: Fence,
+    // Show nerd font glyphs instead of ASCII symbols in the files pane
+    pub nerd_font_icons: bool,
+    // Show file and directory sizes in the files pane
+    pub show_sizes: bool,
+    // Show the absolute path of the entry under the cursor in the footer instead of key hints
+    pub show_cursor_path: bool,
+    // Deepest level walked when copying the directory tree
+    pub tree_max_depth: usize,
+    // Deepest level below the base directory searched by the fuzzy directory jump
+    pub jump_max_depth: usize,
+    // Deepest level walked when collecting the files of a selected directory
+    pub walk_max_depth: usize,
+    // Directories to jump to from the bookmarks popup
+    pub bookmarks: Vec<PathBuf>,
+    // Bytes of each file to copy before truncating it, if any
+    pub max_file_bytes: Option<usize>,
+    // Add each file's line count to its header in copies
+    pub show_line_counts: bool,
+    // Add a line with each file's size, modification date and mode under its header
+    pub show_file_metadata: bool,
+    // Copy the changed files as soon as they are selected from git status
+    pub copy_git_changes: bool,
+    // Order copies of the selection by path instead of by how directories were walked
+    pub sort_copy_output: bool,
+    // Order copies of collections by path too, instead of the order files were added in
+    pub sort_collection_output: bool,
+    // Leave out files ignored by .gitignore
+    pub respect_gitignore: bool,
+    // Follow symlinks when collecting files, instead of skipping them
+    pub follow_symlinks: bool,
+    // List and collect dotfiles and hidden directories
+    pub show_hidden: bool,
+    // Strip comments and blank lines from copied source
+    pub strip_comments: bool,
+    // Whether to copy full contents, git diffs or one block per directory
+    pub output_mode: OutputMode,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Config {
+            recent_files: VecDeque::new(),
+            legacy_copy_history: Vec::new(),
+            copy_history_limit: 50,
+            grep_max_results: 200,
+            message_duration_ms: 1000,
+            poll_interval_ms: 200,
+            theme: "dark".to_string(),
+            prompt_template: None,
+            prompt_suffix: None,
+            split_ratio: 50,
+            collections_split: 50,
+            sort_mode: SortMode::default(),
+            lang_overrides: HashMap::new(),
+            output_format: OutputFormat::default(),
+            token_budget: None,
+            collection_view: CollectionView::default(),
+            use_absolute_paths: false,
+            confirm_quit: true,
+            fence: Fence::default(),
+            nerd_font_icons: false,
+            show_sizes: false,
+            show_cursor_path: false,
+            tree_max_depth: 6,
+            jump_max_depth: 8,
+            walk_max_depth: 64,
+            bookmarks: Vec::new(),
+            max_file_bytes: None,
+            show_line_counts: false,
+            show_file_metadata: false,
+            copy_git_changes: false,
+            sort_copy_output: false,
+            sort_collection_output: false,
+            respect_gitignore: true,
+            follow_symlinks: false,
+            show_hidden: true,
+            strip_comments: false,
+            output_mode: OutputMode::default(),
+        }
+    }
+}
+
+impl Config {
+    // Load the config, falling back to defaults if missing or invalid
+    pub fn load(path: &Path) -> Config {
+        let mut config: Config = fs::File::open(path)
+            .ok()
+            .and_then(|file| serde_json::from_reader(file).ok())
+            .unwrap_or_default();
+        // Accept extensions written as ".Vue" as well as "vue"
+        config.lang_overrides = config
+            .lang_overrides
+            .into_iter()
+            .map(|(ext, lang)| (ext.trim_start_matches('.').to_lowercase(), lang))
+            .collect();
+        // Any other character wouldn't fence the contents at all
+        if !config.fence.is_valid() {
+            config.fence = Fence::default();
+        }
+        config
+    }
+
+    // Write the config to the given path, replacing it only once fully written
+    // since a config that fails to parse is loaded as the defaults
+    pub fn save(&self, path: &Path) -> io::Result<()> {
+        write_pretty_json_atomically(path, self)
+    }
+
+    // Move a file to the front of the recent files, dropping the oldest past the limit
+    pub fn add_recent_file(&mut self, path: &Path) {
+        self.recent_files.retain(|recent| recent != path);
+        self.recent_files.push_front(path.to_path_buf());
+        self.recent_files.truncate(RECENT_FILES_LIMIT);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn fences_other_than_backticks_or_tildes_are_replaced() {
+        let path = std::env::temp_dir().join(format!("pray-config-{}.json", std::process::id()));
+        fs::write(
+            &path,
+            r#"{"fence": {"char": "'", "length": 4}, "split_ratio": 30}"#,
+        )
+        .unwrap();
+        let config = Config::load(&path);
+        let _ = fs::remove_file(&path);
+
+        assert!(config.fence == Fence::default());
+        assert_eq!(config.split_ratio, 30);
+    }
+}
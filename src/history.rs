@@ -0,0 +1,50 @@
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use crate::storage::write_json_atomically;
+
+// One entry of the copy history, logged automatically on every copy
+#[derive(Serialize, Deserialize, Clone)]
+pub struct CopyRecord {
+    pub timestamp: chrono::DateTime<chrono::Local>,
+    pub files: Vec<PathBuf>,
+    // Size of the copied text
+    pub bytes: usize,
+}
+
+// Past copies, newest first, kept in a file of their own so logging a copy
+// doesn't rewrite the config
+#[derive(Serialize, Deserialize, Default)]
+#[serde(transparent)]
+pub struct CopyHistory {
+    pub records: Vec<CopyRecord>,
+}
+
+impl CopyHistory {
+    // Load the history, falling back to an empty one if missing or invalid
+    pub fn load(path: &Path) -> CopyHistory {
+        fs::File::open(path)
+            .ok()
+            .and_then(|file| serde_json::from_reader(io::BufReader::new(file)).ok())
+            .unwrap_or_default()
+    }
+
+    pub fn save(&self, path: &Path) -> io::Result<()> {
+        write_json_atomically(path, self)
+    }
+
+    // Log a copy at the front, dropping the oldest past the limit
+    pub fn add(&mut self, files: &[PathBuf], bytes: usize, limit: usize) {
+        self.records.insert(
+            0,
+            CopyRecord {
+                timestamp: chrono::Local::now(),
+                files: files.to_vec(),
+                bytes,
+            },
+        );
+        self.records.truncate(limit);
+    }
+}
@@ -0,0 +1,169 @@
+use ratatui::crossterm::event::{KeyCode, KeyEvent, KeyModifiers};
+
+// Editable single-field text with a cursor, used by the rename and prompt popups
+#[derive(Default)]
+pub struct TextInput {
+    text: String,
+    // Cursor position in characters, not bytes
+    cursor: usize,
+}
+
+impl TextInput {
+    pub fn text(&self) -> &str {
+        &self.text
+    }
+
+    // Replace the text and put the cursor at its end
+    pub fn set(&mut self, text: impl Into<String>) {
+        self.text = text.into();
+        self.cursor = self.text.chars().count();
+    }
+
+    pub fn clear(&mut self) {
+        self.text.clear();
+        self.cursor = 0;
+    }
+
+    // Take the text out, leaving the input empty
+    pub fn take(&mut self) -> String {
+        self.cursor = 0;
+        std::mem::take(&mut self.text)
+    }
+
+    // Byte offset of the cursor in the text
+    fn byte_index(&self) -> usize {
+        self.text
+            .char_indices()
+            .nth(self.cursor)
+            .map_or(self.text.len(), |(index, _)| index)
+    }
+
+    pub fn insert_char(&mut self, c: char) {
+        let index = self.byte_index();
+        self.text.insert(index, c);
+        self.cursor += 1;
+    }
+
+    // Insert pasted text at the cursor, normalizing line endings
+    pub fn insert_str(&mut self, text: &str) {
+        let text = text.replace("\r\n", "\n").replace('\r', "\n");
+        let index = self.byte_index();
+        self.text.insert_str(index, &text);
+        self.cursor += text.chars().count();
+    }
+
+    // Delete the character before the cursor
+    pub fn backspace(&mut self) {
+        if self.cursor == 0 {
+            return;
+        }
+        self.cursor -= 1;
+        let index = self.byte_index();
+        self.text.remove(index);
+    }
+
+    // Delete the character under the cursor
+    pub fn delete(&mut self) {
+        if self.cursor < self.text.chars().count() {
+            let index = self.byte_index();
+            self.text.remove(index);
+        }
+    }
+
+    // Apply an editing key, returning whether it was handled
+    // Characters typed with Ctrl or Alt are shortcuts, not text, except with both
+    // held, which is how AltGr arrives on some platforms
+    pub fn handle_key(&mut self, key: KeyEvent) -> bool {
+        let shortcut = key
+            .modifiers
+            .intersects(KeyModifiers::CONTROL | KeyModifiers::ALT)
+            && !key
+                .modifiers
+                .contains(KeyModifiers::CONTROL | KeyModifiers::ALT);
+        match key.code {
+            KeyCode::Char(_) if shortcut => return false,
+            KeyCode::Char(c) => self.insert_char(c),
+            KeyCode::Backspace => self.backspace(),
+            KeyCode::Delete => self.delete(),
+            KeyCode::Left => self.cursor = self.cursor.saturating_sub(1),
+            KeyCode::Right => self.cursor = (self.cursor + 1).min(self.text.chars().count()),
+            KeyCode::Home => self.cursor = 0,
+            KeyCode::End => self.cursor = self.text.chars().count(),
+            _ => return false,
+        }
+        true
+    }
+
+    // Row and column of the cursor when the text is wrapped at the given width
+    pub fn cursor_position(&self, width: usize) -> (usize, usize) {
+        let width = width.max(1);
+        let before_cursor: String = self.text.chars().take(self.cursor).collect();
+        let mut lines = before_cursor.split('\n').peekable();
+        let mut row = 0;
+        while let Some(line) = lines.next() {
+            let len = line.chars().count();
+            if lines.peek().is_none() {
+                return (row + len / width, len % width);
+            }
+            row += len.div_ceil(width).max(1);
+        }
+        (row, 0)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn press(input: &mut TextInput, code: KeyCode) -> bool {
+        input.handle_key(KeyEvent::new(code, KeyModifiers::NONE))
+    }
+
+    #[test]
+    fn edits_happen_at_the_cursor() {
+        let mut input = TextInput::default();
+        input.set("héllo");
+        press(&mut input, KeyCode::Home);
+        press(&mut input, KeyCode::Right);
+        press(&mut input, KeyCode::Right);
+        press(&mut input, KeyCode::Backspace);
+        press(&mut input, KeyCode::Char('e'));
+        press(&mut input, KeyCode::Delete);
+        assert_eq!(input.text(), "helo");
+
+        press(&mut input, KeyCode::End);
+        press(&mut input, KeyCode::Right);
+        press(&mut input, KeyCode::Char('!'));
+        input.insert_str("\r\nok");
+        assert_eq!(input.text(), "helo!\nok");
+        assert_eq!(input.take(), "helo!\nok");
+        assert_eq!(input.text(), "");
+        press(&mut input, KeyCode::Left);
+        press(&mut input, KeyCode::Backspace);
+        assert_eq!(input.cursor, 0);
+    }
+
+    #[test]
+    fn chars_typed_with_ctrl_or_alt_are_ignored() {
+        let mut input = TextInput::default();
+        for modifiers in [KeyModifiers::CONTROL, KeyModifiers::ALT] {
+            assert!(!input.handle_key(KeyEvent::new(KeyCode::Char('a'), modifiers)));
+        }
+        let shifted = KeyEvent::new(KeyCode::Char('A'), KeyModifiers::SHIFT);
+        let alt_gr = KeyEvent::new(
+            KeyCode::Char('@'),
+            KeyModifiers::CONTROL | KeyModifiers::ALT,
+        );
+        assert!(input.handle_key(shifted) && input.handle_key(alt_gr));
+        assert_eq!(input.text(), "A@");
+    }
+
+    #[test]
+    fn the_cursor_wraps_with_the_text() {
+        let mut input = TextInput::default();
+        input.set("abcdef\nxy");
+        assert_eq!(input.cursor_position(4), (2, 2));
+        input.set("abcd");
+        assert_eq!(input.cursor_position(4), (1, 0));
+    }
+}
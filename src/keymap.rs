@@ -0,0 +1,223 @@
+use crate::app::FocusedPane;
+
+// Bindings handled by the key match of each pane, as (keys, description) pairs,
+// after the count that main.rs reads before handing keys to the pane.
+// Keep these in the order of the arms in main.rs so changes there are easy to mirror.
+// A test checks the keys against the arms.
+const FILES_PANE: &[(&str, &str)] = &[
+    (
+        "4-9",
+        "Start a count for the next motion or Space, e.g. 5j or 40j",
+    ),
+    ("j/k", "Move down/up"),
+    ("h", "Go back to parent directory"),
+    (":", "Go to a directory by typing its path"),
+    (
+        ";",
+        "Go to a directory under the base by fuzzy matching its name",
+    ),
+    (
+        "b",
+        "Bookmark the current directory, or remove its bookmark",
+    ),
+    ("'", "Show bookmarks to jump to"),
+    ("l/Enter", "Enter directory"),
+    ("Space", "Select/Deselect item"),
+    (
+        "v",
+        "Visual mode: select a range with j/k, toggle with Space",
+    ),
+    ("Esc", "Clear the name search or leave visual mode"),
+    ("/", "Search names in the current directory"),
+    ("G", "Select files git reports as modified or untracked"),
+    (
+        "u",
+        "List everything selected to unselect files from anywhere",
+    ),
+    ("i", "Invert the selection in the current directory"),
+    (
+        "=",
+        "Make the current directory the base for relative paths",
+    ),
+    ("w", "Open the current directory in the file manager"),
+    ("n/N", "Next/previous match while searching names"),
+    (
+        "Ctrl+a",
+        "Select/Deselect all files under this directory recursively",
+    ),
+    ("a", "Select/Deselect all items"),
+    ("c", "Copy selected files' contents and clear the selection"),
+    ("K", "Copy selected files' contents and keep the selection"),
+    (
+        "n",
+        "Save the selection as a named collection without copying",
+    ),
+    (
+        "p",
+        "Preview the files a copy would include, Tab for its text (P)",
+    ),
+    ("C", "Copy only the file or directory under the cursor"),
+    ("r", "Review everything selected, grouped by directory"),
+    ("F5", "Refresh the current directory"),
+    ("y", "Copy selected paths"),
+    (
+        "Y",
+        "Copy the tree of the current directory, without contents",
+    ),
+    ("R", "Pick from recently copied files"),
+    ("f", "Quick jump to a visible file by its label"),
+    ("o", "Cycle sort order"),
+    ("x", "Clear the selection in all directories"),
+    ("X", "Clear the selection and the saved session"),
+    ("e", "Open file in $EDITOR"),
+    ("S", "Search file contents under current directory"),
+    ("A", "Toggle absolute/relative paths"),
+    ("*", "Select files matching a glob pattern"),
+    ("#", "Set line range to copy for file"),
+    ("m", "Rename the file or directory on disk"),
+];
+
+const COLLECTIONS_PANE: &[(&str, &str)] = &[
+    ("4-9", "Start a count for the next motion, e.g. 5j or 40j"),
+    ("j/k", "Move down/up"),
+    ("f", "Filter collections by tag (empty to clear)"),
+    ("d", "Delete selected collection"),
+    ("D", "Duplicate selected collection"),
+    ("o", "Cycle the selected collection's copy format"),
+    ("x", "Export selected collection to a file"),
+    ("z", "Bundle the selected collection's files into a zip"),
+    ("v", "Cycle collections view (normal, compact, verbose)"),
+    ("e", "Edit selected collection's description"),
+    ("p", "Pin/Unpin selected collection at the top"),
+    ("c", "Copy selected collection's files"),
+    ("r", "Rename selected collection"),
+    ("t", "Add/Remove tag on selected collection"),
+    ("i", "Show stats of selected collection"),
+    ("Space", "Mark collection for merging"),
+    ("m/M", "Merge marked collections (M deletes the sources)"),
+    (
+        "b",
+        "Save paths relative to the base directory, or absolute",
+    ),
+    (
+        "R",
+        "Rebase collections whose files moved under the base directory",
+    ),
+];
+
+const SELECTED_FILES_PANE: &[(&str, &str)] = &[
+    ("4-9", "Start a count for the next motion, e.g. 5j or 40j"),
+    ("j/k", "Move down/up"),
+    ("Space", "Leave the file out of copies or include it again"),
+    ("d", "Remove the file from the collection"),
+    ("m", "Move the file to another collection"),
+];
+
+// Bindings specific to a pane, shown by the pane help popup
+pub fn pane_bindings(pane: FocusedPane) -> &'static [(&'static str, &'static str)] {
+    match pane {
+        FocusedPane::FilesPane => FILES_PANE,
+        FocusedPane::CollectionsPane => COLLECTIONS_PANE,
+        FocusedPane::SelectedFilesPane => SELECTED_FILES_PANE,
+    }
+}
+
+// Title of the pane help popup
+pub fn pane_name(pane: FocusedPane) -> &'static str {
+    match pane {
+        FocusedPane::FilesPane => "Files",
+        FocusedPane::CollectionsPane => "Collections",
+        FocusedPane::SelectedFilesPane => "Selected Files",
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::BTreeSet;
+
+    // Keys of a table, with "j/k" split into "j" and "k", leaving out the count
+    fn table_keys(pane: FocusedPane) -> BTreeSet<String> {
+        pane_bindings(pane)
+            .iter()
+            .filter(|(keys, _)| !keys.starts_with(|c: char| c.is_ascii_digit()))
+            .flat_map(|(keys, _)| match *keys {
+                "/" => vec!["/".to_string()],
+                keys => keys.split('/').map(str::to_string).collect(),
+            })
+            .collect()
+    }
+
+    // Keys matched by the arms of a pane's key match in main.rs, leaving out
+    // the arrow keys that mirror j/k
+    fn handled_keys(pane: FocusedPane) -> BTreeSet<String> {
+        let source = include_str!("main.rs");
+        let start = format!("app::FocusedPane::{} => match key.code {{", variant(pane));
+        let block = &source[source.find(&start).unwrap() + start.len()..];
+        let block = &block[..block.find("\n                            },").unwrap()];
+
+        let mut keys = BTreeSet::new();
+        for (i, _) in block.match_indices("KeyCode::") {
+            let rest = &block[i + "KeyCode::".len()..];
+            let arm = &rest[..rest.find("=>").unwrap_or(rest.len())];
+            let key = if let Some(c) = rest.strip_prefix("Char('") {
+                let c = if c.starts_with("\\'") { "'" } else { &c[..1] };
+                let c = if c == " " { "Space" } else { c };
+                if arm.contains("CONTROL") {
+                    format!("Ctrl+{}", c)
+                } else {
+                    c.to_string()
+                }
+            } else if let Some(n) = rest.strip_prefix("F(") {
+                format!("F{}", &n[..n.find(')').unwrap()])
+            } else {
+                let end = rest
+                    .find(|c: char| !c.is_alphanumeric())
+                    .unwrap_or(rest.len());
+                rest[..end].to_string()
+            };
+            if !["Down", "Up"].contains(&key.as_str()) {
+                keys.insert(key);
+            }
+        }
+        keys
+    }
+
+    fn variant(pane: FocusedPane) -> &'static str {
+        match pane {
+            FocusedPane::FilesPane => "FilesPane",
+            FocusedPane::CollectionsPane => "CollectionsPane",
+            FocusedPane::SelectedFilesPane => "SelectedFilesPane",
+        }
+    }
+
+    #[test]
+    fn tables_list_the_keys_each_pane_handles() {
+        for pane in [
+            FocusedPane::FilesPane,
+            FocusedPane::CollectionsPane,
+            FocusedPane::SelectedFilesPane,
+        ] {
+            assert_eq!(table_keys(pane), handled_keys(pane), "{}", pane_name(pane));
+        }
+    }
+
+    #[test]
+    fn tables_list_the_count_the_panes_take() {
+        let source = include_str!("main.rs");
+        let starts = &source[source.find("Digits build a count").unwrap()..];
+        let starts = &starts[starts.find("('").unwrap() + 2..];
+        let (first, last) = (
+            &starts[..1],
+            &starts[starts.find("..='").unwrap() + 4..][..1],
+        );
+        let count = format!("{}-{}", first, last);
+        for pane in [
+            FocusedPane::FilesPane,
+            FocusedPane::CollectionsPane,
+            FocusedPane::SelectedFilesPane,
+        ] {
+            assert_eq!(pane_bindings(pane)[0].0, count, "{}", pane_name(pane));
+        }
+    }
+}
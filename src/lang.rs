@@ -0,0 +1,125 @@
+// Map a file extension to the language tag used in markdown fences
+pub fn extension_to_lang(ext: &str) -> Option<&'static str> {
+    let lang = match ext.to_lowercase().as_str() {
+        "rs" => "rust",
+        "py" | "pyi" => "python",
+        "ts" | "mts" | "cts" => "typescript",
+        "tsx" => "tsx",
+        "js" | "mjs" | "cjs" => "javascript",
+        "jsx" => "jsx",
+        "go" => "go",
+        "c" | "h" => "c",
+        "cc" | "cpp" | "cxx" | "hpp" | "hh" | "hxx" => "cpp",
+        "cs" => "csharp",
+        "java" => "java",
+        "kt" | "kts" => "kotlin",
+        "scala" => "scala",
+        "swift" => "swift",
+        "dart" => "dart",
+        "rb" => "ruby",
+        "php" => "php",
+        "pl" | "pm" => "perl",
+        "lua" => "lua",
+        "r" => "r",
+        "hs" => "haskell",
+        "ex" | "exs" => "elixir",
+        "erl" => "erlang",
+        "clj" | "cljs" => "clojure",
+        "sh" | "bash" => "bash",
+        "zsh" => "zsh",
+        "fish" => "fish",
+        "ps1" => "powershell",
+        "sql" => "sql",
+        "html" | "htm" => "html",
+        "css" => "css",
+        "scss" => "scss",
+        "vue" => "vue",
+        "svelte" => "svelte",
+        "json" => "json",
+        "yaml" | "yml" => "yaml",
+        "toml" => "toml",
+        "xml" => "xml",
+        "md" | "markdown" => "markdown",
+        "dockerfile" => "dockerfile",
+        "proto" => "protobuf",
+        "graphql" | "gql" => "graphql",
+        "tf" => "hcl",
+        "nix" => "nix",
+        "zig" => "zig",
+        _ => return None,
+    };
+    Some(lang)
+}
+
+// Extension of the language a `#!` shebang line runs, for scripts without one,
+// e.g. `#!/usr/bin/env python3` gives "py"
+pub fn shebang_extension(line: &str) -> Option<&'static str> {
+    let mut words = line.strip_prefix("#!")?.split_whitespace();
+    let mut program = words.next()?.rsplit('/').next()?;
+    if program == "env" {
+        // Skip options like `-S` to get to the program env runs
+        program = words.find(|word| !word.starts_with('-'))?;
+    }
+    // Drop version suffixes like the one in `python3.11`
+    let ext = match program.trim_end_matches(|c: char| c.is_ascii_digit() || c == '.') {
+        "sh" | "bash" | "dash" | "ksh" => "sh",
+        "zsh" => "zsh",
+        "fish" => "fish",
+        "python" | "pypy" => "py",
+        "node" | "nodejs" => "js",
+        "deno" | "bun" | "ts-node" | "tsx" => "ts",
+        "ruby" => "rb",
+        "perl" => "pl",
+        "php" => "php",
+        "lua" | "luajit" => "lua",
+        "Rscript" => "r",
+        "pwsh" => "ps1",
+        _ => return None,
+    };
+    Some(ext)
+}
+
+// Short ASCII symbol shown before files of the given extension, three columns wide
+pub fn extension_symbol(ext: &str) -> &'static str {
+    match ext.to_lowercase().as_str() {
+        "rs" => "rs ",
+        "py" | "pyi" => "py ",
+        "ts" | "mts" | "cts" | "tsx" => "ts ",
+        "js" | "mjs" | "cjs" | "jsx" => "js ",
+        "go" => "go ",
+        "c" | "h" | "cc" | "cpp" | "cxx" | "hpp" | "hh" | "hxx" => "c  ",
+        "java" | "kt" | "kts" | "scala" => "jvm",
+        "rb" => "rb ",
+        "sh" | "bash" | "zsh" | "fish" | "ps1" => "sh ",
+        "html" | "htm" | "css" | "scss" | "vue" | "svelte" => "web",
+        "json" | "yaml" | "yml" | "toml" | "xml" | "ini" | "cfg" | "conf" | "lock" => "cfg",
+        "md" | "markdown" | "txt" | "rst" => "md ",
+        "png" | "jpg" | "jpeg" | "gif" | "svg" | "webp" | "ico" | "bmp" => "img",
+        "zip" | "tar" | "gz" | "tgz" | "xz" | "bz2" | "7z" => "zip",
+        "pdf" => "pdf",
+        _ => "   ",
+    }
+}
+
+// Nerd font glyph shown before files of the given extension, padded to three columns
+pub fn extension_glyph(ext: &str) -> &'static str {
+    match ext.to_lowercase().as_str() {
+        "rs" => "\u{e7a8}  ",
+        "py" | "pyi" => "\u{e73c}  ",
+        "ts" | "mts" | "cts" | "tsx" => "\u{e628}  ",
+        "js" | "mjs" | "cjs" | "jsx" => "\u{e74e}  ",
+        "go" => "\u{e627}  ",
+        "c" | "h" | "cc" | "cpp" | "cxx" | "hpp" | "hh" | "hxx" => "\u{e61e}  ",
+        "java" | "kt" | "kts" | "scala" => "\u{e738}  ",
+        "rb" => "\u{e739}  ",
+        "sh" | "bash" | "zsh" | "fish" | "ps1" => "\u{f489}  ",
+        "html" | "htm" => "\u{e736}  ",
+        "css" | "scss" => "\u{e749}  ",
+        "json" | "yaml" | "yml" | "toml" | "xml" | "ini" | "cfg" | "conf" | "lock" => "\u{e615}  ",
+        "md" | "markdown" | "txt" | "rst" => "\u{e73e}  ",
+        "png" | "jpg" | "jpeg" | "gif" | "svg" | "webp" | "ico" | "bmp" => "\u{f1c5}  ",
+        "zip" | "tar" | "gz" | "tgz" | "xz" | "bz2" | "7z" => "\u{f1c6}  ",
+        "pdf" => "\u{f1c1}  ",
+        _ => "\u{f15b}  ",
+    }
+}
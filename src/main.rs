@@ -1,9 +1,13 @@
-use std::{error::Error, io, time::Duration};
+use std::{error::Error, io, path::PathBuf, process::Command};
 
 use ratatui::{
     backend::CrosstermBackend,
     crossterm::{
-        event::{self, DisableMouseCapture, EnableMouseCapture, Event, KeyCode},
+        event::{
+            self, DisableBracketedPaste, DisableMouseCapture, EnableBracketedPaste,
+            EnableMouseCapture, Event, KeyCode, KeyModifiers, MouseButton, MouseEvent,
+            MouseEventKind,
+        },
         execute,
         terminal::{disable_raw_mode, enable_raw_mode, EnterAlternateScreen, LeaveAlternateScreen},
     },
@@ -11,27 +15,113 @@ use ratatui::{
 };
 
 mod app;
+mod config;
+mod history;
+mod input;
+mod keymap;
+mod lang;
+mod minify;
+mod output;
+mod search;
+mod session;
+mod settings;
+mod sizes;
+mod sort;
+mod storage;
+mod theme;
 mod ui;
+mod watch;
 use crate::{app::App, ui::ui};
 
+// Usage shown by --help
+const USAGE: &str = "\
+Select files in a terminal UI and copy their contents to the clipboard
+
+Usage: pray [OPTIONS] [DIR]
+
+Arguments:
+  [DIR]  Directory to start in, used as the base for relative paths [default: .]
+
+Options:
+      --dir <DIR>  Same as the DIR argument
+  -h, --help       Print this help and exit
+  -V, --version    Print the version and exit
+
+Press ? inside pray to see all key bindings.
+";
+
 fn main() -> Result<(), Box<dyn Error>> {
+    // Handle command-line flags before any terminal setup
+    let mut dir = None;
+    let mut args = std::env::args().skip(1);
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "-h" | "--help" => {
+                print!("{}", USAGE);
+                return Ok(());
+            }
+            "-V" | "--version" => {
+                println!("pray {}", env!("CARGO_PKG_VERSION"));
+                return Ok(());
+            }
+            "--dir" => match args.next() {
+                Some(path) if dir.is_none() => dir = Some(path),
+                Some(_) => usage_error("only one directory can be given"),
+                None => usage_error("--dir needs a directory"),
+            },
+            _ if arg.starts_with('-') => {
+                usage_error(&format!("unexpected argument '{}'", arg));
+            }
+            _ if dir.is_none() => dir = Some(arg),
+            _ => usage_error("only one directory can be given"),
+        }
+    }
+
+    // Validate the starting directory before taking over the terminal
+    let dir = match dir {
+        Some(dir) => match PathBuf::from(&dir).canonicalize() {
+            Ok(path) if path.is_dir() => Some(path),
+            Ok(_) => {
+                eprintln!("pray: not a directory: {}", dir);
+                std::process::exit(1);
+            }
+            Err(err) => {
+                eprintln!("pray: cannot open {}: {}", dir, err);
+                std::process::exit(1);
+            }
+        },
+        None => None,
+    };
+
     // Setup terminal
     enable_raw_mode()?;
     let mut stdout = io::stdout();
-    execute!(stdout, EnterAlternateScreen, EnableMouseCapture)?;
+    execute!(
+        stdout,
+        EnterAlternateScreen,
+        EnableMouseCapture,
+        EnableBracketedPaste
+    )?;
     let backend = CrosstermBackend::new(stdout);
     let mut terminal = Terminal::new(backend)?;
 
     // Create app and run it
-    let mut app = App::new();
+    let mut app = match dir {
+        Some(dir) => App::new_with_dir(dir),
+        None => App::new(),
+    };
     let res = run_app(&mut terminal, &mut app);
 
+    // Write collection changes still waiting for the debounce
+    app.flush_collections();
+
     // Restore terminal
     disable_raw_mode()?;
     execute!(
         terminal.backend_mut(),
         LeaveAlternateScreen,
-        DisableMouseCapture
+        DisableMouseCapture,
+        DisableBracketedPaste
     )?;
     terminal.show_cursor()?;
 
@@ -42,147 +132,619 @@ fn main() -> Result<(), Box<dyn Error>> {
     Ok(())
 }
 
+// Report a command-line mistake with the usage and exit
+fn usage_error(message: &str) -> ! {
+    eprintln!("pray: {}\n\n{}", message, USAGE);
+    std::process::exit(2);
+}
+
 fn run_app(
     terminal: &mut Terminal<CrosstermBackend<std::io::Stdout>>,
     app: &mut App,
 ) -> io::Result<()> {
     loop {
-        terminal.draw(|f| {
-            ui(f, app);
+        // Only draw when something changed, so an idle app rarely wakes up
+        if app.needs_redraw {
+            terminal.draw(|f| {
+                ui(f, app);
 
-            // Update scroll after rendering to get correct dimensions
-            if let app::FocusedPane::FilesPane = app.focused_pane {
-                let height = f.area().height.saturating_sub(3) as usize; // Subtract borders and footer
-                app.update_scroll(height);
-            }
-        })?;
+                // Update scroll after rendering to get correct dimensions
+                app.update_scrolls();
+            })?;
+            app.needs_redraw = false;
+        }
 
         // Set a timeout for the event reading
-        if crossterm::event::poll(Duration::from_millis(200))? {
-            if let Event::Key(key) = event::read()? {
+        if crossterm::event::poll(app.poll_timeout())? {
+            let event = event::read()?;
+            // Any input, including resizes, may change what is shown
+            app.needs_redraw = true;
+            if let Event::Mouse(mouse) = event {
+                handle_mouse(app, mouse);
+            }
+            if let Event::Paste(text) = &event {
+                // Pasted text only goes into text inputs
+                if app.renaming_collection {
+                    app.new_collection_name.insert_str(text);
+                } else if app.active_prompt.is_some() {
+                    app.prompt_input.insert_str(text);
+                } else if app.searching {
+                    app.search_input.insert_str(text);
+                }
+            }
+            if let Event::Key(key) = event {
                 if app.show_help {
                     // Hide help screen on any key press
                     app.show_help = false;
                     continue;
                 }
 
-                if app.renaming_collection {
+                if app.show_pane_help {
+                    // Hide pane help on any key press
+                    app.show_pane_help = false;
+                    continue;
+                }
+
+                if app.show_collection_stats {
+                    // Hide stats popup on any key press
+                    app.show_collection_stats = false;
+                    continue;
+                }
+
+                if app.show_selection_review {
+                    match key.code {
+                        KeyCode::Char('j') | KeyCode::Down => {
+                            app.move_review_cursor(1);
+                        }
+                        KeyCode::Char('k') | KeyCode::Up => {
+                            app.move_review_cursor(-1);
+                        }
+                        KeyCode::Enter | KeyCode::Char(' ') | KeyCode::Char('z') => {
+                            app.toggle_review_group();
+                        }
+                        KeyCode::Char('Z') => {
+                            app.toggle_all_review_groups();
+                        }
+                        KeyCode::Char('d') => {
+                            app.remove_review_row();
+                        }
+                        KeyCode::Esc | KeyCode::Char('q') | KeyCode::Char('r') => {
+                            app.show_selection_review = false;
+                        }
+                        _ => {}
+                    }
+                    continue;
+                }
+
+                if app.copy_preview.is_some() {
+                    match key.code {
+                        KeyCode::Char('j') | KeyCode::Down => {
+                            app.scroll_copy_preview(1);
+                        }
+                        KeyCode::Char('k') | KeyCode::Up => {
+                            app.scroll_copy_preview(-1);
+                        }
+                        KeyCode::Tab => {
+                            app.toggle_copy_preview_text();
+                        }
+                        KeyCode::Enter | KeyCode::Char('c') | KeyCode::Char('y') => {
+                            app.close_copy_preview();
+                            app.copy_selected_items_to_clipboard(false);
+                        }
+                        KeyCode::Esc | KeyCode::Char('n') | KeyCode::Char('q') => {
+                            app.close_copy_preview();
+                        }
+                        _ => {}
+                    }
+                    continue;
+                }
+
+                if app.show_settings {
+                    match key.code {
+                        KeyCode::Char('j') | KeyCode::Down => {
+                            app.move_settings_cursor(1);
+                        }
+                        KeyCode::Char('k') | KeyCode::Up => {
+                            app.move_settings_cursor(-1);
+                        }
+                        KeyCode::Enter | KeyCode::Char(' ') => {
+                            app.change_setting();
+                        }
+                        KeyCode::Esc | KeyCode::Char('q') | KeyCode::Char(',') => {
+                            app.close_settings();
+                        }
+                        _ => {}
+                    }
+                    continue;
+                }
+
+                if app.confirming_quit {
+                    match key.code {
+                        KeyCode::Char('c') => {
+                            app.copy_selected_items_to_clipboard(false);
+                            app.save_session();
+                            return Ok(());
+                        }
+                        KeyCode::Char('q') => {
+                            app.save_session();
+                            return Ok(());
+                        }
+                        KeyCode::Esc | KeyCode::Char('n') => {
+                            app.confirming_quit = false;
+                        }
+                        _ => {}
+                    }
+                    continue;
+                }
+
+                if app.pending_overwrite.is_some() {
+                    match key.code {
+                        KeyCode::Char(c) => {
+                            app.resolve_overwrite(c);
+                        }
+                        KeyCode::Esc => {
+                            app.resolve_overwrite('c');
+                        }
+                        _ => {}
+                    }
+                    continue;
+                }
+
+                if app.active_popup.is_some() {
+                    match key.code {
+                        KeyCode::Char('j') | KeyCode::Down
+                            if app.popup_index + 1 < app.popup_len() =>
+                        {
+                            app.popup_index += 1;
+                        }
+                        KeyCode::Char('k') | KeyCode::Up if app.popup_index > 0 => {
+                            app.popup_index -= 1;
+                        }
+                        KeyCode::Char(' ') | KeyCode::Char('d')
+                            if matches!(app.active_popup, Some(app::Popup::Selection)) =>
+                        {
+                            app.remove_from_selection_list();
+                        }
+                        KeyCode::Char(' ') => {
+                            app.toggle_popup_mark();
+                        }
+                        KeyCode::Char('d')
+                            if matches!(app.active_popup, Some(app::Popup::Bookmarks)) =>
+                        {
+                            app.delete_bookmark();
+                        }
+                        KeyCode::Char('m')
+                            if matches!(app.active_popup, Some(app::Popup::GrepResults)) =>
+                        {
+                            app.load_more_grep_results();
+                        }
+                        KeyCode::Char('s')
+                            if matches!(app.active_popup, Some(app::Popup::CopyHistory)) =>
+                        {
+                            app.save_history_as_collection();
+                        }
+                        KeyCode::Enter => {
+                            app.confirm_popup();
+                        }
+                        KeyCode::Esc | KeyCode::Char('q') => {
+                            app.close_popup();
+                        }
+                        _ => {}
+                    }
+                    continue;
+                }
+
+                if app.active_prompt.is_some() {
+                    match key.code {
+                        KeyCode::Enter => {
+                            app.confirm_prompt();
+                        }
+                        KeyCode::Esc => {
+                            app.cancel_prompt();
+                        }
+                        KeyCode::Tab
+                            if matches!(app.active_prompt, Some(app::Prompt::JumpToDirectory)) =>
+                        {
+                            app.complete_jump_path();
+                        }
+                        KeyCode::Down if app.fuzzy_jump.is_some() => {
+                            app.move_fuzzy_jump_cursor(1);
+                        }
+                        KeyCode::Up if app.fuzzy_jump.is_some() => {
+                            app.move_fuzzy_jump_cursor(-1);
+                        }
+                        _ => {
+                            app.prompt_input.handle_key(key);
+                        }
+                    }
+                    continue;
+                }
+
+                if app.quick_jump {
                     match key.code {
                         KeyCode::Char(c) => {
-                            app.new_collection_name.push(c);
+                            app.quick_jump_to(c);
                         }
-                        KeyCode::Backspace => {
-                            app.new_collection_name.pop();
+                        _ => {
+                            app.quick_jump = false;
                         }
+                    }
+                    continue;
+                }
+
+                if app.searching {
+                    match key.code {
+                        KeyCode::Enter => {
+                            app.finish_file_search();
+                        }
+                        KeyCode::Esc => {
+                            app.cancel_file_search();
+                        }
+                        _ => {
+                            app.handle_search_key(key);
+                        }
+                    }
+                    continue;
+                }
+
+                if app.renaming_collection {
+                    match key.code {
                         KeyCode::Enter => {
                             app.confirm_rename();
                         }
                         KeyCode::Esc => {
                             app.cancel_rename();
                         }
-                        _ => {}
+                        _ => {
+                            app.new_collection_name.handle_key(key);
+                        }
                     }
                     continue;
                 }
 
+                // Digits build a count for the next motion; a count starts with 4-9
+                // since 0 can't start one and 1-3 switch panes, then takes any digit
+                if let KeyCode::Char(digit @ '0'..='9') = key.code {
+                    if app.pending_count.is_some() || ('4'..='9').contains(&digit) {
+                        app.push_count_digit(digit);
+                        continue;
+                    }
+                }
+                let count = app.pending_count.take().unwrap_or(1);
+
                 match key.code {
                     KeyCode::Char('g') => {
                         app.toggle_gitignore();
                     }
+                    KeyCode::Char('s') => {
+                        app.toggle_strip_comments();
+                    }
+                    KeyCode::Char('L') => {
+                        app.toggle_follow_symlinks();
+                    }
+                    KeyCode::Char('T') => {
+                        app.cycle_theme();
+                    }
+                    KeyCode::Char('E') => {
+                        app.start_template_prompt();
+                    }
+                    KeyCode::Char('I') => {
+                        app.start_suffix_prompt();
+                    }
+                    KeyCode::Char('P') => {
+                        app.open_copy_preview_text();
+                    }
+                    KeyCode::Char('O') => {
+                        app.cycle_output_mode();
+                    }
+                    KeyCode::Char('F') => {
+                        app.cycle_output_format();
+                    }
+                    KeyCode::Char(',') => {
+                        app.open_settings();
+                    }
+                    KeyCode::Char('`') => {
+                        app.cycle_fence();
+                    }
+                    KeyCode::Char('B') => {
+                        app.start_token_budget_prompt();
+                    }
                     // Quit the application
                     KeyCode::Char('q') => {
+                        if app.config.confirm_quit && !app.selected_items.is_empty() {
+                            app.confirming_quit = true;
+                            continue;
+                        }
+                        app.save_session();
+                        return Ok(());
+                    }
+                    KeyCode::Char('H') => {
+                        app.open_copy_history();
+                    }
+                    KeyCode::Char('U') => {
+                        app.toggle_sizes();
+                    }
+                    // Copy the selection, if any, and quit in one go
+                    KeyCode::Char('Q') => {
+                        if !app.selected_items.is_empty() {
+                            app.copy_selected_items_to_clipboard(false);
+                        }
+                        app.save_session();
                         return Ok(());
                     }
                     // Switch focus between panes using numbers
                     KeyCode::Char('1') => {
-                        app.focused_pane = app::FocusedPane::FilesPane;
+                        app.focus_pane(app::FocusedPane::FilesPane);
                     }
                     KeyCode::Char('2') => {
-                        app.focused_pane = app::FocusedPane::CollectionsPane;
+                        app.focus_pane(app::FocusedPane::CollectionsPane);
                     }
                     KeyCode::Char('3') => {
-                        app.focused_pane = app::FocusedPane::SelectedFilesPane;
+                        app.focus_pane(app::FocusedPane::SelectedFilesPane);
+                    }
+                    // Move by pages or half pages in the focused pane
+                    KeyCode::PageDown => {
+                        app.move_cursor((count * app.page_rows()) as isize);
+                    }
+                    KeyCode::PageUp => {
+                        app.move_cursor(-((count * app.page_rows()) as isize));
+                    }
+                    KeyCode::Char('d') if key.modifiers.contains(KeyModifiers::CONTROL) => {
+                        app.move_cursor((count * app.page_rows().div_ceil(2)) as isize);
+                    }
+                    KeyCode::Char('u') if key.modifiers.contains(KeyModifiers::CONTROL) => {
+                        app.move_cursor(-((count * app.page_rows().div_ceil(2)) as isize));
+                    }
+                    // Scroll long paths in the focused pane
+                    KeyCode::Left if key.modifiers.contains(KeyModifiers::SHIFT) => {
+                        app.scroll_horizontally(-4);
+                    }
+                    KeyCode::Right if key.modifiers.contains(KeyModifiers::SHIFT) => {
+                        app.scroll_horizontally(4);
+                    }
+                    // Resize the focused pane
+                    KeyCode::Char('>') => {
+                        app.widen_focused_pane(5);
+                    }
+                    KeyCode::Char('<') => {
+                        app.widen_focused_pane(-5);
+                    }
+                    KeyCode::Char('Z') => {
+                        app.cycle_collections_split();
                     }
                     // Show help screen
                     KeyCode::Char('?') => {
                         app.show_help = true;
                     }
+                    // Show the bindings of the focused pane
+                    KeyCode::F(1) => {
+                        app.show_pane_help = true;
+                    }
                     _ => {
                         // Handle key events based on the focused pane
                         match app.focused_pane {
                             app::FocusedPane::FilesPane => match key.code {
                                 KeyCode::Char('j') | KeyCode::Down => {
-                                    if app.selected_file_index + 1 < app.directory_entries.len() {
-                                        app.selected_file_index += 1;
-                                    }
+                                    app.move_cursor(count as isize);
                                 }
                                 KeyCode::Char('k') | KeyCode::Up => {
-                                    if app.selected_file_index > 0 {
-                                        app.selected_file_index -= 1;
-                                    }
+                                    app.move_cursor(-(count as isize));
                                 }
                                 KeyCode::Char('h') => {
                                     app.go_back();
                                 }
+                                KeyCode::Char(':') => {
+                                    app.start_prompt(app::Prompt::JumpToDirectory);
+                                }
+                                KeyCode::Char(';') => {
+                                    app.start_fuzzy_jump();
+                                }
+                                KeyCode::Char('b') => {
+                                    app.toggle_bookmark();
+                                }
+                                KeyCode::Char('\'') => {
+                                    app.open_bookmarks();
+                                }
                                 KeyCode::Char('l') | KeyCode::Enter => {
                                     app.enter_directory();
                                 }
                                 KeyCode::Char(' ') => {
-                                    app.toggle_selection();
+                                    if app.visual_anchor.is_some() {
+                                        app.toggle_visual_selection();
+                                    } else {
+                                        app.toggle_selections(count);
+                                    }
+                                }
+                                KeyCode::Char('v') => {
+                                    app.toggle_visual_mode();
+                                }
+                                KeyCode::Esc if !app.search_input.text().is_empty() => {
+                                    app.cancel_file_search();
+                                }
+                                KeyCode::Esc => {
+                                    app.exit_visual_mode();
+                                }
+                                KeyCode::Char('/') => {
+                                    app.start_file_search();
+                                }
+                                KeyCode::Char('G') => {
+                                    app.select_git_modified();
+                                }
+                                KeyCode::Char('u') => {
+                                    app.open_selection_list();
+                                }
+                                KeyCode::Char('i') => {
+                                    app.invert_selection();
+                                }
+                                KeyCode::Char('=') => {
+                                    app.reset_base_dir();
+                                }
+                                KeyCode::Char('w') => {
+                                    let dir = app.current_dir.clone();
+                                    app.reveal_in_file_manager(&dir);
+                                }
+                                KeyCode::Char('n') if !app.search_input.text().is_empty() => {
+                                    app.step_search_match(true);
+                                }
+                                KeyCode::Char('N') if !app.search_input.text().is_empty() => {
+                                    app.step_search_match(false);
+                                }
+                                KeyCode::Char('a')
+                                    if key.modifiers.contains(KeyModifiers::CONTROL) =>
+                                {
+                                    app.toggle_select_recursive();
                                 }
                                 KeyCode::Char('a') => {
                                     app.toggle_select_all();
                                 }
                                 KeyCode::Char('c') => {
-                                    app.copy_selected_items_to_clipboard();
+                                    app.copy_selected_items_to_clipboard(false);
+                                }
+                                KeyCode::Char('K') => {
+                                    app.copy_selected_items_to_clipboard(true);
+                                }
+                                KeyCode::Char('n') => {
+                                    app.start_save_collection_prompt();
+                                }
+                                KeyCode::Char('p') => {
+                                    app.open_copy_preview();
+                                }
+                                KeyCode::Char('C') => {
+                                    app.copy_file_under_cursor();
+                                }
+                                KeyCode::Char('r') => {
+                                    app.open_selection_review();
+                                }
+                                KeyCode::F(5) => {
+                                    app.refresh_directory();
+                                }
+                                KeyCode::Char('y') => {
+                                    app.copy_paths_to_clipboard();
+                                }
+                                KeyCode::Char('Y') => {
+                                    app.copy_directory_tree();
+                                }
+                                KeyCode::Char('R') => {
+                                    app.open_recent_files();
+                                }
+                                KeyCode::Char('f') => {
+                                    app.start_quick_jump();
+                                }
+                                KeyCode::Char('o') => {
+                                    app.cycle_sort_mode();
+                                }
+                                KeyCode::Char('x') => {
+                                    app.clear_selection();
+                                }
+                                KeyCode::Char('X') => {
+                                    app.clear_session();
+                                }
+                                KeyCode::Char('e') => {
+                                    edit_in_editor(terminal, app)?;
+                                }
+                                KeyCode::Char('S') => {
+                                    app.start_prompt(app::Prompt::Grep);
+                                }
+                                KeyCode::Char('A') => {
+                                    app.toggle_absolute_paths();
+                                }
+                                KeyCode::Char('*') => {
+                                    app.start_prompt(app::Prompt::SelectByGlob);
+                                }
+                                KeyCode::Char('#') => {
+                                    app.start_line_range_prompt();
+                                }
+                                KeyCode::Char('m') => {
+                                    app.start_rename_file_prompt();
                                 }
                                 _ => {}
                             },
                             app::FocusedPane::CollectionsPane => match key.code {
                                 KeyCode::Char('j') | KeyCode::Down => {
-                                    if app.selected_collection_index + 1 < app.collections.len() {
-                                        app.selected_collection_index += 1;
-                                        app.selected_file_in_collection_index = 0;
-                                    }
+                                    app.move_cursor(count as isize);
                                 }
                                 KeyCode::Char('k') | KeyCode::Up => {
-                                    if app.selected_collection_index > 0 {
-                                        app.selected_collection_index -= 1;
-                                        app.selected_file_in_collection_index = 0;
-                                    }
+                                    app.move_cursor(-(count as isize));
+                                }
+                                KeyCode::Char('f') => {
+                                    app.start_prompt(app::Prompt::FilterByTag);
+                                }
+                                // The rest act on the collection under the cursor
+                                _ if !app.selected_collection_visible() => {
+                                    app.set_footer_message("No collection to act on");
                                 }
                                 KeyCode::Char('d') => {
                                     app.remove_selected_collection();
                                 }
+                                KeyCode::Char('D') => {
+                                    app.duplicate_collection(app.selected_collection_index);
+                                }
+                                KeyCode::Char('o') => {
+                                    app.cycle_collection_format();
+                                }
+                                KeyCode::Char('x') => {
+                                    app.start_export_prompt();
+                                }
+                                KeyCode::Char('z') => {
+                                    app.start_zip_export_prompt();
+                                }
+                                KeyCode::Char('v') => {
+                                    app.cycle_collection_view();
+                                }
+                                KeyCode::Char('e') => {
+                                    app.start_description_prompt();
+                                }
+                                KeyCode::Char('p') => {
+                                    app.toggle_pin();
+                                }
                                 KeyCode::Char('c') => {
                                     app.copy_selected_collection_to_clipboard();
                                 }
                                 KeyCode::Char('r') => {
                                     app.start_rename();
                                 }
+                                KeyCode::Char('t') => {
+                                    app.start_prompt(app::Prompt::ToggleTag);
+                                }
+                                KeyCode::Char('i') => {
+                                    app.open_collection_stats();
+                                }
+                                KeyCode::Char(' ') => {
+                                    app.toggle_collection_mark();
+                                }
+                                KeyCode::Char('m') => {
+                                    app.start_merge_prompt(false);
+                                }
+                                KeyCode::Char('M') => {
+                                    app.start_merge_prompt(true);
+                                }
+                                KeyCode::Char('b') => {
+                                    app.toggle_collection_root();
+                                }
+                                KeyCode::Char('R') => {
+                                    app.rebase_moved_collections();
+                                }
                                 _ => {}
                             },
                             app::FocusedPane::SelectedFilesPane => match key.code {
                                 KeyCode::Char('j') | KeyCode::Down => {
-                                    if app.collections.is_empty() {
-                                        continue;
-                                    }
-                                    let collection =
-                                        &app.collections[app.selected_collection_index];
-                                    if app.selected_file_in_collection_index + 1
-                                        < collection.files.len()
-                                    {
-                                        app.selected_file_in_collection_index += 1;
-                                    }
+                                    app.move_cursor(count as isize);
                                 }
                                 KeyCode::Char('k') | KeyCode::Up => {
-                                    if app.selected_file_in_collection_index > 0 {
-                                        app.selected_file_in_collection_index -= 1;
-                                    }
+                                    app.move_cursor(-(count as isize));
+                                }
+                                _ if !app.selected_collection_visible() => {
+                                    app.set_footer_message("No collection to act on");
                                 }
                                 KeyCode::Char(' ') => {
+                                    app.toggle_collection_file();
+                                }
+                                KeyCode::Char('d') => {
                                     app.unselect_file_from_collection();
                                 }
+                                KeyCode::Char('m') => {
+                                    app.start_move_to_collection();
+                                }
                                 _ => {}
                             },
                         }
@@ -191,7 +753,95 @@ fn run_app(
             }
         }
 
-        // Decrement message counter if needed
-        app.decrement_message_counter();
+        // Clear notifications once they have expired
+        app.expire_notifications();
+        app.flush_collections_if_idle();
+        app.check_for_directory_changes();
+        app.receive_dir_sizes();
+        app.receive_grep_results();
+        app.update_selection_tokens();
+        app.update_fuzzy_jump();
+    }
+}
+
+// Handle clicks and scrolling on the main screen
+fn handle_mouse(app: &mut App, mouse: MouseEvent) {
+    // Popups, prompts and modes take the keyboard only
+    if app.show_help
+        || app.show_pane_help
+        || app.show_collection_stats
+        || app.show_selection_review
+        || app.show_settings
+        || app.copy_preview.is_some()
+        || app.confirming_quit
+        || app.pending_overwrite.is_some()
+        || app.active_popup.is_some()
+        || app.active_prompt.is_some()
+        || app.renaming_collection
+        || app.quick_jump
+    {
+        return;
+    }
+
+    match mouse.kind {
+        MouseEventKind::Down(MouseButton::Left) => {
+            app.click_at(mouse.column, mouse.row);
+        }
+        MouseEventKind::ScrollDown => {
+            app.scroll_at(mouse.column, mouse.row, 1);
+        }
+        MouseEventKind::ScrollUp => {
+            app.scroll_at(mouse.column, mouse.row, -1);
+        }
+        _ => {}
     }
 }
+
+// Suspend the TUI, open the entry under the cursor in $EDITOR, then restore the TUI
+fn edit_in_editor(
+    terminal: &mut Terminal<CrosstermBackend<std::io::Stdout>>,
+    app: &mut App,
+) -> io::Result<()> {
+    let Some(path) = app.directory_entries.get(app.selected_file_index).cloned() else {
+        return Ok(());
+    };
+
+    // $EDITOR may include arguments, e.g. "code --wait"
+    let editor = std::env::var("EDITOR")
+        .ok()
+        .filter(|editor| !editor.trim().is_empty())
+        .unwrap_or_else(|| "vi".to_string());
+    let mut parts = editor.split_whitespace();
+    let program = parts.next().unwrap_or("vi");
+
+    disable_raw_mode()?;
+    execute!(
+        terminal.backend_mut(),
+        LeaveAlternateScreen,
+        DisableMouseCapture,
+        DisableBracketedPaste
+    )?;
+
+    let status = Command::new(program).args(parts).arg(&path).status();
+
+    enable_raw_mode()?;
+    execute!(
+        terminal.backend_mut(),
+        EnterAlternateScreen,
+        EnableMouseCapture,
+        EnableBracketedPaste
+    )?;
+    terminal.clear()?;
+
+    match status {
+        Ok(status) if status.success() => {}
+        Ok(status) => app.set_footer_message(format!("{} exited with {}", program, status)),
+        Err(err) => app.push_notification(
+            format!("Could not run {}: {}", program, err),
+            app::NotificationLevel::Error,
+        ),
+    }
+    // The edited file may have been the .prayignore
+    app.reload_pray_ignore();
+    Ok(())
+}
@@ -0,0 +1,597 @@
+use std::collections::HashSet;
+
+// How a language writes comments and literals, so comment markers inside
+// strings, heredocs and the like are left alone
+struct LiteralSyntax {
+    // Markers starting a comment that runs to the end of the line
+    line_markers: &'static [&'static str],
+    // Whether `/* */` comments are removed
+    block_comments: bool,
+    // Characters opening and closing a string, and whether a backslash escapes
+    // the next character inside it
+    quotes: &'static [(char, bool)],
+    // Whether three of a quote open a string only the same three close
+    triple_quotes: bool,
+    // Whether `'` opens a character literal like 'a' or '\n', only when one
+    // follows, as Rust lifetimes, labels and C++ digit separators use it too
+    char_literals: bool,
+    raw_strings: RawStrings,
+    // Whether `<<EOF` starts a heredoc running to a line with just `EOF`
+    heredocs: bool,
+    // Whether a line ending in `|` or `>` starts a YAML block scalar made of
+    // the more indented lines after it
+    block_scalars: bool,
+}
+
+// Strings that don't treat a backslash as an escape
+enum RawStrings {
+    None,
+    // r"..." and r#"..."#, also with a `b` in front
+    Rust,
+    // R"delimiter(...)delimiter"
+    Cpp,
+    // @"...", where "" stands for a quote
+    CSharp,
+}
+
+const PLAIN: LiteralSyntax = LiteralSyntax {
+    line_markers: &[],
+    block_comments: false,
+    quotes: &[],
+    triple_quotes: false,
+    char_literals: false,
+    raw_strings: RawStrings::None,
+    heredocs: false,
+    block_scalars: false,
+};
+
+const SLASHES: &[&str] = &["//"];
+const HASH: &[&str] = &["#"];
+const DOUBLE: &[(char, bool)] = &[('"', true)];
+const DOUBLE_SINGLE: &[(char, bool)] = &[('"', true), ('\'', true)];
+// Single quotes that take everything up to the next one literally
+const DOUBLE_LITERAL_SINGLE: &[(char, bool)] = &[('"', true), ('\'', false)];
+
+// Remove blank lines and comments from source code to save tokens
+// Contents of files with unknown extensions are returned untouched
+pub fn minify_contents(extension: &str, contents: &str) -> String {
+    let c_like = LiteralSyntax {
+        line_markers: SLASHES,
+        block_comments: true,
+        quotes: DOUBLE,
+        char_literals: true,
+        ..PLAIN
+    };
+    let syntax = match extension {
+        "rs" => LiteralSyntax {
+            raw_strings: RawStrings::Rust,
+            ..c_like
+        },
+        "c" | "h" => c_like,
+        "java" | "kt" | "kts" | "scala" => LiteralSyntax {
+            triple_quotes: true,
+            ..c_like
+        },
+        "cc" | "cpp" | "hpp" => LiteralSyntax {
+            raw_strings: RawStrings::Cpp,
+            ..c_like
+        },
+        "cs" => LiteralSyntax {
+            raw_strings: RawStrings::CSharp,
+            ..c_like
+        },
+        "go" => LiteralSyntax {
+            quotes: &[('"', true), ('`', false)],
+            ..c_like
+        },
+        "swift" => LiteralSyntax {
+            triple_quotes: true,
+            char_literals: false,
+            ..c_like
+        },
+        "js" | "jsx" | "mjs" | "ts" | "tsx" => LiteralSyntax {
+            quotes: &[('"', true), ('\'', true), ('`', true)],
+            char_literals: false,
+            ..c_like
+        },
+        "dart" => LiteralSyntax {
+            quotes: DOUBLE_SINGLE,
+            triple_quotes: true,
+            char_literals: false,
+            ..c_like
+        },
+        "scss" => LiteralSyntax {
+            quotes: DOUBLE_SINGLE,
+            char_literals: false,
+            ..c_like
+        },
+        "css" => LiteralSyntax {
+            line_markers: &[],
+            quotes: DOUBLE_SINGLE,
+            char_literals: false,
+            ..c_like
+        },
+        "php" => LiteralSyntax {
+            line_markers: &["//", "#"],
+            quotes: DOUBLE_SINGLE,
+            char_literals: false,
+            ..c_like
+        },
+        "py" => LiteralSyntax {
+            line_markers: HASH,
+            quotes: DOUBLE_SINGLE,
+            triple_quotes: true,
+            ..PLAIN
+        },
+        "toml" => LiteralSyntax {
+            line_markers: HASH,
+            quotes: DOUBLE_LITERAL_SINGLE,
+            triple_quotes: true,
+            ..PLAIN
+        },
+        "sh" | "bash" | "zsh" => LiteralSyntax {
+            line_markers: HASH,
+            quotes: DOUBLE_LITERAL_SINGLE,
+            heredocs: true,
+            ..PLAIN
+        },
+        "rb" | "pl" => LiteralSyntax {
+            line_markers: HASH,
+            quotes: DOUBLE_SINGLE,
+            heredocs: true,
+            ..PLAIN
+        },
+        "fish" | "r" => LiteralSyntax {
+            line_markers: HASH,
+            quotes: DOUBLE_SINGLE,
+            ..PLAIN
+        },
+        "ps1" => LiteralSyntax {
+            line_markers: HASH,
+            quotes: DOUBLE_LITERAL_SINGLE,
+            ..PLAIN
+        },
+        "cmake" => LiteralSyntax {
+            line_markers: HASH,
+            quotes: DOUBLE,
+            ..PLAIN
+        },
+        "mk" => LiteralSyntax {
+            line_markers: HASH,
+            ..PLAIN
+        },
+        "yaml" | "yml" => LiteralSyntax {
+            line_markers: HASH,
+            block_scalars: true,
+            ..PLAIN
+        },
+        "sql" => LiteralSyntax {
+            line_markers: &["--"],
+            quotes: &[('\'', false), ('"', false)],
+            ..PLAIN
+        },
+        "lua" => LiteralSyntax {
+            line_markers: &["--"],
+            quotes: DOUBLE_SINGLE,
+            ..PLAIN
+        },
+        "hs" | "elm" | "ada" => LiteralSyntax {
+            line_markers: &["--"],
+            quotes: DOUBLE,
+            char_literals: true,
+            ..PLAIN
+        },
+        _ => return contents.to_string(),
+    };
+
+    let scanned = scan(contents, &syntax);
+    scanned
+        .text
+        .lines()
+        .enumerate()
+        .filter(|(i, line)| {
+            // Lines of a multi-line string or heredoc belong to it, even blank ones
+            if scanned.literal_lines.contains(i) {
+                return true;
+            }
+            let trimmed = line.trim_start();
+            // Keep a shebang, and attributes like PHP's #[Route] that start
+            // with a comment marker
+            if (*i == 0 && line.starts_with("#!")) || trimmed.starts_with("#[") {
+                return true;
+            }
+            !trimmed.is_empty() && !syntax.line_markers.iter().any(|m| trimmed.starts_with(m))
+        })
+        .map(|(_, line)| line)
+        .collect::<Vec<&str>>()
+        .join("\n")
+}
+
+// Contents with block comments removed, and the lines that start inside a literal
+struct Scanned {
+    text: String,
+    // Index of the line being written
+    line: usize,
+    literal_lines: HashSet<usize>,
+}
+
+impl Scanned {
+    fn push(&mut self, c: char) {
+        self.text.push(c);
+        if c == '\n' {
+            self.line += 1;
+        }
+    }
+
+    // Copy a literal, marking the lines that start inside it
+    fn push_literal(&mut self, chars: &[char]) {
+        for &c in chars {
+            self.push(c);
+            if c == '\n' {
+                self.literal_lines.insert(self.line);
+            }
+        }
+    }
+}
+
+// Remove `/* */` comments, copying everything else and noting which lines
+// are inside string literals, heredocs or block scalars
+fn scan(contents: &str, syntax: &LiteralSyntax) -> Scanned {
+    let chars: Vec<char> = contents.chars().collect();
+    let mut output = Scanned {
+        text: String::with_capacity(contents.len()),
+        line: 0,
+        literal_lines: HashSet::new(),
+    };
+    // Heredocs still waiting for their closing lines, in the order they were opened
+    let mut heredocs: Vec<(String, bool)> = vec![];
+    // Indentation of the line a block scalar was started on, while in one
+    let mut block_scalar: Option<usize> = None;
+    let mut i = 0;
+
+    while i < chars.len() {
+        if i == 0 || chars[i - 1] == '\n' {
+            let end = (i..chars.len())
+                .find(|&j| chars[j] == '\n')
+                .unwrap_or(chars.len());
+            let line = &chars[i..end];
+            let in_literal = if let Some((delimiter, indented)) = heredocs.first() {
+                if heredoc_ends(line, delimiter, *indented) {
+                    heredocs.remove(0);
+                }
+                true
+            } else if block_scalar.is_some_and(|indent| {
+                line.iter().all(|c| c.is_whitespace()) || indentation(line) > indent
+            }) {
+                true
+            } else {
+                block_scalar = None;
+                if syntax.block_scalars && starts_block_scalar(line) {
+                    block_scalar = Some(indentation(line));
+                }
+                false
+            };
+            if in_literal {
+                output.literal_lines.insert(output.line);
+                output.text.extend(line);
+                if end < chars.len() {
+                    output.push('\n');
+                }
+                i = end + 1;
+                continue;
+            }
+        }
+
+        let c = chars[i];
+        if syntax.block_comments && c == '/' && chars.get(i + 1) == Some(&'*') {
+            // Skip to the end of the comment, keeping its newlines
+            i += 2;
+            while i < chars.len() && !(chars[i] == '*' && chars.get(i + 1) == Some(&'/')) {
+                if chars[i] == '\n' {
+                    output.push('\n');
+                }
+                i += 1;
+            }
+            i += 2;
+            continue;
+        }
+
+        if line_comment_starts(&chars, i, syntax) {
+            // Copy the comment as it is, so quotes in it don't open strings
+            let end = (i..chars.len())
+                .find(|&j| chars[j] == '\n')
+                .unwrap_or(chars.len());
+            output.text.extend(&chars[i..end]);
+            i = end;
+            continue;
+        }
+
+        if syntax.heredocs {
+            if let Some((delimiter, indented, end)) = heredoc_start(&chars, i) {
+                heredocs.push((delimiter, indented));
+                output.text.extend(&chars[i..end]);
+                i = end;
+                continue;
+            }
+        }
+
+        // Prefixes like r"" only start a raw string on their own, not inside a name
+        let in_word = i > 0 && (chars[i - 1].is_alphanumeric() || chars[i - 1] == '_');
+        let literal_end = if let Some(end) = raw_string_end(&chars, i, syntax).filter(|_| !in_word)
+        {
+            Some(end)
+        } else if let Some(&(quote, escapes)) = syntax.quotes.iter().find(|(q, _)| *q == c) {
+            if syntax.triple_quotes && chars[i..].starts_with(&[quote, quote, quote]) {
+                Some(triple_quoted_end(&chars, i, quote, escapes))
+            } else {
+                Some(quoted_end(&chars, i, quote, escapes))
+            }
+        } else if c == '\'' && syntax.char_literals {
+            Some(char_literal_end(&chars, i))
+        } else {
+            None
+        };
+        match literal_end {
+            Some(end) => {
+                let end = end.min(chars.len());
+                output.push_literal(&chars[i..end]);
+                i = end;
+            }
+            None => {
+                output.push(c);
+                i += 1;
+            }
+        }
+    }
+    output
+}
+
+// Whether a line comment starts at `i`; a `#` only starts one at the start of
+// a line or after whitespace, as in `${#name}` or a URL's fragment it doesn't
+fn line_comment_starts(chars: &[char], i: usize, syntax: &LiteralSyntax) -> bool {
+    syntax.line_markers.iter().any(|marker| {
+        let matches = marker
+            .chars()
+            .enumerate()
+            .all(|(k, m)| chars.get(i + k) == Some(&m));
+        matches && (*marker != "#" || i == 0 || chars[i - 1].is_whitespace())
+    })
+}
+
+// Number of spaces and tabs a line starts with
+fn indentation(line: &[char]) -> usize {
+    line.iter().take_while(|&&c| c == ' ' || c == '\t').count()
+}
+
+// Whether a YAML line ends in a block scalar indicator like `key: |` or `- >-`,
+// optionally followed by a comment
+fn starts_block_scalar(line: &[char]) -> bool {
+    let line: String = line.iter().collect();
+    let line = match line.find(" #") {
+        Some(comment) => &line[..comment],
+        None => &line,
+    };
+    let mut tokens = line.split_whitespace().rev();
+    let (Some(indicator), Some(before)) = (tokens.next(), tokens.next()) else {
+        return false;
+    };
+    indicator.starts_with(['|', '>'])
+        && indicator[1..]
+            .chars()
+            .all(|c| c == '-' || c == '+' || c.is_ascii_digit())
+        && (before.ends_with(':') || before == "-")
+}
+
+// The delimiter of a heredoc opened at `start` like <<EOF, <<-EOF, <<~EOF or
+// <<'EOF', whether its closing line may be indented, and the end of the opener
+fn heredoc_start(chars: &[char], start: usize) -> Option<(String, bool, usize)> {
+    let rest = &chars[start..];
+    if !rest.starts_with(&['<', '<']) {
+        return None;
+    }
+    let mut i = 2;
+    let indented = matches!(rest.get(i), Some('-' | '~'));
+    if indented {
+        i += 1;
+    }
+    let quote = rest.get(i).copied().filter(|&c| c == '\'' || c == '"');
+    if quote.is_some() {
+        i += 1;
+    }
+    let len = rest[i..]
+        .iter()
+        .take_while(|&&c| c.is_alphanumeric() || c == '_')
+        .count();
+    // Not `<<<` here-strings or shifts like `1 << 2`
+    if len == 0 || rest[i].is_ascii_digit() {
+        return None;
+    }
+    let delimiter: String = rest[i..i + len].iter().collect();
+    i += len;
+    if let Some(quote) = quote {
+        if rest.get(i) != Some(&quote) {
+            return None;
+        }
+        i += 1;
+    }
+    Some((delimiter, indented, start + i))
+}
+
+// Whether a line closes the heredoc with the given delimiter
+fn heredoc_ends(line: &[char], delimiter: &str, indented: bool) -> bool {
+    let line: String = line.iter().collect();
+    let line = line.trim_end_matches('\r');
+    let line = if indented { line.trim_start() } else { line };
+    line == delimiter
+}
+
+// End of the string opened by three quotes at `start`, or of the contents when
+// it is never closed
+fn triple_quoted_end(chars: &[char], start: usize, quote: char, escapes: bool) -> usize {
+    let mut i = start + 3;
+    while i < chars.len() {
+        if escapes && chars[i] == '\\' {
+            i += 2;
+        } else if chars[i..].starts_with(&[quote, quote, quote]) {
+            return i + 3;
+        } else {
+            i += 1;
+        }
+    }
+    chars.len()
+}
+
+// End of the string opened by the quote at `start`, or of the contents when
+// it is never closed
+fn quoted_end(chars: &[char], start: usize, quote: char, escapes: bool) -> usize {
+    let mut i = start + 1;
+    while i < chars.len() {
+        if escapes && chars[i] == '\\' {
+            i += 2;
+        } else if chars[i] == quote {
+            return i + 1;
+        } else {
+            i += 1;
+        }
+    }
+    chars.len()
+}
+
+// End of the character literal opened at `start`, or just past the `'` when
+// it isn't one, e.g. the lifetime in `&'a str`
+fn char_literal_end(chars: &[char], start: usize) -> usize {
+    match chars.get(start + 1) {
+        // An escape like '\n' or '\u{1F600}'
+        Some('\\') => chars[start + 2..]
+            .iter()
+            .take(12)
+            .position(|&c| c == '\'')
+            .map_or(start + 1, |offset| start + 2 + offset + 1),
+        Some(&c) if c != '\'' && chars.get(start + 2) == Some(&'\'') => start + 3,
+        _ => start + 1,
+    }
+}
+
+// End of the raw string starting at `start`, if one does
+fn raw_string_end(chars: &[char], start: usize, syntax: &LiteralSyntax) -> Option<usize> {
+    let rest = &chars[start..];
+    match syntax.raw_strings {
+        RawStrings::None => None,
+        RawStrings::Rust => {
+            let prefix = if rest.first() == Some(&'b') { 1 } else { 0 };
+            if rest.get(prefix) != Some(&'r') {
+                return None;
+            }
+            let hashes = rest[prefix + 1..].iter().take_while(|&&c| c == '#').count();
+            let open = prefix + 1 + hashes;
+            if rest.get(open) != Some(&'"') {
+                return None;
+            }
+            let close: Vec<char> = std::iter::once('"')
+                .chain(std::iter::repeat_n('#', hashes))
+                .collect();
+            Some(start + find_after(rest, open + 1, &close))
+        }
+        RawStrings::Cpp => {
+            if !rest.starts_with(&['R', '"']) {
+                return None;
+            }
+            let delimiter_len = rest[2..].iter().position(|&c| c == '(')?;
+            let close: Vec<char> = std::iter::once(')')
+                .chain(rest[2..2 + delimiter_len].iter().copied())
+                .chain(std::iter::once('"'))
+                .collect();
+            Some(start + find_after(rest, 3 + delimiter_len, &close))
+        }
+        RawStrings::CSharp => {
+            if !rest.starts_with(&['@', '"']) {
+                return None;
+            }
+            let mut i = 2;
+            while i < rest.len() {
+                match (rest[i], rest.get(i + 1)) {
+                    ('"', Some('"')) => i += 2,
+                    ('"', _) => return Some(start + i + 1),
+                    _ => i += 1,
+                }
+            }
+            Some(chars.len())
+        }
+    }
+}
+
+// Index just past the first `pattern` at or after `from`, or the end when
+// there is none
+fn find_after(chars: &[char], from: usize, pattern: &[char]) -> usize {
+    (from..chars.len())
+        .find(|&i| chars[i..].starts_with(pattern))
+        .map_or(chars.len(), |i| i + pattern.len())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn comment_markers_in_strings_and_char_literals_stay() {
+        let rust = "let a = '\"'; /* gone */\nlet b = \"/* kept */\";\nlet c = r#\"/* \"raw\" */\"#;\nfn f<'a>(s: &'a str) /* gone */ {}";
+        assert_eq!(
+            minify_contents("rs", rust),
+            "let a = '\"'; \nlet b = \"/* kept */\";\nlet c = r#\"/* \"raw\" */\"#;\nfn f<'a>(s: &'a str)  {}"
+        );
+
+        let js = "const a = 'it\\'s /* kept */';\nconst b = `/* kept */`; /* gone */";
+        assert_eq!(
+            minify_contents("js", js),
+            "const a = 'it\\'s /* kept */';\nconst b = `/* kept */`; "
+        );
+
+        let cpp = "auto s = R\"x(/* \" kept */)x\"; /* gone */ int n = 1'000;";
+        assert_eq!(
+            minify_contents("cpp", cpp),
+            "auto s = R\"x(/* \" kept */)x\";  int n = 1'000;"
+        );
+
+        let cs = "var p = @\"C:\\dir\\\"; /* gone */";
+        assert_eq!(minify_contents("cs", cs), "var p = @\"C:\\dir\\\"; ");
+    }
+
+    #[test]
+    fn lines_inside_multi_line_literals_stay() {
+        let python = "def f():\n    \"\"\"Docs\n\n    # heading\n    \"\"\"\n    # comment\n    return '#'  # it's";
+        assert_eq!(
+            minify_contents("py", python),
+            "def f():\n    \"\"\"Docs\n\n    # heading\n    \"\"\"\n    return '#'  # it's"
+        );
+
+        let shell = "# don't\ncat <<-EOF\n# kept\n\n\tEOF\n# gone\necho ${#x}";
+        assert_eq!(
+            minify_contents("sh", shell),
+            "cat <<-EOF\n# kept\n\n\tEOF\necho ${#x}"
+        );
+
+        let yaml = "run: |\n  # kept\n\n  make\n# gone\nlist:\n  - >-\n    # kept\n  # gone";
+        assert_eq!(
+            minify_contents("yaml", yaml),
+            "run: |\n  # kept\n\n  make\nlist:\n  - >-\n    # kept"
+        );
+
+        let js = "const s = `\n// kept\n`;\n// gone";
+        assert_eq!(minify_contents("js", js), "const s = `\n// kept\n`;");
+    }
+
+    #[test]
+    fn shebangs_and_attributes_survive_line_comments() {
+        let python = "#!/usr/bin/env python\n# comment\nprint(1)";
+        assert_eq!(
+            minify_contents("py", python),
+            "#!/usr/bin/env python\nprint(1)"
+        );
+
+        let php = "<?php\n#[Route('/')]\n# comment\nfunction index() {}";
+        assert_eq!(
+            minify_contents("php", php),
+            "<?php\n#[Route('/')]\nfunction index() {}"
+        );
+    }
+}
@@ -0,0 +1,298 @@
+use serde::{Deserialize, Serialize};
+use std::fs::Metadata;
+
+// What is copied for each file
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Default)]
+pub enum OutputMode {
+    // The file's full contents
+    #[default]
+    Full,
+    // The file's `git diff`, skipping unchanged files
+    Diff,
+    // The files of each selected directory joined into one block, with a
+    // comment line naming each file
+    ConcatByDir,
+}
+
+impl OutputMode {
+    // The mode that follows this one when cycling
+    pub fn next(self) -> OutputMode {
+        match self {
+            OutputMode::Full => OutputMode::Diff,
+            OutputMode::Diff => OutputMode::ConcatByDir,
+            OutputMode::ConcatByDir => OutputMode::Full,
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            OutputMode::Full => "full",
+            OutputMode::Diff => "diff",
+            OutputMode::ConcatByDir => "one block per directory",
+        }
+    }
+}
+
+// How each file is wrapped in the copied text
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Default)]
+pub enum OutputFormat {
+    // A header line followed by a fenced code block
+    #[default]
+    Markdown,
+    // Contents inside <file path="..."> tags
+    Xml,
+    // A header line followed by the raw contents
+    Plain,
+}
+
+impl OutputFormat {
+    // The format that follows this one when cycling
+    pub fn next(self) -> OutputFormat {
+        match self {
+            OutputFormat::Markdown => OutputFormat::Xml,
+            OutputFormat::Xml => OutputFormat::Plain,
+            OutputFormat::Plain => OutputFormat::Markdown,
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            OutputFormat::Markdown => "markdown",
+            OutputFormat::Xml => "xml",
+            OutputFormat::Plain => "plain",
+        }
+    }
+
+    // A comment to put above the files, e.g. a collection's description
+    pub fn comment(self, text: &str) -> String {
+        match self {
+            OutputFormat::Markdown | OutputFormat::Xml => format!("<!-- {} -->\n\n", text),
+            OutputFormat::Plain => {
+                let mut comment: String =
+                    text.lines().map(|line| format!("# {}\n", line)).collect();
+                comment.push('\n');
+                comment
+            }
+        }
+    }
+
+    // Wrap a file's contents under its header, with an optional line of metadata
+    pub fn block(
+        self,
+        header: &str,
+        meta: Option<&str>,
+        lang: &str,
+        contents: &str,
+        fence: Fence,
+    ) -> String {
+        // End the contents with exactly one newline so the closing line is never
+        // joined to the last line or preceded by blank ones
+        let contents = format!("{}\n", contents.trim_end_matches(['\n', '\r']));
+
+        let mut block = String::new();
+        match self {
+            OutputFormat::Markdown => {
+                let fence = fence.for_contents(&contents);
+                block.push_str(&format!("------ {} ------\n", header));
+                if let Some(meta) = meta {
+                    block.push_str(&format!("{}\n", meta));
+                }
+                block.push_str(&format!("{}{}\n", fence, lang));
+                block.push_str(&contents);
+                block.push_str(&format!("{}\n", fence));
+            }
+            OutputFormat::Xml => {
+                block.push_str(&format!("<file path=\"{}\"", header.replace('"', "&quot;")));
+                if let Some(meta) = meta {
+                    block.push_str(&format!(" meta=\"{}\"", meta.replace('"', "&quot;")));
+                }
+                block.push_str(">\n");
+                block.push_str(&contents);
+                block.push_str("</file>\n");
+            }
+            OutputFormat::Plain => {
+                block.push_str(&format!("------ {} ------\n", header));
+                if let Some(meta) = meta {
+                    block.push_str(&format!("{}\n", meta));
+                }
+                block.push_str(&contents);
+            }
+        }
+        block
+    }
+}
+
+// Format a byte count for display, e.g. 4.2 KB
+pub fn format_size(bytes: u64) -> String {
+    const UNITS: [&str; 4] = ["B", "KB", "MB", "GB"];
+    let mut size = bytes as f64;
+    let mut unit = 0;
+    while size >= 1024.0 && unit < UNITS.len() - 1 {
+        size /= 1024.0;
+        unit += 1;
+    }
+    if unit == 0 {
+        format!("{} B", bytes)
+    } else {
+        format!("{:.1} {}", size, UNITS[unit])
+    }
+}
+
+// Line of metadata shown under a file's header, e.g.
+// `size: 4.2 KB | modified: 2024-01-02 | mode: 644`
+pub fn file_metadata_line(metadata: &Metadata) -> String {
+    let mut parts = vec![format!("size: {}", format_size(metadata.len()))];
+    if let Ok(modified) = metadata.modified() {
+        let modified = chrono::DateTime::<chrono::Local>::from(modified);
+        parts.push(format!("modified: {}", modified.format("%Y-%m-%d")));
+    }
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        parts.push(format!("mode: {:o}", metadata.permissions().mode() & 0o777));
+    }
+    parts.join(" | ")
+}
+
+// Cut contents longer than the cap at a character boundary, noting how much was left out
+pub fn truncate_contents(contents: &mut String, max_bytes: usize) {
+    if contents.len() <= max_bytes {
+        return;
+    }
+    let mut end = max_bytes;
+    while !contents.is_char_boundary(end) {
+        end -= 1;
+    }
+    let omitted = contents.len() - end;
+    contents.truncate(end);
+    contents.push_str(&format!("\n… [truncated, {} bytes omitted]", omitted));
+}
+
+// Delimiter of the code fences around copied files in the markdown format
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq)]
+pub struct Fence {
+    // Either a backtick or a tilde
+    pub char: char,
+    // How many of them make up the fence, at least 3
+    pub length: usize,
+}
+
+impl Default for Fence {
+    fn default() -> Self {
+        Fence {
+            char: '`',
+            length: 6,
+        }
+    }
+}
+
+// Fences offered when cycling, starting with the default
+const FENCE_PRESETS: [Fence; 3] = [
+    Fence {
+        char: '`',
+        length: 6,
+    },
+    Fence {
+        char: '`',
+        length: 3,
+    },
+    Fence {
+        char: '~',
+        length: 3,
+    },
+];
+
+impl Fence {
+    // Whether markdown would take this as a fence at all
+    pub fn is_valid(self) -> bool {
+        self.char == '`' || self.char == '~'
+    }
+
+    // The fence for the given contents, grown past the longest run of the fence
+    // character inside them so the contents can't close it early
+    pub fn for_contents(self, contents: &str) -> String {
+        let mut longest_run = 0;
+        let mut run = 0;
+        for c in contents.chars() {
+            if c == self.char {
+                run += 1;
+                longest_run = longest_run.max(run);
+            } else {
+                run = 0;
+            }
+        }
+        let length = self.length.max(3).max(longest_run + 1);
+        self.char.to_string().repeat(length)
+    }
+
+    // The preset that follows this one when cycling
+    pub fn next(self) -> Fence {
+        let position = FENCE_PRESETS.iter().position(|preset| *preset == self);
+        match position {
+            Some(position) => FENCE_PRESETS[(position + 1) % FENCE_PRESETS.len()],
+            None => FENCE_PRESETS[0],
+        }
+    }
+
+    pub fn name(self) -> String {
+        self.char.to_string().repeat(self.length.max(3))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const FORMATS: [OutputFormat; 3] = [
+        OutputFormat::Markdown,
+        OutputFormat::Xml,
+        OutputFormat::Plain,
+    ];
+
+    #[test]
+    fn block_contents_end_with_one_newline() {
+        for format in FORMATS {
+            let expected = format.block("a.rs", None, "rust", "fn main() {}\n", Fence::default());
+            for contents in ["fn main() {}", "fn main() {}\n\n\n", "fn main() {}\r\n"] {
+                let block = format.block("a.rs", None, "rust", contents, Fence::default());
+                assert_eq!(block, expected, "{:?} as {}", contents, format.name());
+            }
+            assert!(expected.contains("fn main() {}\n"));
+            assert!(!expected.contains("fn main() {}\n\n"));
+        }
+    }
+
+    #[test]
+    fn markdown_fence_closes_on_its_own_line() {
+        let block = OutputFormat::Markdown.block("a.md", None, "", "text", Fence::default());
+        assert_eq!(block, "------ a.md ------\n``````\ntext\n``````\n");
+    }
+
+    #[test]
+    fn fences_grow_past_runs_inside_the_contents() {
+        let fence = Fence::default();
+        assert_eq!(fence.for_contents("no fences"), "``````");
+        assert_eq!(fence.for_contents("```rust\n```"), "``````");
+        assert_eq!(fence.for_contents("a `````````` b"), "```````````");
+        let tilde = Fence {
+            char: '~',
+            length: 3,
+        };
+        assert_eq!(tilde.for_contents("~~~~ and ``````"), "~~~~~");
+    }
+
+    #[test]
+    fn metadata_goes_under_the_header() {
+        let meta = Some("size: 4 B | mode: 644");
+        let block = OutputFormat::Markdown.block("a.md", meta, "", "text", Fence::default());
+        assert_eq!(
+            block,
+            "------ a.md ------\nsize: 4 B | mode: 644\n``````\ntext\n``````\n"
+        );
+        let block = OutputFormat::Xml.block("a.md", meta, "", "text", Fence::default());
+        assert_eq!(
+            block,
+            "<file path=\"a.md\" meta=\"size: 4 B | mode: 644\">\ntext\n</file>\n"
+        );
+    }
+}
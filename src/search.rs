@@ -0,0 +1,43 @@
+// Match a query against a name as a case-insensitive subsequence, returning the
+// character positions of the matched characters in the name
+pub fn fuzzy_match(name: &str, query: &str) -> Option<Vec<usize>> {
+    let mut positions = Vec::new();
+    let mut query_chars = query.chars().flat_map(char::to_lowercase).peekable();
+    for (position, c) in name.chars().enumerate() {
+        let Some(&wanted) = query_chars.peek() else {
+            break;
+        };
+        if c.to_lowercase().eq(std::iter::once(wanted)) {
+            positions.push(position);
+            query_chars.next();
+        }
+    }
+    query_chars.peek().is_none().then_some(positions)
+}
+
+// Rank a match found by `fuzzy_match`, higher is better: matched characters that
+// follow each other or start a path segment or word count extra, and matches in
+// the last path segment beat ones in its parents
+pub fn fuzzy_score(name: &str, positions: &[usize]) -> i64 {
+    let chars: Vec<char> = name.chars().collect();
+    let last_segment = chars.iter().rposition(|&c| c == '/').map_or(0, |i| i + 1);
+    let mut score = 0;
+    let mut previous: Option<usize> = None;
+    for &position in positions {
+        score += 1;
+        if previous.is_some_and(|previous| previous + 1 == position) {
+            score += 5;
+        }
+        let starts_segment =
+            position == 0 || matches!(chars.get(position - 1), Some('/' | '_' | '-' | '.' | ' '));
+        if starts_segment {
+            score += 8;
+        }
+        if position >= last_segment {
+            score += 2;
+        }
+        previous = Some(position);
+    }
+    // Between equally good matches, prefer shorter paths
+    score * 1000 - chars.len() as i64
+}
@@ -0,0 +1,48 @@
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use crate::storage::write_pretty_json_atomically;
+
+// In-progress selections persisted across restarts, keyed by base directory
+#[derive(Serialize, Deserialize, Default)]
+#[serde(default)]
+pub struct Session {
+    pub selections: HashMap<PathBuf, Vec<PathBuf>>,
+}
+
+impl Session {
+    // Load the session, falling back to an empty one if missing or invalid
+    pub fn load(path: &Path) -> Session {
+        fs::File::open(path)
+            .ok()
+            .and_then(|file| serde_json::from_reader(file).ok())
+            .unwrap_or_default()
+    }
+
+    // Write the session to the given path, replacing it only once fully written
+    pub fn save(&self, path: &Path) -> io::Result<()> {
+        write_pretty_json_atomically(path, self)
+    }
+
+    // The saved selection for a directory, without paths that no longer exist
+    pub fn selection_for(&self, base_dir: &Path) -> HashSet<PathBuf> {
+        self.selections
+            .get(base_dir)
+            .map(|paths| paths.iter().filter(|path| path.exists()).cloned().collect())
+            .unwrap_or_default()
+    }
+
+    // Replace the saved selection for a directory, dropping it when empty
+    pub fn set_selection(&mut self, base_dir: &Path, selection: &HashSet<PathBuf>) {
+        if selection.is_empty() {
+            self.selections.remove(base_dir);
+        } else {
+            let mut paths: Vec<PathBuf> = selection.iter().cloned().collect();
+            paths.sort();
+            self.selections.insert(base_dir.to_path_buf(), paths);
+        }
+    }
+}
@@ -0,0 +1,80 @@
+// Options listed on the settings screen, in the order they are shown
+#[derive(Clone, Copy, PartialEq)]
+pub enum Setting {
+    RespectGitignore,
+    FollowSymlinks,
+    ShowHidden,
+    StripComments,
+    Theme,
+    OutputFormat,
+    OutputMode,
+    Fence,
+    SortMode,
+    CollectionView,
+    AbsolutePaths,
+    ConfirmQuit,
+    NerdFontIcons,
+    Sizes,
+    CursorPath,
+    MaxFileSize,
+    LineCounts,
+    FileMetadata,
+    CopyGitChanges,
+    SortCopyOutput,
+    SortCollectionOutput,
+}
+
+pub const SETTINGS: [Setting; 21] = [
+    Setting::RespectGitignore,
+    Setting::FollowSymlinks,
+    Setting::ShowHidden,
+    Setting::StripComments,
+    Setting::Theme,
+    Setting::OutputFormat,
+    Setting::OutputMode,
+    Setting::Fence,
+    Setting::SortMode,
+    Setting::CollectionView,
+    Setting::AbsolutePaths,
+    Setting::ConfirmQuit,
+    Setting::NerdFontIcons,
+    Setting::Sizes,
+    Setting::CursorPath,
+    Setting::MaxFileSize,
+    Setting::LineCounts,
+    Setting::FileMetadata,
+    Setting::CopyGitChanges,
+    Setting::SortCopyOutput,
+    Setting::SortCollectionOutput,
+];
+
+// Per-file size caps offered when cycling, before going back to no cap
+pub const MAX_FILE_BYTES_PRESETS: [usize; 3] = [64 * 1024, 256 * 1024, 1024 * 1024];
+
+impl Setting {
+    pub fn label(self) -> &'static str {
+        match self {
+            Setting::RespectGitignore => "Respect .gitignore",
+            Setting::FollowSymlinks => "Follow symlinks",
+            Setting::ShowHidden => "Show hidden files",
+            Setting::StripComments => "Strip comments",
+            Setting::Theme => "Theme",
+            Setting::OutputFormat => "Copy format",
+            Setting::OutputMode => "Output mode",
+            Setting::Fence => "Code fence",
+            Setting::SortMode => "Sort",
+            Setting::CollectionView => "Collections view",
+            Setting::AbsolutePaths => "Absolute paths",
+            Setting::ConfirmQuit => "Confirm quit with a selection",
+            Setting::NerdFontIcons => "Nerd font icons",
+            Setting::Sizes => "Sizes in the files pane",
+            Setting::CursorPath => "Cursor path in the footer",
+            Setting::MaxFileSize => "Truncate files larger than",
+            Setting::LineCounts => "Line counts in file headers",
+            Setting::FileMetadata => "Size, date and mode under file headers",
+            Setting::CopyGitChanges => "Copy git changes right after selecting",
+            Setting::SortCopyOutput => "Sort copied files by path",
+            Setting::SortCollectionOutput => "Sort collection copies by path",
+        }
+    }
+}
@@ -0,0 +1,122 @@
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::{self, Receiver, Sender};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::SystemTime;
+
+// Threads walking directories, so one huge tree doesn't hold up the rest
+const WORKERS: usize = 4;
+
+// Total sizes of directories, computed on background threads as they are requested
+pub struct DirSizes {
+    // Known sizes with the directory's modification time when they were computed
+    sizes: HashMap<PathBuf, (Option<SystemTime>, u64)>,
+    // Directories being walked, with the generation they were requested in
+    pending: HashMap<PathBuf, u64>,
+    // Bumped on every invalidation so results of walks started before it are dropped
+    generation: u64,
+    jobs: Sender<(PathBuf, u64)>,
+    results: Receiver<(PathBuf, u64, u64)>,
+}
+
+impl Default for DirSizes {
+    fn default() -> Self {
+        DirSizes::new()
+    }
+}
+
+impl DirSizes {
+    pub fn new() -> DirSizes {
+        let (jobs, job_receiver) = mpsc::channel::<(PathBuf, u64)>();
+        let (result_sender, results) = mpsc::channel();
+        let job_receiver = Arc::new(Mutex::new(job_receiver));
+        for _ in 0..WORKERS {
+            let job_receiver = Arc::clone(&job_receiver);
+            let result_sender = result_sender.clone();
+            thread::spawn(move || loop {
+                // Hold the lock only while taking a job, not while walking
+                let job = job_receiver.lock().ok().and_then(|jobs| jobs.recv().ok());
+                let Some((dir, generation)) = job else {
+                    return;
+                };
+                let size = dir_size(&dir);
+                if result_sender.send((dir, generation, size)).is_err() {
+                    return;
+                }
+            });
+        }
+        DirSizes {
+            sizes: HashMap::new(),
+            pending: HashMap::new(),
+            generation: 0,
+            jobs,
+            results,
+        }
+    }
+
+    // The size of a directory if known and still current, otherwise queue a walk
+    pub fn get(&mut self, dir: &Path) -> Option<u64> {
+        let mtime = fs::metadata(dir).and_then(|m| m.modified()).ok();
+        if let Some(&(cached_mtime, size)) = self.sizes.get(dir) {
+            if cached_mtime == mtime {
+                return Some(size);
+            }
+            // Entries were added or removed directly inside it
+            self.sizes.remove(dir);
+        }
+        if !self.pending.contains_key(dir)
+            && self.jobs.send((dir.to_path_buf(), self.generation)).is_ok()
+        {
+            self.pending.insert(dir.to_path_buf(), self.generation);
+        }
+        None
+    }
+
+    // Store the sizes that arrived since the last call, returning whether there were any
+    pub fn receive(&mut self) -> bool {
+        let mut received = false;
+        while let Ok((dir, generation, size)) = self.results.try_recv() {
+            if self.pending.get(&dir) != Some(&generation) {
+                continue;
+            }
+            self.pending.remove(&dir);
+            let mtime = fs::metadata(&dir).and_then(|m| m.modified()).ok();
+            self.sizes.insert(dir, (mtime, size));
+            received = true;
+        }
+        received
+    }
+
+    // Forget the sizes of the given directory, everything inside it and its
+    // ancestors, which all contain whatever changed
+    pub fn invalidate(&mut self, dir: &Path) {
+        self.generation += 1;
+        let affected = |path: &Path| path.starts_with(dir) || dir.starts_with(path);
+        self.sizes.retain(|path, _| !affected(path));
+        self.pending.retain(|path, _| !affected(path));
+    }
+}
+
+// Total length of the files under a directory, without following symlinks
+fn dir_size(dir: &Path) -> u64 {
+    let mut total = 0;
+    let mut stack = vec![dir.to_path_buf()];
+    while let Some(dir) = stack.pop() {
+        let Ok(entries) = fs::read_dir(&dir) else {
+            continue;
+        };
+        for entry in entries.flatten() {
+            let Ok(file_type) = entry.file_type() else {
+                continue;
+            };
+            if file_type.is_dir() {
+                stack.push(entry.path());
+            } else if file_type.is_file() {
+                total += entry.metadata().map(|m| m.len()).unwrap_or(0);
+            }
+        }
+    }
+    total
+}
@@ -0,0 +1,123 @@
+use serde::{Deserialize, Serialize};
+use std::cmp::Ordering;
+use std::path::PathBuf;
+
+// How directory entries are ordered in the files pane
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Default)]
+pub enum SortMode {
+    // Case-insensitive, with digit runs compared by value (file2 before file10)
+    #[default]
+    Natural,
+    // Raw byte order of the paths
+    Lexicographic,
+}
+
+impl SortMode {
+    // The mode that follows this one when cycling
+    pub fn next(self) -> SortMode {
+        match self {
+            SortMode::Natural => SortMode::Lexicographic,
+            SortMode::Lexicographic => SortMode::Natural,
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            SortMode::Natural => "natural",
+            SortMode::Lexicographic => "lexicographic",
+        }
+    }
+
+    // Sort paths in place according to this mode
+    pub fn sort(self, paths: &mut [PathBuf]) {
+        match self {
+            SortMode::Natural => {
+                paths.sort_by(|a, b| natural_cmp(&a.to_string_lossy(), &b.to_string_lossy()))
+            }
+            SortMode::Lexicographic => paths.sort(),
+        }
+    }
+}
+
+// Compare strings case-insensitively, treating runs of digits as numbers
+pub fn natural_cmp(a: &str, b: &str) -> Ordering {
+    let mut a_chars = a.chars().peekable();
+    let mut b_chars = b.chars().peekable();
+
+    loop {
+        match (a_chars.peek().copied(), b_chars.peek().copied()) {
+            (None, None) => break,
+            (None, Some(_)) => return Ordering::Less,
+            (Some(_), None) => return Ordering::Greater,
+            (Some(x), Some(y)) if x.is_ascii_digit() && y.is_ascii_digit() => {
+                let a_digits = take_digits(&mut a_chars);
+                let b_digits = take_digits(&mut b_chars);
+
+                // Compare by value without overflowing: fewer significant digits is smaller
+                let a_trimmed = a_digits.trim_start_matches('0');
+                let b_trimmed = b_digits.trim_start_matches('0');
+                let ordering = a_trimmed
+                    .len()
+                    .cmp(&b_trimmed.len())
+                    .then_with(|| a_trimmed.cmp(b_trimmed));
+                if ordering != Ordering::Equal {
+                    return ordering;
+                }
+            }
+            (Some(x), Some(y)) => {
+                let ordering = x.to_lowercase().cmp(y.to_lowercase());
+                if ordering != Ordering::Equal {
+                    return ordering;
+                }
+                a_chars.next();
+                b_chars.next();
+            }
+        }
+    }
+
+    // Fall back to byte order so distinct names never compare equal
+    a.cmp(b)
+}
+
+// Consume a run of ASCII digits
+fn take_digits(chars: &mut std::iter::Peekable<std::str::Chars>) -> String {
+    let mut digits = String::new();
+    while let Some(&c) = chars.peek() {
+        if !c.is_ascii_digit() {
+            break;
+        }
+        digits.push(c);
+        chars.next();
+    }
+    digits
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn digit_runs_compare_by_value() {
+        assert_eq!(natural_cmp("file2", "file10"), Ordering::Less);
+        assert_eq!(natural_cmp("v1.10", "v1.9"), Ordering::Greater);
+        assert_eq!(
+            natural_cmp("99999999999999999999999", "100000000000000000000000"),
+            Ordering::Less
+        );
+    }
+
+    #[test]
+    fn leading_zeros_only_break_ties() {
+        assert_eq!(natural_cmp("a007", "a8"), Ordering::Less);
+        assert_eq!(natural_cmp("a01", "a1"), Ordering::Less);
+        assert_eq!(natural_cmp("a1", "a01"), Ordering::Greater);
+    }
+
+    #[test]
+    fn case_only_breaks_ties() {
+        assert_eq!(natural_cmp("apple", "Banana"), Ordering::Less);
+        assert_eq!(natural_cmp("Apple", "apple"), Ordering::Less);
+        assert_eq!(natural_cmp("README", "readme"), Ordering::Less);
+        assert_eq!(natural_cmp("same", "same"), Ordering::Equal);
+    }
+}
@@ -0,0 +1,53 @@
+use serde::Serialize;
+use std::fs;
+use std::io::{self, BufWriter};
+use std::path::Path;
+
+// Write the value as JSON to `<path>.tmp`, then replace the real file only once
+// the data is safely on disk, so a crash or full disk can't leave it half-written
+pub fn write_json_atomically<T: Serialize>(path: &Path, value: &T) -> io::Result<()> {
+    write_atomically(path, |writer| serde_json::to_writer(writer, value))
+}
+
+// Like `write_json_atomically`, but indented for files people read or edit by hand
+pub fn write_pretty_json_atomically<T: Serialize>(path: &Path, value: &T) -> io::Result<()> {
+    write_atomically(path, |writer| serde_json::to_writer_pretty(writer, value))
+}
+
+fn write_atomically(
+    path: &Path,
+    write: impl FnOnce(&mut BufWriter<fs::File>) -> serde_json::Result<()>,
+) -> io::Result<()> {
+    let mut tmp_file = path.as_os_str().to_owned();
+    tmp_file.push(".tmp");
+    let file = fs::File::create(&tmp_file)?;
+    let mut writer = BufWriter::new(file);
+    write(&mut writer)?;
+    let file = writer.into_inner().map_err(|err| err.into_error())?;
+    file.sync_all()?;
+    fs::rename(&tmp_file, path)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn writes_replace_the_file_without_leaving_the_temporary_one() {
+        let dir = std::env::temp_dir().join(format!("pray-storage-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("state.json");
+        fs::write(&path, "old").unwrap();
+
+        write_pretty_json_atomically(&path, &vec![1]).unwrap();
+        let pretty = fs::read_to_string(&path).unwrap();
+        write_json_atomically(&path, &vec![1]).unwrap();
+        let compact = fs::read_to_string(&path).unwrap();
+        let leftover = dir.join("state.json.tmp").exists();
+        let _ = fs::remove_dir_all(&dir);
+
+        assert_eq!(pretty, "[\n  1\n]");
+        assert_eq!(compact, "[1]");
+        assert!(!leftover);
+    }
+}
@@ -0,0 +1,168 @@
+use ratatui::style::Color;
+use serde::Deserialize;
+use std::fs;
+use std::path::Path;
+use std::str::FromStr;
+
+// Names of the built-in themes, in the order they are cycled
+pub const BUILT_IN_THEMES: [&str; 3] = ["dark", "light", "high-contrast"];
+
+// Colors used throughout the UI
+#[derive(Clone)]
+pub struct Theme {
+    pub name: String,
+    // Border and title of the focused pane, popups and prompts
+    pub focused_border: Color,
+    // Plain footer text
+    pub text: Color,
+    // Footer messages
+    pub message: Color,
+    // Entry under the cursor
+    pub cursor_fg: Color,
+    pub cursor_bg: Color,
+    // Selected entries, and a selected entry under the cursor
+    pub selected_fg: Color,
+    pub selected_bg: Color,
+    pub selected_cursor_bg: Color,
+    // Entries in the visual selection range
+    pub visual_bg: Color,
+    // Tags and other secondary highlights
+    pub accent: Color,
+    pub warning: Color,
+    pub error: Color,
+}
+
+// Colors of a custom theme file, each overriding its base theme when set
+#[derive(Deserialize, Default)]
+#[serde(default)]
+struct ThemeFile {
+    name: Option<String>,
+    base: Option<String>,
+    focused_border: Option<String>,
+    text: Option<String>,
+    message: Option<String>,
+    cursor_fg: Option<String>,
+    cursor_bg: Option<String>,
+    selected_fg: Option<String>,
+    selected_bg: Option<String>,
+    selected_cursor_bg: Option<String>,
+    visual_bg: Option<String>,
+    accent: Option<String>,
+    warning: Option<String>,
+    error: Option<String>,
+}
+
+impl Theme {
+    // The original look of the app
+    pub fn dark() -> Theme {
+        Theme {
+            name: "dark".to_string(),
+            focused_border: Color::Yellow,
+            text: Color::White,
+            message: Color::Green,
+            cursor_fg: Color::White,
+            cursor_bg: Color::Blue,
+            selected_fg: Color::Black,
+            selected_bg: Color::Green,
+            selected_cursor_bg: Color::LightGreen,
+            visual_bg: Color::Magenta,
+            accent: Color::Cyan,
+            warning: Color::Yellow,
+            error: Color::Red,
+        }
+    }
+
+    // Darker colors that stay readable on a light background
+    pub fn light() -> Theme {
+        Theme {
+            name: "light".to_string(),
+            focused_border: Color::Blue,
+            text: Color::Black,
+            message: Color::Rgb(0, 110, 0),
+            cursor_fg: Color::White,
+            cursor_bg: Color::Blue,
+            selected_fg: Color::Black,
+            selected_bg: Color::LightGreen,
+            selected_cursor_bg: Color::Green,
+            visual_bg: Color::LightMagenta,
+            accent: Color::Magenta,
+            warning: Color::Rgb(150, 90, 0),
+            error: Color::Red,
+        }
+    }
+
+    // Black and white with bright accents
+    pub fn high_contrast() -> Theme {
+        Theme {
+            name: "high-contrast".to_string(),
+            focused_border: Color::White,
+            text: Color::White,
+            message: Color::LightYellow,
+            cursor_fg: Color::Black,
+            cursor_bg: Color::White,
+            selected_fg: Color::Black,
+            selected_bg: Color::LightYellow,
+            selected_cursor_bg: Color::LightCyan,
+            visual_bg: Color::LightMagenta,
+            accent: Color::LightCyan,
+            warning: Color::LightYellow,
+            error: Color::LightRed,
+        }
+    }
+
+    // Look up a built-in theme by name
+    pub fn built_in(name: &str) -> Option<Theme> {
+        match name {
+            "dark" => Some(Theme::dark()),
+            "light" => Some(Theme::light()),
+            "high-contrast" => Some(Theme::high_contrast()),
+            _ => None,
+        }
+    }
+
+    // Load the named theme, checking the custom theme file before the built-in ones
+    // and falling back to the dark theme
+    pub fn load(name: &str, theme_file: &Path) -> Theme {
+        if let Some(custom) = Self::load_custom(theme_file) {
+            if custom.name == name {
+                return custom;
+            }
+        }
+        Theme::built_in(name).unwrap_or_else(Theme::dark)
+    }
+
+    // Load a custom theme from a JSON file, named "custom" unless it says otherwise
+    pub fn load_custom(theme_file: &Path) -> Option<Theme> {
+        let contents = fs::read_to_string(theme_file).ok()?;
+        let file: ThemeFile = serde_json::from_str(&contents).ok()?;
+
+        let mut theme = file
+            .base
+            .as_deref()
+            .and_then(Theme::built_in)
+            .unwrap_or_else(Theme::dark);
+        theme.name = file.name.unwrap_or_else(|| "custom".to_string());
+
+        let overrides = [
+            (&mut theme.focused_border, file.focused_border),
+            (&mut theme.text, file.text),
+            (&mut theme.message, file.message),
+            (&mut theme.cursor_fg, file.cursor_fg),
+            (&mut theme.cursor_bg, file.cursor_bg),
+            (&mut theme.selected_fg, file.selected_fg),
+            (&mut theme.selected_bg, file.selected_bg),
+            (&mut theme.selected_cursor_bg, file.selected_cursor_bg),
+            (&mut theme.visual_bg, file.visual_bg),
+            (&mut theme.accent, file.accent),
+            (&mut theme.warning, file.warning),
+            (&mut theme.error, file.error),
+        ];
+        for (color, value) in overrides {
+            // Colors are names like "yellow" or hex like "#ffaa00"
+            if let Some(parsed) = value.and_then(|value| Color::from_str(&value).ok()) {
+                *color = parsed;
+            }
+        }
+        Some(theme)
+    }
+}
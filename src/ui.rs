@@ -1,26 +1,132 @@
 use ratatui::{
     layout::{Alignment, Constraint, Direction, Layout, Rect},
-    style::{Color, Modifier, Style},
+    style::{Modifier, Style},
     text::{Line, Span},
-    widgets::{Block, Borders, Clear, List, ListItem, Paragraph},
+    widgets::{Block, Borders, Clear, List, ListItem, Paragraph, Wrap},
     Frame,
 };
 
 use ratatui::layout::Position;
 
-use crate::app::{App, FocusedPane};
+use crate::app::{
+    estimate_tokens, App, FocusedPane, FuzzyJump, NotificationLevel, Popup, Prompt, ReviewRow,
+    MAX_SPLIT_RATIO, MIN_SPLIT_RATIO, QUICK_JUMP_LABELS,
+};
+use crate::config::CollectionView;
+use crate::input::TextInput;
+use crate::keymap;
+use crate::output::format_size;
+use crate::search::fuzzy_match;
+use crate::settings::SETTINGS;
+use std::fs;
+use std::path::PathBuf;
 
 // Main UI function to draw all panes at once
 pub fn ui(frame: &mut Frame, app: &App) {
     let size = frame.area();
+    draw_screen(frame, app, size);
+
+    // Notifications go last so they show over popups too, above the footer row
+    let content = Rect {
+        height: size.height.saturating_sub(1),
+        ..size
+    };
+    draw_notifications(frame, app, content);
+}
 
+// The main screen, or whichever popup or screen replaces it
+fn draw_screen(frame: &mut Frame, app: &App, size: Rect) {
     if app.show_help {
         draw_help_screen(frame, size);
         return;
     }
 
+    if app.show_pane_help {
+        draw_pane_help(frame, app, size);
+        return;
+    }
+
     if app.renaming_collection {
-        draw_rename_prompt(frame, app, size);
+        draw_input_prompt(
+            frame,
+            app,
+            "Rename Collection",
+            &app.new_collection_name,
+            size,
+        );
+        return;
+    }
+
+    if app.show_collection_stats {
+        draw_collection_stats(frame, app, size);
+        return;
+    }
+
+    if app.show_selection_review {
+        draw_selection_review(frame, app, size);
+        return;
+    }
+
+    if app.show_settings {
+        draw_settings(frame, app, size);
+        return;
+    }
+
+    if let Some(text) = &app.copy_preview_text {
+        draw_copy_preview_text(frame, app, text, size);
+        return;
+    }
+
+    if let Some(files) = &app.copy_preview {
+        draw_copy_preview(frame, app, files, size);
+        return;
+    }
+
+    if app.confirming_quit {
+        draw_confirm_popup(
+            frame,
+            app,
+            "Quit",
+            vec![
+                Line::from(format!(
+                    "{} items are selected but not copied.",
+                    app.selected_items.len()
+                )),
+                Line::from(""),
+                Line::from("[c] Copy then quit  [q] Quit anyway  [n/Esc] Cancel"),
+            ],
+            size,
+        );
+        return;
+    }
+
+    if let Some((path, _)) = &app.pending_overwrite {
+        draw_confirm_popup(
+            frame,
+            app,
+            "File Exists",
+            vec![
+                Line::from(format!("{} already exists.", path.display())),
+                Line::from(""),
+                Line::from("[o] Overwrite  [r] Rename  [c/Esc] Cancel"),
+            ],
+            size,
+        );
+        return;
+    }
+
+    if let Some(popup) = app.active_popup {
+        draw_list_popup(frame, app, popup, size);
+        return;
+    }
+
+    if let Some(jump) = &app.fuzzy_jump {
+        draw_fuzzy_jump(frame, app, jump, size);
+        return;
+    }
+
+    if let Some(prompt) = app.active_prompt {
+        draw_input_prompt(frame, app, prompt_title(prompt), &app.prompt_input, size);
         return;
     }
 
@@ -34,11 +140,15 @@ pub fn ui(frame: &mut Frame, app: &App) {
         .split(size);
 
     // Split the main content horizontally into files and collections panes
+    let split_ratio = app
+        .config
+        .split_ratio
+        .clamp(MIN_SPLIT_RATIO, MAX_SPLIT_RATIO);
     let main_chunks = Layout::default()
         .direction(Direction::Horizontal)
         .constraints([
-            Constraint::Percentage(50), // Left: Files pane
-            Constraint::Percentage(50), // Right: Collections pane
+            Constraint::Percentage(split_ratio),       // Left: Files pane
+            Constraint::Percentage(100 - split_ratio), // Right: Collections pane
         ])
         .split(chunks[0]);
 
@@ -47,27 +157,140 @@ pub fn ui(frame: &mut Frame, app: &App) {
     // Draw the collections pane
     draw_collections_pane(frame, app, main_chunks[1]);
 
-    // Footer with basic commands or messages
-    let footer_text = if let Some(message) = &app.footer_message {
-        Span::styled(message, Style::default().fg(Color::Green))
+    // Footer with basic commands, or a warning that saving fails
+    let footer_text = if let Some(warning) = &app.save_warning {
+        Span::styled(warning, Style::default().fg(app.theme.warning))
+    } else if let Some(count) = app.pending_count {
+        Span::raw(format!(
+            "{}  [j/k] Move that many rows [PageDown/PageUp] Pages [Space] Select that many files",
+            count
+        ))
     } else {
         match app.focused_pane {
+            FocusedPane::FilesPane if app.searching || !app.search_input.text().is_empty() => {
+                let hint = if app.searching {
+                    "[Enter] Done [Esc] Cancel"
+                } else {
+                    "[n/N] Next/Previous [Esc] Clear"
+                };
+                Span::raw(format!(
+                    "/{}  {}  {}",
+                    app.search_input.text(),
+                    app.search_status(),
+                    hint
+                ))
+            }
+            FocusedPane::FilesPane if app.quick_jump => {
+                Span::raw("-- JUMP -- Press a label to jump to that file, any other key to cancel")
+            }
+            FocusedPane::FilesPane if app.visual_anchor.is_some() => {
+                Span::raw("-- VISUAL -- [j/k] Extend [Space] Toggle range [Esc] Exit")
+            }
+            FocusedPane::FilesPane
+                if app.config.show_cursor_path && !app.directory_entries.is_empty() =>
+            {
+                let gauge_width = if app.selected_items.is_empty() {
+                    0
+                } else {
+                    token_gauge(app).width()
+                };
+                let width = (chunks[1].width as usize).saturating_sub(gauge_width);
+                Span::raw(truncate_start(&app.cursor_path().unwrap_or_default(), width))
+            }
             FocusedPane::FilesPane => Span::raw(
                 "[j/k] Up/Down [h] Back [l/Enter] Enter \
-                 [Space] Select [a] All [c] Copy [q] Quit",
+                 [Space] Select [a] All [*] Glob [c] Copy [y] Paths [q] Quit",
+            ),
+            FocusedPane::CollectionsPane => Span::raw(
+                "[j/k] Up/Down [d] Delete [c] Copy [r] Rename [t] Tag [f] Filter [m] Merge [q] Quit",
             ),
-            FocusedPane::CollectionsPane => {
-                Span::raw("[j/k] Up/Down [d] Delete [c] Copy [r] Rename [q] Quit")
+            FocusedPane::SelectedFilesPane => {
+                Span::raw("[j/k] Up/Down [Space] Include/Leave out [d] Remove [m] Move [q] Quit")
             }
-            FocusedPane::SelectedFilesPane => Span::raw("[j/k] Up/Down [Space] Unselect [q] Quit"),
         }
     };
 
     let footer = Paragraph::new(Line::from(footer_text))
-        .style(Style::default().fg(Color::White))
+        .style(Style::default().fg(app.theme.text))
         .block(Block::default());
 
-    frame.render_widget(footer, chunks[1]);
+    // Show the running token estimate of the selection on the right
+    let footer_area = if app.selected_items.is_empty() {
+        chunks[1]
+    } else {
+        let gauge = token_gauge(app);
+        let footer_chunks = Layout::default()
+            .direction(Direction::Horizontal)
+            .constraints([Constraint::Min(0), Constraint::Length(gauge.width() as u16)])
+            .split(chunks[1]);
+        frame.render_widget(Paragraph::new(gauge), footer_chunks[1]);
+        footer_chunks[0]
+    };
+
+    frame.render_widget(footer, footer_area);
+}
+
+// Draw the recent notifications over the bottom of the given area, newest last,
+// colored by severity
+fn draw_notifications(frame: &mut Frame, app: &App, area: Rect) {
+    if app.notifications.is_empty() {
+        return;
+    }
+    let lines: Vec<Line> = app
+        .notifications
+        .iter()
+        .map(|notification| {
+            let color = match notification.level {
+                NotificationLevel::Info => app.theme.message,
+                NotificationLevel::Warning => app.theme.warning,
+                NotificationLevel::Error => app.theme.error,
+            };
+            Line::from(Span::styled(
+                format!(" {} ", notification.text),
+                Style::default().fg(color),
+            ))
+        })
+        .collect();
+
+    let width = lines
+        .iter()
+        .map(|line| line.width() as u16)
+        .max()
+        .unwrap_or(0)
+        .min(area.width);
+    let height = (lines.len() as u16).min(area.height);
+    let notification_area = Rect::new(area.x, area.y + area.height - height, width, height);
+    frame.render_widget(Clear, notification_area);
+    frame.render_widget(Paragraph::new(lines), notification_area);
+}
+
+// Estimated tokens of the selection, as a colored bar against the budget when one is set
+fn token_gauge(app: &App) -> Line<'static> {
+    const BAR_WIDTH: usize = 10;
+
+    let Some(tokens) = app.selection_tokens else {
+        return Line::from(" counting tokens… ");
+    };
+    let Some(budget) = app.config.token_budget else {
+        return Line::from(format!(" ~{} tokens ", tokens));
+    };
+
+    let ratio = tokens as f64 / budget as f64;
+    let color = if ratio > 1.0 {
+        app.theme.error
+    } else if ratio > 0.75 {
+        app.theme.warning
+    } else {
+        app.theme.message
+    };
+    let filled = ((ratio * BAR_WIDTH as f64).round() as usize).min(BAR_WIDTH);
+
+    Line::from(vec![
+        Span::raw(format!(" ~{}/{} ", tokens, budget)),
+        Span::styled("█".repeat(filled), Style::default().fg(color)),
+        Span::styled("░".repeat(BAR_WIDTH - filled), Style::default().fg(color)),
+        Span::raw(" "),
+    ])
 }
 
 // Draw the files pane
@@ -76,7 +299,7 @@ fn draw_files_pane(frame: &mut Frame, app: &App, area: Rect) {
     let is_focused = matches!(app.focused_pane, FocusedPane::FilesPane);
 
     let border_style = if is_focused {
-        Style::default().fg(Color::Yellow)
+        Style::default().fg(app.theme.focused_border)
     } else {
         Style::default()
     };
@@ -89,9 +312,13 @@ fn draw_files_pane(frame: &mut Frame, app: &App, area: Rect) {
 
     let inner_area = block.inner(area);
     frame.render_widget(block, area);
+    app.pane_areas.files.set(inner_area);
 
     // Create list items for the directory entries
     let list_height = inner_area.height as usize;
+    let visual_range = app.visual_range();
+    let horizontal_scroll = if is_focused { app.horizontal_scroll } else { 0 };
+    let selected_counts = app.selected_counts();
     let visible_entries: Vec<ListItem> = app
         .directory_entries
         .iter()
@@ -99,22 +326,94 @@ fn draw_files_pane(frame: &mut Frame, app: &App, area: Rect) {
         .skip(app.scroll_position)
         .take(list_height)
         .map(|(i, entry)| {
-            let file_name = entry.file_name().unwrap().to_string_lossy();
+            // Entries without a final component, like `..`, show their whole path
+            let name = entry.file_name().unwrap_or(entry.as_os_str());
+            let file_name = name.to_string_lossy();
+            let is_lossy = name.to_str().is_none();
             let is_selected = app.selected_items.contains(entry);
             let is_cursor = is_focused && i == app.selected_file_index;
+            let in_visual_range = visual_range
+                .as_ref()
+                .is_some_and(|range| range.contains(&i));
 
             let style = match (is_selected, is_cursor) {
-                (true, true) => Style::default().fg(Color::Black).bg(Color::LightGreen),
-                (true, false) => Style::default().fg(Color::Black).bg(Color::Green),
-                (false, true) => Style::default().fg(Color::White).bg(Color::Blue),
+                _ if in_visual_range && !is_cursor => Style::default()
+                    .fg(app.theme.selected_fg)
+                    .bg(app.theme.visual_bg),
+                (true, true) => Style::default()
+                    .fg(app.theme.selected_fg)
+                    .bg(app.theme.selected_cursor_bg),
+                (true, false) => Style::default()
+                    .fg(app.theme.selected_fg)
+                    .bg(app.theme.selected_bg),
+                (false, true) => Style::default()
+                    .fg(app.theme.cursor_fg)
+                    .bg(app.theme.cursor_bg),
                 (false, false) => Style::default(),
             };
 
-            let symbol = if entry.is_dir() { "[D]" } else { "   " };
-            ListItem::new(Line::from(Span::styled(
-                format!("{} {}", symbol, file_name),
-                style,
-            )))
+            let symbol = app.entry_symbol(entry);
+            let mut spans = vec![];
+            if app.quick_jump {
+                // Label each visible row with the key that jumps to it
+                let label = QUICK_JUMP_LABELS
+                    .chars()
+                    .nth(i - app.scroll_position)
+                    .unwrap_or(' ');
+                spans.push(Span::styled(
+                    format!("{} ", label),
+                    Style::default()
+                        .fg(app.theme.focused_border)
+                        .add_modifier(Modifier::BOLD),
+                ));
+            }
+            spans.push(Span::styled(format!("{} ", symbol), style));
+            // Emphasize the characters the search matched
+            let matched = fuzzy_match(&file_name, app.search_input.text())
+                .filter(|_| !app.search_input.text().is_empty())
+                .unwrap_or_default();
+            if matched.is_empty() {
+                spans.push(Span::styled(file_name.to_string(), style));
+            } else {
+                let matched_style = style.fg(app.theme.accent).add_modifier(Modifier::BOLD);
+                for (position, c) in file_name.chars().enumerate() {
+                    let char_style = if matched.contains(&position) {
+                        matched_style
+                    } else {
+                        style
+                    };
+                    spans.push(Span::styled(c.to_string(), char_style));
+                }
+            }
+
+            // Flag names that are not valid UTF-8 and only shown approximately
+            if is_lossy {
+                spans.push(Span::styled(
+                    " [non-UTF-8]",
+                    Style::default().fg(app.theme.warning),
+                ));
+            }
+
+            if app.config.show_sizes {
+                let size = match app.entry_size(entry) {
+                    Some(size) => format_size(size),
+                    None => "…".to_string(),
+                };
+                spans.push(Span::styled(
+                    format!("  {}", size),
+                    Style::default().fg(app.theme.message),
+                ));
+            }
+
+            // Mark directories with selected items somewhere inside them
+            if let Some(count) = selected_counts.get(entry) {
+                spans.push(Span::styled(
+                    format!(" ●{}", count),
+                    Style::default().fg(app.theme.accent),
+                ));
+            }
+
+            ListItem::new(scroll_line(Line::from(spans), horizontal_scroll))
         })
         .collect();
 
@@ -127,18 +426,27 @@ fn draw_files_pane(frame: &mut Frame, app: &App, area: Rect) {
 // Draw the collections pane
 fn draw_collections_pane(frame: &mut Frame, app: &App, area: Rect) {
     // Split the collections pane vertically into list and details
+    let split = app.config.collections_split.min(100);
     let chunks = Layout::default()
         .direction(Direction::Vertical)
         .constraints([
-            Constraint::Percentage(50), // Top: Collection list
-            Constraint::Percentage(50), // Bottom: Selected files
+            Constraint::Percentage(split),       // Top: Collection list
+            Constraint::Percentage(100 - split), // Bottom: Selected files
         ])
         .split(area);
 
-    // Draw the collection list
-    draw_collection_list(frame, app, chunks[0]);
-    // Draw the selected files
-    draw_selected_files_pane(frame, app, chunks[1]);
+    // Draw the collection list, unless collapsed; an empty area keeps clicks off it
+    if split > 0 {
+        draw_collection_list(frame, app, chunks[0]);
+    } else {
+        app.pane_areas.collections.set(Rect::default());
+    }
+    // Draw the selected files, unless collapsed
+    if split < 100 {
+        draw_selected_files_pane(frame, app, chunks[1]);
+    } else {
+        app.pane_areas.selected_files.set(Rect::default());
+    }
 }
 
 // Draw the collection list
@@ -147,42 +455,111 @@ fn draw_collection_list(frame: &mut Frame, app: &App, area: Rect) {
     let is_focused = matches!(app.focused_pane, FocusedPane::CollectionsPane);
 
     let border_style = if is_focused {
-        Style::default().fg(Color::Yellow)
+        Style::default().fg(app.theme.focused_border)
     } else {
         Style::default()
     };
 
+    // Show the active tag filter in the title
+    let title = match &app.tag_filter {
+        Some(tag) => format!("[2] Collections #{}", tag),
+        None => "[2] Collections".to_string(),
+    };
+
     // Create a block with title and border
     let block = Block::default()
         .borders(Borders::ALL)
-        .title("[2] Collections")
+        .title(title)
         .border_style(border_style);
 
     let inner_area = block.inner(area);
     frame.render_widget(block, area);
+    app.pane_areas.collections.set(inner_area);
+
+    let horizontal_scroll = if is_focused { app.horizontal_scroll } else { 0 };
 
     // Create list items for the collections
     let items: Vec<ListItem> = app
-        .collections
-        .iter()
-        .enumerate()
-        .map(|(i, collection)| {
+        .visible_collection_indices()
+        .into_iter()
+        .skip(app.collections_scroll)
+        .map(|i| {
+            let collection = &app.collections[i];
             let is_cursor = is_focused && i == app.selected_collection_index;
 
             let style = if is_cursor {
-                Style::default().fg(Color::White).bg(Color::Blue)
+                Style::default()
+                    .fg(app.theme.cursor_fg)
+                    .bg(app.theme.cursor_bg)
             } else {
                 Style::default()
             };
 
-            let item_text = format!(
-                "{} - {} files - {}",
-                collection.name,
-                collection.num_files,
-                collection.timestamp.format("%Y-%m-%d %H:%M:%S")
-            );
+            let mark = if app.marked_collections.contains(&i) {
+                "[x] "
+            } else {
+                ""
+            };
+            let pin = if collection.pinned { "📌 " } else { "" };
+            let mut spans = vec![Span::styled(
+                format!("{}{}{}", mark, pin, collection.name),
+                style,
+            )];
+            if app.config.collection_view == CollectionView::Compact {
+                return ListItem::new(scroll_line(Line::from(spans), horizontal_scroll));
+            }
+
+            let tags_style = style.fg(app.theme.accent);
+            let tags: Vec<String> = collection
+                .tags
+                .iter()
+                .map(|tag| format!("#{}", tag))
+                .collect();
+            let count = format!("{} files", collection.num_files);
+            let timestamp = collection.timestamp.format("%Y-%m-%d %H:%M:%S").to_string();
+
+            if app.config.collection_view == CollectionView::Verbose {
+                if let Some(format) = collection.format {
+                    spans.push(Span::styled(format!(" [{}]", format.name()), tags_style));
+                }
+                let size = format_size(app.collection_stats(i).total_size);
+                let mut lines = vec![
+                    Line::from(spans),
+                    Line::from(Span::styled(format!("  {}, {}", count, size), style)),
+                ];
+                if !tags.is_empty() {
+                    lines.push(Line::from(Span::styled(
+                        format!("  {}", tags.join(" ")),
+                        tags_style,
+                    )));
+                }
+                lines.push(Line::from(Span::styled(format!("  {}", timestamp), style)));
+                let lines: Vec<Line> = lines
+                    .into_iter()
+                    .map(|line| scroll_line(line, horizontal_scroll))
+                    .collect();
+                return ListItem::new(lines);
+            }
+
+            for tag in &tags {
+                spans.push(Span::styled(format!(" {}", tag), tags_style));
+            }
+            if let Some(format) = collection.format {
+                spans.push(Span::styled(format!(" [{}]", format.name()), tags_style));
+            }
 
-            ListItem::new(Line::from(Span::styled(item_text, style)))
+            // Add the count and timestamp only while they fit the pane
+            let mut width: usize = spans.iter().map(|span| span.content.chars().count()).sum();
+            for detail in [count, timestamp] {
+                let detail = format!(" - {}", detail);
+                width += detail.chars().count();
+                if width > inner_area.width as usize {
+                    break;
+                }
+                spans.push(Span::styled(detail, style));
+            }
+
+            ListItem::new(scroll_line(Line::from(spans), horizontal_scroll))
         })
         .collect();
 
@@ -198,7 +575,7 @@ fn draw_selected_files_pane(frame: &mut Frame, app: &App, area: Rect) {
     let is_focused = matches!(app.focused_pane, FocusedPane::SelectedFilesPane);
 
     let border_style = if is_focused {
-        Style::default().fg(Color::Yellow)
+        Style::default().fg(app.theme.focused_border)
     } else {
         Style::default()
     };
@@ -209,10 +586,12 @@ fn draw_selected_files_pane(frame: &mut Frame, app: &App, area: Rect) {
         .border_style(border_style);
     let inner_area = block.inner(area);
     frame.render_widget(block, area);
+    app.pane_areas.selected_files.set(inner_area);
 
-    let items: Vec<ListItem>;
+    let horizontal_scroll = if is_focused { app.horizontal_scroll } else { 0 };
+    let list_height = inner_area.height as usize;
 
-    match app.focused_pane {
+    let items: Vec<ListItem> = match app.focused_pane {
         FocusedPane::FilesPane => {
             // Display selected items from the FilesPane
             if app.selected_items.is_empty() {
@@ -222,31 +601,31 @@ fn draw_selected_files_pane(frame: &mut Frame, app: &App, area: Rect) {
                 return;
             }
 
-            let base_dir = &app.base_dir;
-
-            items = app
-                .selected_items
+            let under_cursor = app.directory_entries.get(app.selected_file_index);
+            app.sorted_selection()
                 .iter()
-                .enumerate()
-                .map(|(i, entry)| {
-                    let display_path = entry.strip_prefix(base_dir).unwrap_or(entry);
-                    let file_name = display_path.to_string_lossy();
-                    let is_cursor = is_focused && i == app.selected_file_in_collection_index;
-
-                    let style = if is_cursor {
-                        Style::default().fg(Color::White).bg(Color::Blue)
+                .skip(app.selected_files_scroll)
+                .take(list_height)
+                .map(|entry| {
+                    let file_name = app.display_path(entry);
+                    // The item under the files pane cursor stands out
+                    let style = if Some(entry) == under_cursor {
+                        Style::default().add_modifier(Modifier::BOLD)
                     } else {
                         Style::default()
                     };
 
-                    ListItem::new(Line::from(Span::styled(file_name, style)))
+                    ListItem::new(scroll_line(
+                        Line::from(Span::styled(file_name, style)),
+                        horizontal_scroll,
+                    ))
                 })
-                .collect();
+                .collect()
         }
         FocusedPane::CollectionsPane | FocusedPane::SelectedFilesPane => {
             // Display files from the selected collection
-            if app.collections.is_empty() {
-                // Display a message if there are no collections
+            if !app.selected_collection_visible() {
+                // Display a message if there are no collections, or none shown
                 let text = Paragraph::new("No collections").alignment(Alignment::Center);
                 frame.render_widget(text, inner_area);
                 return;
@@ -261,28 +640,36 @@ fn draw_selected_files_pane(frame: &mut Frame, app: &App, area: Rect) {
                 return;
             }
 
-            let base_dir = &app.base_dir;
-
-            items = collection
+            collection
                 .files
                 .iter()
                 .enumerate()
+                .skip(app.selected_files_scroll)
+                .take(list_height)
                 .map(|(i, entry)| {
-                    let display_path = entry.strip_prefix(base_dir).unwrap_or(entry);
-                    let file_name = display_path.to_string_lossy();
+                    let file_name = app.display_path(&entry.path);
                     let is_cursor = is_focused && i == app.selected_file_in_collection_index;
 
-                    let style = if is_cursor {
-                        Style::default().fg(Color::White).bg(Color::Blue)
+                    let mut style = if is_cursor {
+                        Style::default()
+                            .fg(app.theme.cursor_fg)
+                            .bg(app.theme.cursor_bg)
                     } else {
                         Style::default()
                     };
-
-                    ListItem::new(Line::from(Span::styled(file_name, style)))
+                    // Files left out of copies are dimmed
+                    if !entry.enabled {
+                        style = style.add_modifier(Modifier::DIM | Modifier::CROSSED_OUT);
+                    }
+
+                    ListItem::new(scroll_line(
+                        Line::from(Span::styled(file_name, style)),
+                        horizontal_scroll,
+                    ))
                 })
-                .collect();
+                .collect()
         }
-    }
+    };
 
     let files_list =
         List::new(items).highlight_style(Style::default().add_modifier(Modifier::BOLD));
@@ -292,8 +679,6 @@ fn draw_selected_files_pane(frame: &mut Frame, app: &App, area: Rect) {
 
 // Draw the help screen
 fn draw_help_screen(frame: &mut Frame, size: Rect) {
-    use ratatui::widgets::Wrap;
-
     let help_text = vec![
         Line::from(Span::styled(
             "Help - Available Commands",
@@ -303,18 +688,164 @@ fn draw_help_screen(frame: &mut Frame, size: Rect) {
         Line::from(Span::raw("[1] Switch to Files Pane")),
         Line::from(Span::raw("[2] Switch to Collections Pane")),
         Line::from(Span::raw("[3] Switch to Selected Files Pane")),
+        Line::from(Span::raw(
+            "[PageDown/PageUp] Move a page, [Ctrl+d/Ctrl+u] half a page",
+        )),
         Line::from(Span::raw("[h] Go back to parent directory")),
         Line::from(Span::raw("[l/Enter] Enter directory")),
-        Line::from(Span::raw("[j/k] Move down/up")),
+        Line::from(Span::raw("[:] Go to a directory by typing its path")),
+        Line::from(Span::raw(
+            "[;] Go to a directory under the base by fuzzy matching its name",
+        )),
+        Line::from(Span::raw(
+            "[/] Search names in the current directory; [n/N] next/previous match",
+        )),
+        Line::from(Span::raw(
+            "[b] Bookmark the current directory, or remove its bookmark",
+        )),
+        Line::from(Span::raw("['] Show bookmarks to jump to")),
+        Line::from(Span::raw("[e] Open file in $EDITOR")),
+        Line::from(Span::raw(
+            "[w] Open the current directory in the file manager",
+        )),
+        Line::from(Span::raw(
+            "[=] Make the current directory the base for relative paths",
+        )),
+        Line::from(Span::raw("[F5] Refresh the current directory")),
+        Line::from(Span::raw(
+            "[j/k] Move down/up (or mouse wheel, click, double-click)",
+        )),
+        Line::from(Span::raw(
+            "[4-9] Start a count for the next motion or selection, e.g. 5j, 40j or 4 PageDown",
+        )),
+        Line::from(Span::raw("[f] Quick jump to a visible file by its label")),
+        Line::from(Span::raw("[o] Cycle sort order (natural / lexicographic)")),
+        Line::from(Span::raw(
+            "[Shift+Left/Right] Scroll long paths horizontally",
+        )),
         Line::from(Span::raw("[Space] Select/Deselect item")),
         Line::from(Span::raw("[a] Select/Deselect all items")),
-        Line::from(Span::raw("[c] Copy selected files' contents to clipboard")),
+        Line::from(Span::raw(
+            "[i] Invert the selection in the current directory",
+        )),
+        Line::from(Span::raw(
+            "[Ctrl+a] Select/Deselect all files under this directory recursively",
+        )),
+        Line::from(Span::raw("[x] Clear the selection in all directories")),
+        Line::from(Span::raw("[X] Clear the selection and the saved session")),
+        Line::from(Span::raw(
+            "[r] Review everything selected, grouped by directory",
+        )),
+        Line::from(Span::raw(
+            "[u] List everything selected to unselect files from any directory",
+        )),
+        Line::from(Span::raw(
+            "[v] Visual mode: select a range with j/k, toggle with Space",
+        )),
+        Line::from(Span::raw("[*] Select files matching a glob pattern")),
+        Line::from(Span::raw(
+            "[S] Search file contents under current directory",
+        )),
+        Line::from(Span::raw("[#] Set line range to copy for file")),
+        Line::from(Span::raw("[m] Rename the file or directory on disk")),
+        Line::from(Span::raw(
+            "[c] Copy selected files' contents to clipboard and clear the selection",
+        )),
+        Line::from(Span::raw(
+            "[G] Select files git reports as modified or untracked",
+        )),
+        Line::from(Span::raw(
+            "[n] Save the selection as a named collection without copying",
+        )),
+        Line::from(Span::raw(
+            "[p] Preview the files a copy would include, [P] the exact text ([Tab] switches)",
+        )),
+        Line::from(Span::raw(
+            "[K] Copy selected files' contents to clipboard and keep the selection",
+        )),
+        Line::from(Span::raw(
+            "[C] Copy only the file or directory under the cursor",
+        )),
+        Line::from(Span::raw("[y] Copy selected paths to clipboard")),
+        Line::from(Span::raw(
+            "[Y] Copy the tree of the current directory, without contents",
+        )),
+        Line::from(Span::raw(
+            "[A] Toggle absolute/relative paths in copies and the selected files pane",
+        )),
+        Line::from(Span::raw("[R] Pick from recently copied files")),
+        Line::from(Span::raw(
+            "[H] Show the history of copies to copy one again or save it as a collection",
+        )),
         Line::from(Span::raw("[d] Delete selected collection or unselect file")),
+        Line::from(Span::raw(
+            "[m] In Selected Files, move the file to another collection",
+        )),
+        Line::from(Span::raw(
+            "[Space] In Selected Files, leave the file out of copies or include it again; [d] removes it",
+        )),
+        Line::from(Span::raw("[D] Duplicate selected collection")),
+        Line::from(Span::raw("[x] Export selected collection to a file")),
+        Line::from(Span::raw(
+            "[z] Bundle the selected collection's files into a zip",
+        )),
+        Line::from(Span::raw("[e] Edit selected collection's description")),
+        Line::from(Span::raw("[p] Pin/Unpin selected collection at the top")),
+        Line::from(Span::raw(
+            "[v] Cycle collections view (normal, compact, verbose)",
+        )),
+        Line::from(Span::raw("[o] Cycle the selected collection's copy format")),
         Line::from(Span::raw("[r] Rename selected collection")),
+        Line::from(Span::raw("[t] Add/Remove tag on selected collection")),
+        Line::from(Span::raw("[i] Show stats of selected collection")),
+        Line::from(Span::raw("[f] Filter collections by tag (empty to clear)")),
+        Line::from(Span::raw("[Space] Mark collection for merging")),
+        Line::from(Span::raw(
+            "[m/M] Merge marked collections (M deletes the sources)",
+        )),
+        Line::from(Span::raw(
+            "[b] In Collections, save paths relative to the base so the collection survives moves",
+        )),
+        Line::from(Span::raw(
+            "[R] In Collections, rebase collections whose files moved under the base",
+        )),
         Line::from(Span::raw("[g] Toggle respecting .gitignore")),
-        Line::from(Span::raw("[ESC] Cancel renaming")),
+        Line::from(Span::raw("[</>] Narrow/Widen the focused pane")),
+        Line::from(Span::raw(
+            "[Z] Resize or collapse the collection list and selected files",
+        )),
+        Line::from(Span::raw("[T] Cycle color themes")),
+        Line::from(Span::raw(
+            "[E] Edit the prompt template wrapped around copied files",
+        )),
+        Line::from(Span::raw(
+            "[I] Edit the instructions appended after copied files",
+        )),
+        Line::from(Span::raw("[s] Toggle stripping comments and blank lines")),
+        Line::from(Span::raw("[L] Toggle following symlinks when copying")),
+        Line::from(Span::raw(
+            "[O] Cycle copying full contents, git diffs or one block per selected directory",
+        )),
+        Line::from(Span::raw(
+            "[F] Cycle the default copy format (markdown, xml, plain)",
+        )),
+        Line::from(Span::raw(
+            "[,] Open the settings screen to change options in one place",
+        )),
+        Line::from(Span::raw(
+            "[`] Cycle the markdown code fence (``````, ```, ~~~)",
+        )),
+        Line::from(Span::raw(
+            "[B] Set a token budget; files past it are left out of copies",
+        )),
+        Line::from(Span::raw("[ESC] Cancel renaming or leave visual mode")),
+        Line::from(Span::raw(
+            "[U] Show sizes in the files pane, directories computed in the background",
+        )),
         Line::from(Span::raw("[q] Quit the application")),
+        Line::from(Span::raw("[Q] Copy the selection and quit")),
         Line::from(Span::raw("[?] Show this help screen")),
+        Line::from(Span::raw("[F1] Show only the keys of the focused pane")),
         Line::from(""),
         Line::from(Span::raw("Press any key to return")),
     ];
@@ -329,22 +860,533 @@ fn draw_help_screen(frame: &mut Frame, size: Rect) {
     frame.render_widget(help_paragraph, size);
 }
 
-// Draw the rename prompt
-fn draw_rename_prompt(frame: &mut Frame, app: &App, area: Rect) {
+// Draw a list popup where entries can be marked with Space and confirmed with Enter
+fn draw_list_popup(frame: &mut Frame, app: &App, popup: Popup, area: Rect) {
+    let (title, entries): (&str, Vec<String>) = match popup {
+        Popup::RecentFiles => (
+            "Recent Files - [Space] Mark [Enter] Select [Esc] Close",
+            app.config
+                .recent_files
+                .iter()
+                .map(|path| {
+                    path.strip_prefix(&app.base_dir)
+                        .unwrap_or(path)
+                        .display()
+                        .to_string()
+                })
+                .collect(),
+        ),
+        Popup::Bookmarks => (
+            "Bookmarks - [Enter] Go to [d] Delete [Esc] Close",
+            app.config
+                .bookmarks
+                .iter()
+                .map(|dir| {
+                    if dir.is_dir() {
+                        dir.display().to_string()
+                    } else {
+                        format!("{} (missing)", dir.display())
+                    }
+                })
+                .collect(),
+        ),
+        Popup::MoveToCollection => (
+            "Move File to Collection - [Enter] Move [Esc] Cancel",
+            app.collections
+                .iter()
+                .enumerate()
+                .map(|(i, collection)| {
+                    if i == app.selected_collection_index {
+                        format!("{} (current)", collection.name)
+                    } else {
+                        collection.name.clone()
+                    }
+                })
+                .collect(),
+        ),
+        Popup::CopyHistory => (
+            "Copy History - [Enter] Copy again [s] Save as collection [Esc] Close",
+            app.copy_history
+                .records
+                .iter()
+                .map(|record| {
+                    format!(
+                        "{}  {} files, {}",
+                        record.timestamp.format("%Y-%m-%d %H:%M:%S"),
+                        record.files.len(),
+                        format_size(record.bytes as u64)
+                    )
+                })
+                .collect(),
+        ),
+        Popup::Selection => (
+            "Selected - [d/Space] Unselect [Enter] Go to file [Esc] Close",
+            app.sorted_selection()
+                .iter()
+                .map(|path| {
+                    if path.is_dir() {
+                        format!("{}/", app.display_path(path))
+                    } else {
+                        app.display_path(path)
+                    }
+                })
+                .collect(),
+        ),
+        Popup::GrepResults => (
+            "Search Results - [Enter] Go to file [Space] Mark [Enter] Select marked [m] More [Esc] Close",
+            app.grep_results
+                .iter()
+                .take(app.grep_shown)
+                .map(|path| {
+                    path.strip_prefix(&app.base_dir)
+                        .unwrap_or(path)
+                        .display()
+                        .to_string()
+                })
+                .collect(),
+        ),
+    };
+
+    let mut block = Block::default()
+        .borders(Borders::ALL)
+        .title(title)
+        .border_style(Style::default().fg(app.theme.focused_border));
+    // The popup covers the footer, so search progress goes on its bottom border
+    if matches!(popup, Popup::GrepResults) {
+        block = block.title_bottom(format!(" {} ", app.grep_status()));
+    }
+    let popup_area = centered_rect(80, 60, area);
+    let inner_area = block.inner(popup_area);
+
+    // Clear the background before rendering the popup
+    frame.render_widget(Clear, popup_area);
+    frame.render_widget(block, popup_area);
+
+    if entries.is_empty() {
+        let searching = matches!(popup, Popup::GrepResults)
+            && app.grep_search.as_ref().is_some_and(|search| !search.done);
+        let text = if searching {
+            "Searching…"
+        } else {
+            "Nothing here yet"
+        };
+        let text = Paragraph::new(text).alignment(Alignment::Center);
+        frame.render_widget(text, inner_area);
+        return;
+    }
+
+    // Keep the cursor visible in long lists
+    let list_height = inner_area.height as usize;
+    let scroll = app
+        .popup_index
+        .saturating_sub(list_height.saturating_sub(1));
+
+    let items: Vec<ListItem> = entries
+        .into_iter()
+        .enumerate()
+        .skip(scroll)
+        .take(list_height)
+        .map(|(i, entry)| {
+            let mark = if !app.popup_is_markable() {
+                ""
+            } else if app.popup_marked.contains(&i) {
+                "[x] "
+            } else {
+                "[ ] "
+            };
+            let style = if i == app.popup_index {
+                Style::default()
+                    .fg(app.theme.cursor_fg)
+                    .bg(app.theme.cursor_bg)
+            } else {
+                Style::default()
+            };
+            ListItem::new(Line::from(Span::styled(
+                format!("{}{}", mark, entry),
+                style,
+            )))
+        })
+        .collect();
+
+    frame.render_widget(List::new(items), inner_area);
+}
+
+// Draw the stats popup of the selected collection
+fn draw_collection_stats(frame: &mut Frame, app: &App, area: Rect) {
+    let collection = &app.collections[app.selected_collection_index];
+    let stats = app.collection_stats(app.selected_collection_index);
+
+    let mut lines = vec![
+        Line::from(Span::styled(
+            collection.name.clone(),
+            Style::default().add_modifier(Modifier::BOLD),
+        )),
+        Line::from(""),
+    ];
+    if !collection.description.is_empty() {
+        lines.push(Line::from(collection.description.clone()));
+        lines.push(Line::from(""));
+    }
+    lines.extend([
+        Line::from(format!("Files: {}", stats.file_count)),
+        Line::from(format!("Total size: {}", format_size(stats.total_size))),
+        Line::from(format!("Estimated tokens: ~{}", stats.estimated_tokens)),
+        Line::from(match &collection.root {
+            Some(root) => format!("Paths: relative to {}", root.display()),
+            None => "Paths: absolute".to_string(),
+        }),
+    ]);
+    if let Some((path, size)) = &stats.largest_file {
+        let display_path = path.strip_prefix(&app.base_dir).unwrap_or(path);
+        lines.push(Line::from(format!(
+            "Largest file: {} ({})",
+            display_path.display(),
+            format_size(*size)
+        )));
+    }
+
+    let missing = app.validate_collection(app.selected_collection_index);
+    if !missing.is_empty() {
+        lines.push(Line::from(""));
+        lines.push(Line::from(Span::styled(
+            format!("Missing files: {}", missing.len()),
+            Style::default().fg(app.theme.error),
+        )));
+        for path in &missing {
+            let display_path = path.strip_prefix(&app.base_dir).unwrap_or(path);
+            lines.push(Line::from(format!("  {}", display_path.display())));
+        }
+    }
+
+    let changed = app.changed_files(app.selected_collection_index);
+    if !changed.is_empty() {
+        lines.push(Line::from(""));
+        lines.push(Line::from(Span::styled(
+            format!("Changed since added: {}", changed.len()),
+            Style::default().fg(app.theme.warning),
+        )));
+        for path in &changed {
+            let display_path = path.strip_prefix(&app.base_dir).unwrap_or(path);
+            lines.push(Line::from(format!("  {}", display_path.display())));
+        }
+    }
+
+    lines.push(Line::from(""));
+    lines.push(Line::from("File types:"));
+    for (extension, count) in &stats.type_breakdown {
+        lines.push(Line::from(format!("  {}: {}", extension, count)));
+    }
+    lines.push(Line::from(""));
+    lines.push(Line::from("Press any key to return"));
+
+    let stats_paragraph = Paragraph::new(lines)
+        .block(
+            Block::default()
+                .borders(Borders::ALL)
+                .title("Collection Stats"),
+        )
+        .wrap(Wrap { trim: false });
+
+    let popup_area = centered_rect(60, 60, area);
+
+    // Clear the background before rendering the popup
+    frame.render_widget(Clear, popup_area);
+    frame.render_widget(stats_paragraph, popup_area);
+}
+
+// Draw the review screen of all selected items grouped by directory
+fn draw_selection_review(frame: &mut Frame, app: &App, area: Rect) {
+    let block = Block::default()
+        .borders(Borders::ALL)
+        .title(format!(
+            "Selection Review ({} items) - [Enter/z] Fold [Z] Fold all [d] Remove [Esc] Close",
+            app.selected_items.len()
+        ))
+        .border_style(Style::default().fg(app.theme.focused_border));
+    let popup_area = centered_rect(80, 80, area);
+    let inner_area = block.inner(popup_area);
+
+    // Clear the background before rendering the popup
+    frame.render_widget(Clear, popup_area);
+    frame.render_widget(block, popup_area);
+
+    // Keep the cursor visible in long lists
+    let list_height = inner_area.height as usize;
+    let scroll = app
+        .review_index
+        .saturating_sub(list_height.saturating_sub(1));
+
+    let items: Vec<ListItem> = app
+        .review_rows()
+        .into_iter()
+        .enumerate()
+        .skip(scroll)
+        .take(list_height)
+        .map(|(i, row)| {
+            let style = if i == app.review_index {
+                Style::default()
+                    .fg(app.theme.cursor_fg)
+                    .bg(app.theme.cursor_bg)
+            } else {
+                Style::default()
+            };
+            let line = match row {
+                ReviewRow::Group(dir, count) => {
+                    let fold = if app.review_collapsed.contains(&dir) {
+                        "[+]"
+                    } else {
+                        "[-]"
+                    };
+                    let display_dir = dir.strip_prefix(&app.base_dir).unwrap_or(&dir);
+                    let display_dir = if display_dir.as_os_str().is_empty() {
+                        ".".to_string()
+                    } else {
+                        display_dir.display().to_string()
+                    };
+                    Line::from(Span::styled(
+                        format!("{} {}/ ({})", fold, display_dir, count),
+                        style.add_modifier(Modifier::BOLD),
+                    ))
+                }
+                ReviewRow::Item(path) => {
+                    let name = path.file_name().unwrap_or_default().to_string_lossy();
+                    let suffix = if path.is_dir() { "/" } else { "" };
+                    Line::from(Span::styled(format!("    {}{}", name, suffix), style))
+                }
+            };
+            ListItem::new(line)
+        })
+        .collect();
+
+    frame.render_widget(List::new(items), inner_area);
+}
+
+// Draw the files the next copy would include, with their total size and tokens
+fn draw_copy_preview(frame: &mut Frame, app: &App, files: &[PathBuf], area: Rect) {
+    let sizes: Vec<u64> = files
+        .iter()
+        .map(|file| fs::metadata(file).map_or(0, |metadata| metadata.len()))
+        .collect();
+    let tokens: usize = files.iter().map(|file| app.file_tokens(file)).sum();
+
+    let block = Block::default()
+        .borders(Borders::ALL)
+        .title(format!(
+            "Copy Preview ({} files, {}, ~{} tokens) - [Tab] Text [Enter] Copy [Esc] Cancel",
+            files.len(),
+            format_size(sizes.iter().sum()),
+            tokens
+        ))
+        .border_style(Style::default().fg(app.theme.focused_border));
+    let popup_area = centered_rect(80, 80, area);
+    let inner_area = block.inner(popup_area);
+
+    // Clear the background before rendering the popup
+    frame.render_widget(Clear, popup_area);
+    frame.render_widget(block, popup_area);
+
+    if files.is_empty() {
+        let text = Paragraph::new("No text files to copy").alignment(Alignment::Center);
+        frame.render_widget(text, inner_area);
+        return;
+    }
+
+    let items: Vec<ListItem> = files
+        .iter()
+        .zip(&sizes)
+        .skip(app.copy_preview_scroll)
+        .take(inner_area.height as usize)
+        .map(|(file, &size)| {
+            ListItem::new(Line::from(vec![
+                Span::raw(app.display_path(file)),
+                Span::styled(
+                    format!(" ({})", format_size(size)),
+                    Style::default().fg(app.theme.accent),
+                ),
+            ]))
+        })
+        .collect();
+
+    frame.render_widget(List::new(items), inner_area);
+}
+
+// Draw the exact text a copy would produce, scrolled by lines
+fn draw_copy_preview_text(frame: &mut Frame, app: &App, text: &str, area: Rect) {
     let block = Block::default()
         .borders(Borders::ALL)
-        .title("Rename Collection");
+        .title(format!(
+            "Copy Preview ({}, ~{} tokens) - [Tab] Files [Enter/c] Copy [Esc] Cancel",
+            format_size(text.len() as u64),
+            estimate_tokens(text.len() as u64)
+        ))
+        .border_style(Style::default().fg(app.theme.focused_border));
+    let popup_area = centered_rect(90, 90, area);
+
+    let paragraph = Paragraph::new(text)
+        .block(block)
+        .scroll((app.copy_preview_scroll.min(u16::MAX as usize) as u16, 0));
+
+    // Clear the background before rendering the popup
+    frame.render_widget(Clear, popup_area);
+    frame.render_widget(paragraph, popup_area);
+}
+
+// Draw the settings screen, one option per row with its current value
+fn draw_settings(frame: &mut Frame, app: &App, area: Rect) {
+    let block = Block::default()
+        .borders(Borders::ALL)
+        .title("Settings - [Enter] Change [Esc] Close")
+        .border_style(Style::default().fg(app.theme.focused_border));
+    let popup_area = centered_rect(60, 60, area);
+    let inner_area = block.inner(popup_area);
+
+    // Clear the background before rendering the popup
+    frame.render_widget(Clear, popup_area);
+    frame.render_widget(block, popup_area);
+
+    let label_width = SETTINGS
+        .iter()
+        .map(|setting| setting.label().chars().count())
+        .max()
+        .unwrap_or(0);
+    let items: Vec<ListItem> = SETTINGS
+        .iter()
+        .enumerate()
+        .map(|(i, &setting)| {
+            let style = if i == app.settings_index {
+                Style::default()
+                    .fg(app.theme.cursor_fg)
+                    .bg(app.theme.cursor_bg)
+            } else {
+                Style::default()
+            };
+            ListItem::new(Line::from(vec![
+                Span::styled(
+                    format!("{:width$}  ", setting.label(), width = label_width),
+                    style,
+                ),
+                Span::styled(
+                    app.setting_value(setting),
+                    style.fg(app.theme.accent).add_modifier(Modifier::BOLD),
+                ),
+            ]))
+        })
+        .collect();
+
+    frame.render_widget(List::new(items), inner_area);
+}
+
+// Draw a small popup asking the user to pick one of a few keys
+fn draw_confirm_popup(frame: &mut Frame, app: &App, title: &str, lines: Vec<Line>, area: Rect) {
+    let paragraph = Paragraph::new(lines)
+        .block(
+            Block::default()
+                .borders(Borders::ALL)
+                .title(title)
+                .border_style(Style::default().fg(app.theme.warning)),
+        )
+        .alignment(Alignment::Center)
+        .wrap(Wrap { trim: false });
+
+    let popup_area = centered_rect(60, 20, area);
+
+    // Clear the background before rendering the popup
+    frame.render_widget(Clear, popup_area);
+    frame.render_widget(paragraph, popup_area);
+}
+
+// Popup listing the bindings of the focused pane
+fn draw_pane_help(frame: &mut Frame, app: &App, area: Rect) {
+    let bindings = keymap::pane_bindings(app.focused_pane);
+    let key_width = bindings
+        .iter()
+        .map(|(keys, _)| keys.len())
+        .max()
+        .unwrap_or(0);
+    let lines: Vec<Line> = bindings
+        .iter()
+        .map(|(keys, description)| {
+            Line::from(vec![
+                Span::styled(
+                    format!("{:<width$}  ", keys, width = key_width),
+                    Style::default().fg(app.theme.accent),
+                ),
+                Span::raw(*description),
+            ])
+        })
+        .collect();
+
+    let paragraph = Paragraph::new(lines).block(
+        Block::default()
+            .borders(Borders::ALL)
+            .title(format!("{} Keys", keymap::pane_name(app.focused_pane)))
+            .title_bottom(" Press any key to return "),
+    );
+
+    let popup_area = centered_rect(70, 80, area);
+
+    // Clear the background before rendering the popup
+    frame.render_widget(Clear, popup_area);
+    frame.render_widget(paragraph, popup_area);
+}
+
+// Cut the start off text wider than the given width, so the end of a path stays visible
+fn truncate_start(text: &str, width: usize) -> String {
+    let len = text.chars().count();
+    if len <= width {
+        return text.to_string();
+    }
+    if width == 0 {
+        return String::new();
+    }
+    let tail: String = text.chars().skip(len - width + 1).collect();
+    format!("…{}", tail)
+}
+
+// Title shown on the popup of a text prompt
+fn prompt_title(prompt: Prompt) -> &'static str {
+    match prompt {
+        Prompt::SelectByGlob => "Select by Glob (e.g. **/*.rs)",
+        Prompt::LineRange => "Line Range (e.g. 40-120, empty for whole file)",
+        Prompt::Grep => "Search File Contents",
+        Prompt::EditSuffix => "Prompt Suffix (appended after the files, empty to remove)",
+        Prompt::EditTemplate => {
+            "Prompt Template ({tree} and {files} placeholders, empty to remove)"
+        }
+        Prompt::ToggleTag => "Add/Remove Tag",
+        Prompt::FilterByTag => "Filter by Tag (empty to clear)",
+        Prompt::MergeCollections {
+            delete_sources: false,
+        } => "Merged Collection Name",
+        Prompt::MergeCollections {
+            delete_sources: true,
+        } => "Merged Collection Name (sources will be deleted)",
+        Prompt::ExportCollection => "Export Collection to File",
+        Prompt::ExportZip => "Export Collection Files as Zip",
+        Prompt::TokenBudget => "Token Budget (e.g. 8000, empty to remove)",
+        Prompt::EditDescription => "Collection Description",
+        Prompt::SaveCollection => "Save Selection as Collection",
+        Prompt::JumpToDirectory => "Go to Directory ([Tab] Complete, ~ for home)",
+        Prompt::FuzzyJump => "Go to Directory",
+        Prompt::RenameFile => "Rename on Disk",
+    }
+}
 
-    let input = Paragraph::new(app.new_collection_name.as_str())
+// Draw a text input prompt
+fn draw_input_prompt(frame: &mut Frame, app: &App, title: &str, input: &TextInput, area: Rect) {
+    let block = Block::default().borders(Borders::ALL).title(title);
+
+    let paragraph = Paragraph::new(input.text())
         .block(block.clone())
-        .style(Style::default().fg(Color::Yellow));
+        .style(Style::default().fg(app.theme.focused_border))
+        .wrap(Wrap { trim: false });
 
     // Center the popup
     let popup_area = centered_rect(60, 20, area);
 
     // Clear the background before rendering the popup
     frame.render_widget(Clear, popup_area);
-    frame.render_widget(input, popup_area);
+    frame.render_widget(paragraph, popup_area);
 
     // Add a hint below the input box
     let hint = Paragraph::new("[Enter] Confirm, [Esc] Cancel")
@@ -360,11 +1402,108 @@ fn draw_rename_prompt(frame: &mut Frame, app: &App, area: Rect) {
 
     frame.render_widget(hint, hint_area);
 
-    // Put cursor past the end of the input text
+    // Put the terminal cursor at the input cursor, following it onto wrapped lines
+    let (row, column) = input.cursor_position(popup_area.width.saturating_sub(2) as usize);
+    frame.set_cursor_position(Position::new(
+        popup_area.x + column as u16 + 1,
+        popup_area.y + row as u16 + 1,
+    ));
+}
+
+// Draw the fuzzy directory jump: the query on top and the ranked matches below it
+fn draw_fuzzy_jump(frame: &mut Frame, app: &App, jump: &FuzzyJump, area: Rect) {
+    let block = Block::default()
+        .borders(Borders::ALL)
+        .title("Go to Directory - [Up/Down] Move [Enter] Go [Esc] Cancel")
+        .border_style(Style::default().fg(app.theme.focused_border));
+    let popup_area = centered_rect(80, 60, area);
+    let inner_area = block.inner(popup_area);
+    frame.render_widget(Clear, popup_area);
+    frame.render_widget(block, popup_area);
+    if inner_area.height < 2 {
+        return;
+    }
+
+    let query = format!("> {}", app.prompt_input.text());
+    frame.render_widget(
+        Paragraph::new(query).style(Style::default().fg(app.theme.focused_border)),
+        Rect {
+            height: 1,
+            ..inner_area
+        },
+    );
+    let (_, column) = app.prompt_input.cursor_position(usize::MAX);
+    let column = column.min(inner_area.width.saturating_sub(3) as usize);
     frame.set_cursor_position(Position::new(
-        popup_area.x + app.new_collection_name.len() as u16 + 1,
-        popup_area.y + 1,
+        inner_area.x + 2 + column as u16,
+        inner_area.y,
     ));
+
+    let list_area = Rect {
+        y: inner_area.y + 1,
+        height: inner_area.height - 1,
+        ..inner_area
+    };
+    if jump.matches.is_empty() {
+        let text = Paragraph::new("No matching directories").alignment(Alignment::Center);
+        frame.render_widget(text, list_area);
+        return;
+    }
+
+    // Keep the cursor visible in long lists
+    let list_height = list_area.height as usize;
+    let scroll = jump.index.saturating_sub(list_height.saturating_sub(1));
+    let items: Vec<ListItem> = jump
+        .matches
+        .iter()
+        .enumerate()
+        .skip(scroll)
+        .take(list_height)
+        .map(|(i, (path, positions))| {
+            let style = if i == jump.index {
+                Style::default()
+                    .fg(app.theme.cursor_fg)
+                    .bg(app.theme.cursor_bg)
+            } else {
+                Style::default()
+            };
+            let matched_style = style.fg(app.theme.accent).add_modifier(Modifier::BOLD);
+            let spans: Vec<Span> = path
+                .chars()
+                .enumerate()
+                .map(|(position, c)| {
+                    let char_style = if positions.contains(&position) {
+                        matched_style
+                    } else {
+                        style
+                    };
+                    Span::styled(c.to_string(), char_style)
+                })
+                .collect();
+            ListItem::new(Line::from(spans))
+        })
+        .collect();
+    frame.render_widget(List::new(items), list_area);
+}
+
+// Drop the first `offset` characters of a line to scroll it horizontally
+fn scroll_line(line: Line<'_>, offset: usize) -> Line<'_> {
+    let mut remaining = offset;
+    let spans: Vec<Span> = line
+        .spans
+        .into_iter()
+        .filter_map(|span| {
+            let len = span.content.chars().count();
+            if remaining >= len {
+                remaining -= len;
+                return None;
+            }
+            let content: String = span.content.chars().skip(remaining).collect();
+            remaining = 0;
+            Some(Span::styled(content, span.style))
+        })
+        .collect();
+    Line::from(spans)
 }
 
 // Helper function to create a centered rectangle
@@ -391,3 +1530,18 @@ fn centered_rect(percent_x: u16, percent_y: u16, area: Rect) -> Rect {
 
     horizontal_layout[1]
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn truncating_keeps_the_end_within_the_width() {
+        assert_eq!(truncate_start("/home/me/src", 20), "/home/me/src");
+        assert_eq!(truncate_start("/home/me/src", 12), "/home/me/src");
+        assert_eq!(truncate_start("/home/me/src", 6), "…e/src");
+        assert_eq!(truncate_start("/home/mé/src", 7), "…mé/src");
+        assert_eq!(truncate_start("/home/me/src", 1), "…");
+        assert_eq!(truncate_start("/home/me/src", 0), "");
+    }
+}
@@ -0,0 +1,65 @@
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::{self, Receiver};
+
+use notify::{Event, EventKind, RecommendedWatcher, RecursiveMode, Watcher};
+
+// Watches the directory shown in the files pane, one at a time
+pub struct DirWatcher {
+    // Missing when the platform's watcher couldn't be started
+    watcher: Option<RecommendedWatcher>,
+    // Directory currently watched, if watching it succeeded
+    watched: Option<PathBuf>,
+    events: Receiver<notify::Result<Event>>,
+}
+
+impl Default for DirWatcher {
+    fn default() -> Self {
+        DirWatcher::new()
+    }
+}
+
+impl DirWatcher {
+    pub fn new() -> DirWatcher {
+        let (sender, events) = mpsc::channel();
+        DirWatcher {
+            watcher: notify::recommended_watcher(sender).ok(),
+            watched: None,
+            events,
+        }
+    }
+
+    // Watch `dir` instead of the directory watched so far
+    pub fn watch(&mut self, dir: &Path) -> notify::Result<()> {
+        if self.watched.as_deref() == Some(dir) {
+            return Ok(());
+        }
+        let Some(watcher) = &mut self.watcher else {
+            return Err(notify::Error::generic("no file watcher available"));
+        };
+        if let Some(old) = self.watched.take() {
+            // The old directory may be gone, which drops its watch anyway
+            let _ = watcher.unwatch(&old);
+        }
+        // Events from the old directory still queued are no longer relevant
+        while self.events.try_recv().is_ok() {}
+        watcher.watch(dir, RecursiveMode::NonRecursive)?;
+        self.watched = Some(dir.to_path_buf());
+        Ok(())
+    }
+
+    // Take the queued events, returning whether any of them changed an entry
+    // of the watched directory or the contents of one of its files
+    // Opening and reading files doesn't count, so reading the directory
+    // doesn't set off another reload
+    pub fn changed(&self) -> bool {
+        let mut changed = false;
+        while let Ok(event) = self.events.try_recv() {
+            match event {
+                Ok(event) if matches!(event.kind, EventKind::Access(_)) => {}
+                // An error such as a queue overflow may have lost changes
+                _ => changed = true,
+            }
+        }
+        changed
+    }
+}